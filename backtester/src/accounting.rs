@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::broker::EPSILON;
+use crate::order::Side;
+
+/// Binance trading fees, optionally paid in BNB at a discount.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeSchedule {
+    /// Fraction of the traded notional, `0.001` is Binance's default 0.1%.
+    pub rate: f64,
+    /// Fraction taken off the fee when it is paid in BNB.
+    pub bnb_discount: f64,
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        Self {
+            rate: 0.001,
+            bnb_discount: 0.25,
+        }
+    }
+}
+
+impl FeeSchedule {
+    /// Fee for trading `notional`, in the quote asset.
+    pub fn fee(&self, notional: f64) -> f64 {
+        notional * self.rate
+    }
+
+    /// Fee for trading `notional` paid in BNB, `bnb_price` being the BNB price in the quote asset.
+    pub fn bnb_fee(&self, notional: f64, bnb_price: f64) -> f64 {
+        self.fee(notional) * (1.0 - self.bnb_discount) / bnb_price
+    }
+}
+
+/// One fill of `asset` against the quote asset of the ledger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    pub asset: String,
+    pub side: Side,
+    pub quantity: f64,
+    pub price: f64,
+    pub fee: f64,
+    /// Asset the fee was paid in: the quote, the traded asset or e.g. BNB.
+    pub fee_asset: String,
+    pub time: u64,
+}
+
+/// Holding of one asset, valued with the average cost method.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Position {
+    pub quantity: f64,
+    /// Average price paid for the current quantity, `0` when flat.
+    pub avg_entry: f64,
+    /// Gains of the sells against the average entry, before fees.
+    pub realized_pnl: f64,
+    /// Fees of the fills of this asset, valued in the quote asset when they were paid.
+    pub fees: f64,
+    pub last_price: f64,
+}
+
+impl Position {
+    pub fn unrealized_pnl(&self) -> f64 {
+        (self.last_price - self.avg_entry) * self.quantity
+    }
+
+    /// Realized and unrealized PnL after fees.
+    pub fn net_pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl() - self.fees
+    }
+
+    pub fn value(&self) -> f64 {
+        self.quantity * self.last_price
+    }
+}
+
+/// Balances, positions and PnL in one quote asset, built from fills and marked to the latest closes.
+///
+/// Used the same way by backtests and live trading: feed every [`Execution`] to [`Ledger::apply`] and
+/// the close of every candle to [`Ledger::mark`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Ledger {
+    quote: String,
+    balances: BTreeMap<String, f64>,
+    positions: BTreeMap<String, Position>,
+    /// Last price of each asset in the quote asset.
+    prices: BTreeMap<String, f64>,
+}
+
+impl Ledger {
+    pub fn new(quote: &str) -> Self {
+        Self {
+            quote: quote.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Sets what the account held before the first fill. Assets already held are carried at the first
+    /// known price, so they start without PnL.
+    pub fn with_balance(mut self, asset: &str, amount: f64) -> Self {
+        self.set_balance(asset, amount);
+        self
+    }
+
+    pub fn set_balance(&mut self, asset: &str, amount: f64) {
+        self.balances.insert(asset.to_string(), amount);
+        if asset != self.quote {
+            let price = self.price(asset).unwrap_or_default();
+            let position = self.positions.entry(asset.to_string()).or_default();
+            position.quantity = amount;
+            position.avg_entry = price;
+            position.last_price = price;
+        }
+    }
+
+    /// Overwrites the balance of `asset` with what the exchange reports, e.g. after a deposit.
+    /// Positions keep their quantity and cost basis, only fills change those.
+    pub fn sync_balance(&mut self, asset: &str, amount: f64) {
+        self.balances.insert(asset.to_string(), amount);
+    }
+
+    pub fn quote(&self) -> &str {
+        &self.quote
+    }
+
+    pub fn balance(&self, asset: &str) -> f64 {
+        self.balances.get(asset).copied().unwrap_or_default()
+    }
+
+    pub fn balances(&self) -> &BTreeMap<String, f64> {
+        &self.balances
+    }
+
+    pub fn position(&self, asset: &str) -> Option<&Position> {
+        self.positions.get(asset)
+    }
+
+    pub fn positions(&self) -> &BTreeMap<String, Position> {
+        &self.positions
+    }
+
+    /// Price of `asset` in the quote asset, `None` before it was marked.
+    pub fn price(&self, asset: &str) -> Option<f64> {
+        if asset == self.quote {
+            Some(1.0)
+        } else {
+            self.prices.get(asset).copied()
+        }
+    }
+
+    /// Updates the price of `asset`.
+    pub fn mark(&mut self, asset: &str, price: f64) {
+        self.prices.insert(asset.to_string(), price);
+        if let Some(position) = self.positions.get_mut(asset) {
+            if position.avg_entry == 0.0 && position.quantity > EPSILON {
+                // held before the first price was known
+                position.avg_entry = price;
+            }
+            position.last_price = price;
+        }
+    }
+
+    pub fn apply(&mut self, execution: &Execution) {
+        let Execution { asset, side, quantity, price, .. } = execution;
+        let notional = quantity * price;
+        let position = self.positions.entry(asset.clone()).or_default();
+        match side {
+            Side::Buy => {
+                let cost = position.avg_entry * position.quantity + notional;
+                position.quantity += quantity;
+                position.avg_entry = cost / position.quantity;
+                *self.balances.entry(asset.clone()).or_default() += quantity;
+                *self.balances.entry(self.quote.clone()).or_default() -= notional;
+            }
+            Side::Sell => {
+                // anything sold beyond the known position has no entry price, it realizes nothing
+                let closed = quantity.min(position.quantity);
+                position.realized_pnl += (price - position.avg_entry) * closed;
+                position.quantity -= closed;
+                if position.quantity <= EPSILON {
+                    position.quantity = 0.0;
+                    position.avg_entry = 0.0;
+                }
+                *self.balances.entry(asset.clone()).or_default() -= quantity;
+                *self.balances.entry(self.quote.clone()).or_default() += notional;
+            }
+        }
+        position.last_price = *price;
+        self.prices.insert(asset.clone(), *price);
+
+        let fee_price = self.price(&execution.fee_asset).unwrap_or_default();
+        let position = self.positions.get_mut(asset).expect("position was just created");
+        position.fees += execution.fee * fee_price;
+        if execution.fee_asset == *asset {
+            // fees in the traded asset shrink the position, its cost stays the same
+            position.quantity = (position.quantity - execution.fee).max(0.0);
+        } else if let Some(fee_position) = self.positions.get_mut(&execution.fee_asset) {
+            fee_position.quantity = (fee_position.quantity - execution.fee).max(0.0);
+        }
+        *self.balances.entry(execution.fee_asset.clone()).or_default() -= execution.fee;
+    }
+
+    pub fn realized_pnl(&self) -> f64 {
+        self.positions.values().map(|p| p.realized_pnl).sum()
+    }
+
+    pub fn unrealized_pnl(&self) -> f64 {
+        self.positions.values().map(Position::unrealized_pnl).sum()
+    }
+
+    /// Fees of every fill, in the quote asset.
+    pub fn fees(&self) -> f64 {
+        self.positions.values().map(|p| p.fees).sum()
+    }
+
+    /// Every balance valued in the quote asset, assets without a price count as zero.
+    pub fn equity(&self) -> f64 {
+        self.balances
+            .iter()
+            .map(|(asset, amount)| amount * self.price(asset).unwrap_or_default())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn execution(side: Side, quantity: f64, price: f64, fee: f64, fee_asset: &str) -> Execution {
+        Execution {
+            asset: "BTC".to_string(),
+            side,
+            quantity,
+            price,
+            fee,
+            fee_asset: fee_asset.to_string(),
+            time: 0,
+        }
+    }
+
+    #[test]
+    fn average_entry_and_pnl() {
+        let mut ledger = Ledger::new("USDT").with_balance("USDT", 1_000.0);
+        ledger.apply(&execution(Side::Buy, 2.0, 100.0, 0.2, "USDT"));
+        ledger.apply(&execution(Side::Buy, 2.0, 110.0, 0.22, "USDT"));
+        let position = ledger.position("BTC").unwrap();
+        assert_eq!((position.quantity, position.avg_entry), (4.0, 105.0));
+
+        ledger.mark("BTC", 120.0);
+        assert_eq!(ledger.unrealized_pnl(), 60.0);
+
+        ledger.apply(&execution(Side::Sell, 3.0, 115.0, 0.345, "USDT"));
+        let position = ledger.position("BTC").unwrap();
+        assert_eq!((position.quantity, position.avg_entry), (1.0, 105.0));
+        assert_eq!(position.realized_pnl, 30.0);
+        assert!((ledger.fees() - 0.765).abs() < 1e-9);
+        assert_eq!(ledger.unrealized_pnl(), 10.0);
+
+        // equity moves by exactly the PnL after fees
+        let cash = 1_000.0 - 200.2 - 220.22 + 344.655;
+        assert!((ledger.balance("USDT") - cash).abs() < 1e-9);
+        assert!((ledger.equity() - (cash + 115.0)).abs() < 1e-9);
+        assert!((ledger.equity() - 1_000.0 - position.net_pnl()).abs() < 1e-9);
+
+        ledger.apply(&execution(Side::Sell, 1.0, 100.0, 0.1, "USDT"));
+        let position = ledger.position("BTC").unwrap();
+        assert_eq!((position.quantity, position.avg_entry, position.realized_pnl), (0.0, 0.0, 25.0));
+    }
+
+    #[test]
+    fn fees_in_base_and_bnb() {
+        let mut ledger = Ledger::new("USDT").with_balance("USDT", 1_000.0);
+        ledger.apply(&execution(Side::Buy, 1.0, 100.0, 0.001, "BTC"));
+        let position = ledger.position("BTC").unwrap();
+        assert_eq!((position.quantity, position.avg_entry), (0.999, 100.0));
+        assert!((position.fees - 0.1).abs() < 1e-9);
+        assert!((ledger.equity() - 999.9).abs() < 1e-9);
+
+        let schedule = FeeSchedule::default();
+        ledger.set_balance("BNB", 1.0);
+        ledger.mark("BNB", 500.0);
+        let bnb_fee = schedule.bnb_fee(100.0, 500.0);
+        assert!((bnb_fee - 0.00015).abs() < 1e-12);
+        ledger.apply(&execution(Side::Sell, 0.999, 100.0, bnb_fee, "BNB"));
+        assert_eq!(ledger.balance("BNB"), 1.0 - bnb_fee);
+        assert!((ledger.position("BTC").unwrap().fees - 0.175).abs() < 1e-9);
+        assert!((ledger.equity() - (1_000.0 - 0.1 + 500.0 - 0.075)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,300 @@
+use data_downloader::Kline;
+use tracing::warn;
+
+use crate::accounting::{Execution, Ledger};
+use crate::order::{Fill, Order, OrderType, Side};
+
+// tolerance for float rounding when checking balances
+pub(crate) const EPSILON: f64 = 1e-9;
+
+/// Assets the brokers book in their ledger. They do not know the traded symbol, so the quote is named
+/// generically and the single-asset broker calls its position the base.
+pub(crate) const QUOTE: &str = "QUOTE";
+const BASE: &str = "BASE";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokerConfig {
+    /// Starting balance in the quote asset.
+    pub initial_cash: f64,
+    /// Fee as a fraction of the traded notional, `0.001` is Binance's default 0.1%.
+    pub fee_rate: f64,
+    /// Adverse price move applied to market and stop fills, as a fraction of the price.
+    pub slippage: f64,
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        Self {
+            initial_cash: 10_000.0,
+            fee_rate: 0.001,
+            slippage: 0.0,
+        }
+    }
+}
+
+/// Simulated spot account: a quote balance, a long-only base position and the resting orders.
+/// Fills are booked in a [`Ledger`], like the fills of live trading.
+#[derive(Debug, Clone)]
+pub struct Broker {
+    config: BrokerConfig,
+    ledger: Ledger,
+    open_orders: Vec<Order>,
+    next_order_id: u64,
+    time: u64,
+}
+
+impl Broker {
+    pub fn new(config: BrokerConfig) -> Self {
+        Self {
+            ledger: Ledger::new(QUOTE).with_balance(QUOTE, config.initial_cash),
+            config,
+            open_orders: Vec::new(),
+            next_order_id: 1,
+            time: 0,
+        }
+    }
+
+    pub fn config(&self) -> &BrokerConfig {
+        &self.config
+    }
+
+    /// Balances, PnL and fees of the fills so far. The quote asset is `QUOTE` and the position `BASE`.
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    pub fn cash(&self) -> f64 {
+        self.ledger.balance(QUOTE)
+    }
+
+    pub fn position(&self) -> f64 {
+        self.ledger.balance(BASE)
+    }
+
+    /// Close of the last processed bar.
+    pub fn last_price(&self) -> f64 {
+        self.ledger.price(BASE).unwrap_or_default()
+    }
+
+    /// Cash plus the position valued at the last close.
+    pub fn equity(&self) -> f64 {
+        self.ledger.equity()
+    }
+
+    pub fn open_orders(&self) -> &[Order] {
+        &self.open_orders
+    }
+
+    /// Queues an order and returns its id. It is matched starting with the next bar.
+    /// Quantities that are not a positive number are rejected with `None`.
+    pub fn submit(&mut self, side: Side, order_type: OrderType, quantity: f64) -> Option<u64> {
+        if !valid_quantity(quantity) {
+            warn!("Rejecting {side:?} order of {quantity}");
+            return None;
+        }
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+        self.open_orders.push(Order {
+            id,
+            side,
+            order_type,
+            quantity,
+            submitted_at: self.time,
+        });
+        Some(id)
+    }
+
+    pub fn cancel(&mut self, order_id: u64) -> bool {
+        let before = self.open_orders.len();
+        self.open_orders.retain(|o| o.id != order_id);
+        before != self.open_orders.len()
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.open_orders.clear();
+    }
+
+    /// Matches the resting orders against `bar` and returns the resulting fills.
+    /// Orders that can not be afforded when they trigger are rejected and dropped.
+    pub(crate) fn match_orders(&mut self, bar: &Kline) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        let orders = std::mem::take(&mut self.open_orders);
+
+        for order in orders {
+            let Some(price) = fill_price(&order, bar, self.config.slippage) else {
+                self.open_orders.push(order);
+                continue;
+            };
+
+            fills.extend(settle(&order, BASE, price, bar.open_time, self.config.fee_rate, &mut self.ledger));
+        }
+        fills
+    }
+
+    /// Records the close of `bar` as the current mark price.
+    pub(crate) fn mark(&mut self, bar: &Kline) {
+        self.time = bar.open_time;
+        self.ledger.mark(BASE, bar.close);
+    }
+}
+
+/// Books `order` of `asset` filled at `price` in `ledger`, paying the fee in the quote asset. Shared by
+/// the single and multi-asset brokers. Orders that can not be afforded are rejected with `None`.
+pub(crate) fn settle(order: &Order, asset: &str, price: f64, time: u64, fee_rate: f64, ledger: &mut Ledger) -> Option<Fill> {
+    let notional = order.quantity * price;
+    let fee = notional * fee_rate;
+    let cash = ledger.balance(ledger.quote());
+    let position = ledger.balance(asset);
+    match order.side {
+        Side::Buy if notional + fee > cash + EPSILON => {
+            warn!("Rejecting order {}: cost {} exceeds cash {}", order.id, notional + fee, cash);
+            return None;
+        }
+        Side::Sell if order.quantity > position + EPSILON => {
+            warn!("Rejecting order {}: quantity {} exceeds position {}", order.id, order.quantity, position);
+            return None;
+        }
+        _ => {}
+    }
+    ledger.apply(&Execution {
+        asset: asset.to_string(),
+        side: order.side,
+        quantity: order.quantity,
+        price,
+        fee,
+        fee_asset: ledger.quote().to_string(),
+        time,
+    });
+    Some(Fill {
+        order_id: order.id,
+        side: order.side,
+        quantity: order.quantity,
+        price,
+        fee,
+        time,
+    })
+}
+
+/// Whether `quantity` can be ordered: finite and above zero.
+pub(crate) fn valid_quantity(quantity: f64) -> bool {
+    quantity.is_finite() && quantity > 0.0
+}
+
+/// Price `order` executes at during `bar`, `None` if it does not trigger.
+pub(crate) fn fill_price(order: &Order, bar: &Kline, slippage: f64) -> Option<f64> {
+    match (order.order_type, order.side) {
+        (OrderType::Market, Side::Buy) => Some(bar.open * (1.0 + slippage)),
+        (OrderType::Market, Side::Sell) => Some(bar.open * (1.0 - slippage)),
+        (OrderType::Limit(limit), Side::Buy) if bar.low <= limit => Some(bar.open.min(limit)),
+        (OrderType::Limit(limit), Side::Sell) if bar.high >= limit => Some(bar.open.max(limit)),
+        (OrderType::Stop(stop), Side::Buy) if bar.high >= stop => Some(bar.open.max(stop) * (1.0 + slippage)),
+        (OrderType::Stop(stop), Side::Sell) if bar.low <= stop => Some(bar.open.min(stop) * (1.0 - slippage)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(open: f64, high: f64, low: f64, close: f64) -> Kline {
+        Kline {
+            open_time: 0,
+            open,
+            high,
+            low,
+            close,
+            volume: 1.0,
+            close_time: 59_999,
+            quote_asset_volume: close,
+            trade_number: 1,
+            buy_base: 0.5,
+            buy_quote: close / 2.0,
+        }
+    }
+
+    fn broker() -> Broker {
+        Broker::new(BrokerConfig { initial_cash: 1_000.0, fee_rate: 0.01, slippage: 0.0 })
+    }
+
+    #[test]
+    fn market_order_fills_at_open_with_fee() {
+        let mut broker = broker();
+        broker.submit(Side::Buy, OrderType::Market, 2.0);
+        let fills = broker.match_orders(&bar(100.0, 110.0, 90.0, 105.0));
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].price, 100.0);
+        assert_eq!(fills[0].fee, 2.0);
+        assert_eq!(broker.cash(), 798.0);
+        assert_eq!(broker.position(), 2.0);
+    }
+
+    #[test]
+    fn books_fills_in_the_ledger() {
+        let mut broker = broker();
+        broker.submit(Side::Buy, OrderType::Market, 2.0);
+        broker.match_orders(&bar(100.0, 100.0, 100.0, 100.0));
+        broker.submit(Side::Sell, OrderType::Market, 2.0);
+        broker.match_orders(&bar(110.0, 110.0, 110.0, 110.0));
+
+        let ledger = broker.ledger();
+        assert_eq!(ledger.realized_pnl(), 20.0);
+        assert!((ledger.fees() - 4.2).abs() < EPSILON);
+        assert!((broker.cash() - (1_000.0 + 20.0 - 4.2)).abs() < EPSILON);
+        assert_eq!(broker.position(), 0.0);
+    }
+
+    #[test]
+    fn limit_and_stop_orders_rest_until_triggered() {
+        let mut broker = broker();
+        let limit = broker.submit(Side::Buy, OrderType::Limit(95.0), 1.0).unwrap();
+        broker.submit(Side::Buy, OrderType::Stop(120.0), 1.0);
+
+        assert!(broker.match_orders(&bar(100.0, 110.0, 96.0, 105.0)).is_empty());
+        assert_eq!(broker.open_orders().len(), 2);
+
+        let fills = broker.match_orders(&bar(100.0, 125.0, 94.0, 105.0));
+        assert_eq!(fills.iter().map(|f| f.price).collect::<Vec<_>>(), vec![95.0, 120.0]);
+        assert_eq!(fills[0].order_id, limit);
+        assert!(broker.open_orders().is_empty());
+    }
+
+    #[test]
+    fn gaps_fill_at_the_open() {
+        let mut broker = broker();
+        broker.submit(Side::Buy, OrderType::Limit(95.0), 1.0);
+        let fills = broker.match_orders(&bar(90.0, 92.0, 88.0, 91.0));
+        assert_eq!(fills[0].price, 90.0);
+    }
+
+    #[test]
+    fn slippage_and_rejections() {
+        let mut broker = Broker::new(BrokerConfig { initial_cash: 1_000.0, fee_rate: 0.0, slippage: 0.01 });
+        broker.submit(Side::Sell, OrderType::Market, 1.0);
+        broker.submit(Side::Buy, OrderType::Market, 20.0);
+        broker.submit(Side::Buy, OrderType::Market, 1.0);
+        let fills = broker.match_orders(&bar(100.0, 100.0, 100.0, 100.0));
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].price, 101.0);
+        assert!(broker.open_orders().is_empty());
+    }
+
+    #[test]
+    fn rejects_quantities_that_are_not_positive() {
+        let mut broker = broker();
+        for quantity in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(broker.submit(Side::Buy, OrderType::Market, quantity), None);
+        }
+        assert!(broker.open_orders().is_empty());
+        assert!(broker.match_orders(&bar(100.0, 100.0, 100.0, 100.0)).is_empty());
+        assert_eq!((broker.cash(), broker.position()), (1_000.0, 0.0));
+    }
+
+    #[test]
+    fn cancel() {
+        let mut broker = broker();
+        let id = broker.submit(Side::Buy, OrderType::Limit(1.0), 1.0).unwrap();
+        assert!(broker.cancel(id));
+        assert!(!broker.cancel(id));
+    }
+}
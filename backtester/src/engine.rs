@@ -0,0 +1,201 @@
+use std::path::Path;
+
+use data_downloader::Kline;
+use polars::prelude::*;
+use tracing::info;
+
+use crate::broker::{Broker, BrokerConfig};
+use crate::error::Result;
+use crate::order::{Fill, Side};
+use crate::strategy::Strategy;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EquityPoint {
+    /// Close time of the bar.
+    pub time: u64,
+    pub equity: f64,
+    /// Base asset held at the close.
+    pub position: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestResult {
+    pub initial_cash: f64,
+    pub equity_curve: Vec<EquityPoint>,
+    pub trades: Vec<Fill>,
+}
+
+/// Replays klines through a [`Strategy`] against a simulated [`Broker`].
+pub struct Backtester<S: Strategy> {
+    strategy: S,
+    broker: Broker,
+    equity_curve: Vec<EquityPoint>,
+    trades: Vec<Fill>,
+}
+
+impl<S: Strategy> Backtester<S> {
+    pub fn new(strategy: S, config: BrokerConfig) -> Self {
+        Self {
+            strategy,
+            broker: Broker::new(config),
+            equity_curve: Vec::new(),
+            trades: Vec::new(),
+        }
+    }
+
+    pub fn strategy(&self) -> &S {
+        &self.strategy
+    }
+
+    pub fn broker(&self) -> &Broker {
+        &self.broker
+    }
+
+    /// Processes one closed bar: fills resting orders, marks the account and lets the strategy react.
+    pub fn step(&mut self, bar: &Kline) {
+        let fills = self.broker.match_orders(bar);
+        self.broker.mark(bar);
+        for fill in &fills {
+            self.strategy.on_fill(fill, &mut self.broker);
+        }
+        self.trades.extend(fills);
+
+        self.strategy.on_bar(bar, &mut self.broker);
+        self.equity_curve.push(EquityPoint {
+            time: bar.close_time,
+            equity: self.broker.equity(),
+            position: self.broker.position(),
+        });
+    }
+
+    pub fn run(mut self, data: &[Kline]) -> BacktestResult {
+        for bar in data {
+            self.step(bar);
+        }
+        self.into_result()
+    }
+
+    /// Replays a kline Parquet file written by the downloader.
+    pub fn run_file<P: AsRef<Path>>(self, path: P) -> Result<BacktestResult> {
+        let data = data_downloader::read_klines(path)?;
+        info!("Backtesting over {} bars", data.len());
+        Ok(self.run(&data))
+    }
+
+    pub fn into_result(self) -> BacktestResult {
+        BacktestResult {
+            initial_cash: self.broker.config().initial_cash,
+            equity_curve: self.equity_curve,
+            trades: self.trades,
+        }
+    }
+}
+
+impl BacktestResult {
+    pub fn final_equity(&self) -> f64 {
+        self.equity_curve.last().map_or(self.initial_cash, |p| p.equity)
+    }
+
+    pub fn equity_df(&self) -> Result<DataFrame> {
+        Ok(df!(
+            "time" => self.equity_curve.iter().map(|p| p.time).collect::<Vec<u64>>(),
+            "equity" => self.equity_curve.iter().map(|p| p.equity).collect::<Vec<f64>>(),
+            "position" => self.equity_curve.iter().map(|p| p.position).collect::<Vec<f64>>(),
+        )?)
+    }
+
+    pub fn trades_df(&self) -> Result<DataFrame> {
+        Ok(df!(
+            "time" => self.trades.iter().map(|f| f.time).collect::<Vec<u64>>(),
+            "order_id" => self.trades.iter().map(|f| f.order_id).collect::<Vec<u64>>(),
+            "side" => self.trades.iter().map(|f| match f.side { Side::Buy => "BUY", Side::Sell => "SELL" }).collect::<Vec<&str>>(),
+            "quantity" => self.trades.iter().map(|f| f.quantity).collect::<Vec<f64>>(),
+            "price" => self.trades.iter().map(|f| f.price).collect::<Vec<f64>>(),
+            "fee" => self.trades.iter().map(|f| f.fee).collect::<Vec<f64>>(),
+        )?)
+    }
+
+    /// Writes `equity.parquet` and `trades.parquet` into `dir`.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        info!("Saving backtest result to {}", dir.display());
+
+        let mut file = std::fs::File::create(dir.join("equity.parquet"))?;
+        ParquetWriter::new(&mut file).finish(&mut self.equity_df()?)?;
+        let mut file = std::fs::File::create(dir.join("trades.parquet"))?;
+        ParquetWriter::new(&mut file).finish(&mut self.trades_df()?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::OrderType;
+
+    fn bar(i: u64, open: f64, close: f64) -> Kline {
+        Kline {
+            open_time: i * 60_000,
+            open,
+            high: open.max(close),
+            low: open.min(close),
+            close,
+            volume: 1.0,
+            close_time: i * 60_000 + 59_999,
+            quote_asset_volume: close,
+            trade_number: 1,
+            buy_base: 0.5,
+            buy_quote: close / 2.0,
+        }
+    }
+
+    /// Buys on the first bar and sells on the third.
+    struct BuyThenSell {
+        bars: usize,
+        fills: usize,
+    }
+
+    impl Strategy for BuyThenSell {
+        fn on_bar(&mut self, _bar: &Kline, broker: &mut Broker) {
+            self.bars += 1;
+            match self.bars {
+                1 => { broker.submit(Side::Buy, OrderType::Market, 1.0); }
+                3 => { broker.submit(Side::Sell, OrderType::Market, broker.position()); }
+                _ => {}
+            }
+        }
+
+        fn on_fill(&mut self, _fill: &Fill, _broker: &mut Broker) {
+            self.fills += 1;
+        }
+    }
+
+    #[test]
+    fn orders_fill_on_the_next_bar() {
+        let data = vec![bar(0, 100.0, 101.0), bar(1, 102.0, 110.0), bar(2, 111.0, 120.0), bar(3, 121.0, 90.0)];
+        let config = BrokerConfig { initial_cash: 1_000.0, fee_rate: 0.0, slippage: 0.0 };
+        let mut backtester = Backtester::new(BuyThenSell { bars: 0, fills: 0 }, config);
+        for bar in &data {
+            backtester.step(bar);
+        }
+        assert_eq!(backtester.strategy().fills, 2);
+
+        let result = backtester.into_result();
+        assert_eq!(result.trades.iter().map(|f| f.price).collect::<Vec<_>>(), vec![102.0, 121.0]);
+        assert_eq!(
+            result.equity_curve.iter().map(|p| p.equity).collect::<Vec<_>>(),
+            vec![1_000.0, 1_008.0, 1_018.0, 1_019.0]
+        );
+        assert_eq!(result.equity_curve[0].time, 59_999);
+        assert_eq!(result.final_equity(), 1_019.0);
+    }
+
+    #[test]
+    fn result_frames() {
+        let data = vec![bar(0, 100.0, 101.0), bar(1, 102.0, 110.0)];
+        let result = Backtester::new(BuyThenSell { bars: 0, fills: 0 }, BrokerConfig::default()).run(&data);
+        assert_eq!(result.equity_df().unwrap().height(), 2);
+        assert_eq!(result.trades_df().unwrap().height(), 1);
+    }
+}
@@ -0,0 +1,43 @@
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Downloader(data_downloader::Error),
+    Polars(polars::error::PolarsError),
+    Io(std::io::Error),
+    Report(String),
+    /// The walk-forward windows can not move through the data.
+    WalkForward(String),
+    /// A parameter grid or space with nothing to pick from.
+    InvalidParams(String),
+}
+
+// region:    - Froms
+impl From<data_downloader::Error> for Error {
+    fn from(err: data_downloader::Error) -> Self {
+        Error::Downloader(err)
+    }
+}
+
+impl From<polars::error::PolarsError> for Error {
+    fn from(err: polars::error::PolarsError) -> Self {
+        Error::Polars(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+// endregion: - Froms
+
+// region:    - Error impl
+impl core::fmt::Display for Error {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for Error {}
+// endregion:  - Error impl
@@ -0,0 +1,160 @@
+use data_downloader::Kline;
+
+/// Streaming Wilder RSI, producing the same values as the `RSI[n]` feature column.
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    period: usize,
+    prev_close: Option<f64>,
+    diffs: usize,
+    avg_gain: f64,
+    avg_loss: f64,
+    value: Option<f64>,
+}
+
+impl Rsi {
+    /// # Panics
+    ///
+    /// If `period` is zero, the averages would be divided by it.
+    pub fn new(period: usize) -> Self {
+        assert!(period > 0, "the RSI period must be at least 1");
+        Self {
+            period,
+            prev_close: None,
+            diffs: 0,
+            avg_gain: 0.0,
+            avg_loss: 0.0,
+            value: None,
+        }
+    }
+
+    /// Feeds the next close and returns the RSI once `period` price changes were seen.
+    pub fn update(&mut self, close: f64) -> Option<f64> {
+        let prev = self.prev_close.replace(close)?;
+        let diff = close - prev;
+        let gain = if diff > 0.0 { diff } else { 0.0 };
+        let loss = if diff < 0.0 { -diff } else { 0.0 };
+        let period = self.period as f64;
+
+        self.diffs += 1;
+        if self.diffs <= self.period {
+            // seed with the simple average of the first `period` changes
+            self.avg_gain += gain / period;
+            self.avg_loss += loss / period;
+            if self.diffs < self.period {
+                return None;
+            }
+        } else {
+            self.avg_gain = (self.avg_gain * (period - 1.0) + gain) / period;
+            self.avg_loss = (self.avg_loss * (period - 1.0) + loss) / period;
+        }
+
+        let rs = self.avg_gain / self.avg_loss;
+        self.value = Some(100.0 - (100.0 / (1.0 + rs)));
+        self.value
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// Streaming Wilder ATR, the average true range of the last `period` bars.
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period: usize,
+    prev_close: Option<f64>,
+    bars: usize,
+    value: f64,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            prev_close: None,
+            bars: 0,
+            value: 0.0,
+        }
+    }
+
+    /// Feeds the next bar and returns the ATR once `period` bars were seen.
+    pub fn update(&mut self, bar: &Kline) -> Option<f64> {
+        let range = match self.prev_close.replace(bar.close) {
+            Some(prev) => (bar.high - bar.low).max((bar.high - prev).abs()).max((bar.low - prev).abs()),
+            None => bar.high - bar.low,
+        };
+        let period = self.period as f64;
+
+        self.bars += 1;
+        if self.bars <= self.period {
+            self.value += range / period;
+            if self.bars < self.period {
+                return None;
+            }
+        } else {
+            self.value = (self.value * (period - 1.0) + range) / period;
+        }
+        Some(self.value)
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        (self.bars >= self.period).then_some(self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_downloader::FeatureSpec;
+    use polars::prelude::*;
+
+    #[test]
+    fn matches_feature_column() {
+        let closes: Vec<f64> = (0..60).map(|i| 100.0 + (i as f64 * 0.7).sin() * 5.0 + i as f64 * 0.1).collect();
+        let mut df = df!("close" => &closes).unwrap();
+        FeatureSpec { rsi: vec![14], ..Default::default() }.apply(&mut df).unwrap();
+        let expected: Vec<Option<f64>> = df.column("RSI[14]").unwrap().f64().unwrap().into_iter().collect();
+
+        let mut rsi = Rsi::new(14);
+        let streamed: Vec<Option<f64>> = closes.iter().map(|c| rsi.update(*c)).collect();
+
+        assert_eq!(streamed.iter().filter(|v| v.is_none()).count(), 14);
+        for (s, e) in streamed.iter().zip(&expected) {
+            match (s, e) {
+                (Some(s), Some(e)) => assert!((s - e).abs() < 1e-9, "{s} != {e}"),
+                (None, None) => {}
+                _ => panic!("warmup differs: {s:?} vs {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "period must be at least 1")]
+    fn rejects_an_empty_rsi_period() {
+        Rsi::new(0);
+    }
+
+    #[test]
+    fn average_true_range() {
+        let bar = |high: f64, low: f64, close: f64| Kline {
+            open_time: 0,
+            open: close,
+            high,
+            low,
+            close,
+            volume: 1.0,
+            close_time: 59_999,
+            quote_asset_volume: close,
+            trade_number: 1,
+            buy_base: 0.5,
+            buy_quote: close / 2.0,
+        };
+        let mut atr = Atr::new(3);
+        assert_eq!(atr.update(&bar(12.0, 10.0, 11.0)), None);
+        // gap up, the range starts at the previous close
+        assert_eq!(atr.update(&bar(16.0, 14.0, 15.0)), None);
+        assert_eq!(atr.update(&bar(16.0, 14.0, 15.5)), Some(3.0));
+        assert_eq!(atr.update(&bar(17.5, 15.5, 17.0)), Some((3.0 * 2.0 + 2.0) / 3.0));
+        assert_eq!(atr.value(), Some(8.0 / 3.0));
+    }
+}
@@ -0,0 +1,31 @@
+// IAMbot Backtester
+// Replays stored klines through trading strategies
+
+#![crate_name = "backtester"]
+
+mod accounting;
+mod broker;
+mod engine;
+mod error;
+mod indicator;
+mod live;
+mod metrics;
+pub mod optimize;
+mod order;
+mod portfolio;
+mod report;
+pub mod sizing;
+mod strategy;
+pub mod strategies;
+
+pub use accounting::{Execution, FeeSchedule, Ledger, Position};
+pub use broker::{Broker, BrokerConfig};
+pub use engine::{BacktestResult, Backtester, EquityPoint};
+pub use error::{Error, Result};
+pub use indicator::{Atr, Rsi};
+pub use live::run_live;
+pub use metrics::{round_trips, Metrics, RoundTrip};
+pub use order::{Fill, Order, OrderType, Side};
+pub use portfolio::{PortfolioBacktester, PortfolioBroker, PortfolioResult, PortfolioStrategy, Timeline};
+pub use report::Report;
+pub use strategy::Strategy;
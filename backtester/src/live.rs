@@ -0,0 +1,48 @@
+use data_downloader::Kline;
+use tokio::sync::mpsc;
+use tracing::info;
+
+use crate::engine::Backtester;
+use crate::strategy::Strategy;
+
+/// Drives a backtester with closed candles arriving from a live stream, e.g. the one fed by
+/// `Requester::connect_to_ws`. Returns once the sending side is dropped.
+pub async fn run_live<S: Strategy>(backtester: &mut Backtester<S>, mut receiver: mpsc::Receiver<Kline>) {
+    while let Some(bar) = receiver.recv().await {
+        backtester.step(&bar);
+        info!("Bar {} closed at {}, equity {:.2}", bar.open_time, bar.close, backtester.broker().equity());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::BrokerConfig;
+    use crate::strategies::{RsiThreshold, RsiThresholdConfig};
+
+    #[tokio::test]
+    async fn consumes_the_channel() {
+        let (sender, receiver) = mpsc::channel(8);
+        let mut backtester = Backtester::new(RsiThreshold::new(RsiThresholdConfig::default()), BrokerConfig::default());
+        tokio::spawn(async move {
+            for i in 0..5u64 {
+                let kline = Kline {
+                    open_time: i * 60_000,
+                    open: 1.0,
+                    high: 1.0,
+                    low: 1.0,
+                    close: 1.0,
+                    volume: 1.0,
+                    close_time: i * 60_000 + 59_999,
+                    quote_asset_volume: 1.0,
+                    trade_number: 1,
+                    buy_base: 0.5,
+                    buy_quote: 0.5,
+                };
+                sender.send(kline).await.unwrap();
+            }
+        });
+        run_live(&mut backtester, receiver).await;
+        assert_eq!(backtester.into_result().equity_curve.len(), 5);
+    }
+}
@@ -0,0 +1,286 @@
+use serde::Serialize;
+
+use crate::engine::{BacktestResult, EquityPoint};
+use crate::order::{Fill, Side};
+
+const YEAR_MS: f64 = 365.0 * 86_400_000.0;
+// below this the position is considered flat
+const EPSILON: f64 = 1e-9;
+
+/// A position opened from flat and closed back to flat.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RoundTrip {
+    pub entry_time: u64,
+    pub exit_time: u64,
+    pub quantity: f64,
+    pub entry_price: f64,
+    pub exit_price: f64,
+    /// Net of fees, in the quote asset.
+    pub pnl: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Metrics {
+    pub initial_equity: f64,
+    pub final_equity: f64,
+    pub total_return: f64,
+    pub annualized_return: f64,
+    pub sharpe: f64,
+    pub sortino: f64,
+    pub calmar: f64,
+    /// Largest peak-to-trough loss as a fraction of the peak.
+    pub max_drawdown: f64,
+    /// Longest time spent below a previous equity peak.
+    pub max_drawdown_duration_ms: u64,
+    pub trades: usize,
+    pub win_rate: f64,
+    pub profit_factor: f64,
+    pub average_trade: f64,
+    /// Fraction of bars with an open position.
+    pub exposure: f64,
+    /// Traded notional divided by the average equity.
+    pub turnover: f64,
+    pub fees_paid: f64,
+}
+
+impl Metrics {
+    pub fn from_result(result: &BacktestResult) -> Self {
+        Self::compute(result.initial_cash, &result.equity_curve, &round_trips(&result.trades), &result.trades)
+    }
+
+    /// Metrics of any equity curve, given its closed round trips and all fills.
+    pub fn compute(initial: f64, curve: &[EquityPoint], round_trips: &[RoundTrip], fills: &[Fill]) -> Self {
+        let final_equity = curve.last().map_or(initial, |p| p.equity);
+
+        let returns: Vec<f64> = std::iter::once(initial)
+            .chain(curve.iter().map(|p| p.equity))
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|w| w[1] / w[0] - 1.0)
+            .collect();
+        let periods_per_year = periods_per_year(curve);
+
+        let total_return = final_equity / initial - 1.0;
+        let annualized_return = if returns.is_empty() {
+            0.0
+        } else {
+            (final_equity / initial).powf(periods_per_year / returns.len() as f64) - 1.0
+        };
+
+        let mean_return = mean(&returns);
+        let std_dev = mean_of(&returns, |r| (r - mean_return).powi(2)).sqrt();
+        let downside_dev = mean_of(&returns, |r| r.min(0.0).powi(2)).sqrt();
+        let sharpe = ratio(mean_return, std_dev) * periods_per_year.sqrt();
+        let sortino = ratio(mean_return, downside_dev) * periods_per_year.sqrt();
+
+        let (max_drawdown, max_drawdown_duration_ms) = max_drawdown(initial, curve);
+        let calmar = ratio(annualized_return, max_drawdown);
+
+        let pnls: Vec<f64> = round_trips.iter().map(|t| t.pnl).collect();
+        let gross_profit: f64 = pnls.iter().filter(|p| **p > 0.0).sum();
+        let gross_loss: f64 = -pnls.iter().filter(|p| **p < 0.0).sum::<f64>();
+        let win_rate = ratio(pnls.iter().filter(|p| **p > 0.0).count() as f64, pnls.len() as f64);
+        let profit_factor = if gross_loss > 0.0 {
+            gross_profit / gross_loss
+        } else if gross_profit > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+
+        let exposure = ratio(
+            curve.iter().filter(|p| p.position.abs() > EPSILON).count() as f64,
+            curve.len() as f64,
+        );
+        let traded: f64 = fills.iter().map(Fill::notional).sum();
+        let turnover = ratio(traded, mean_of(curve, |p| p.equity));
+
+        Metrics {
+            initial_equity: initial,
+            final_equity,
+            total_return,
+            annualized_return,
+            sharpe,
+            sortino,
+            calmar,
+            max_drawdown,
+            max_drawdown_duration_ms,
+            trades: round_trips.len(),
+            win_rate,
+            profit_factor,
+            average_trade: mean(&pnls),
+            exposure,
+            turnover,
+            fees_paid: fills.iter().map(|f| f.fee).sum(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Metrics are always serializable")
+    }
+}
+
+/// Pairs the fills of a long-only account into round trips.
+pub fn round_trips(fills: &[Fill]) -> Vec<RoundTrip> {
+    let mut trips = Vec::new();
+    let mut position = 0.0;
+    let mut entry_time = 0;
+    let (mut bought, mut bought_qty, mut sold, mut sold_qty, mut fees) = (0.0, 0.0, 0.0, 0.0, 0.0);
+
+    for fill in fills {
+        if position < EPSILON {
+            entry_time = fill.time;
+            (bought, bought_qty, sold, sold_qty, fees) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        }
+        fees += fill.fee;
+        match fill.side {
+            Side::Buy => {
+                position += fill.quantity;
+                bought += fill.notional();
+                bought_qty += fill.quantity;
+            }
+            Side::Sell => {
+                position -= fill.quantity;
+                sold += fill.notional();
+                sold_qty += fill.quantity;
+            }
+        }
+        if position < EPSILON && bought_qty > 0.0 {
+            trips.push(RoundTrip {
+                entry_time,
+                exit_time: fill.time,
+                quantity: bought_qty,
+                entry_price: bought / bought_qty,
+                exit_price: ratio(sold, sold_qty),
+                pnl: sold - bought - fees,
+            });
+            position = 0.0;
+        }
+    }
+    trips
+}
+
+/// Drawdown of every equity point, as a fraction of the running peak.
+pub fn drawdowns(initial: f64, curve: &[EquityPoint]) -> Vec<f64> {
+    let mut peak = initial;
+    curve
+        .iter()
+        .map(|p| {
+            peak = peak.max(p.equity);
+            ratio(peak - p.equity, peak)
+        })
+        .collect()
+}
+
+fn max_drawdown(initial: f64, curve: &[EquityPoint]) -> (f64, u64) {
+    let max = drawdowns(initial, curve).into_iter().fold(0.0, f64::max);
+
+    let mut peak = initial;
+    let mut peak_time = curve.first().map_or(0, |p| p.time);
+    let mut longest = 0;
+    for point in curve {
+        if point.equity >= peak {
+            peak = point.equity;
+            peak_time = point.time;
+        } else {
+            longest = longest.max(point.time - peak_time);
+        }
+    }
+    (max, longest)
+}
+
+fn periods_per_year(curve: &[EquityPoint]) -> f64 {
+    let mut spacings: Vec<u64> = curve.windows(2).map(|w| w[1].time - w[0].time).collect();
+    if spacings.is_empty() {
+        return 0.0;
+    }
+    spacings.sort_unstable();
+    YEAR_MS / spacings[spacings.len() / 2] as f64
+}
+
+fn mean(values: &[f64]) -> f64 {
+    mean_of(values, |v| *v)
+}
+
+fn mean_of<T>(values: &[T], f: impl Fn(&T) -> f64) -> f64 {
+    ratio(values.iter().map(f).sum(), values.len() as f64)
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator == 0.0 { 0.0 } else { numerator / denominator }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86_400_000;
+
+    fn fill(time: u64, side: Side, quantity: f64, price: f64, fee: f64) -> Fill {
+        Fill { order_id: time, side, quantity, price, fee, time }
+    }
+
+    fn result(equity: &[f64], trades: Vec<Fill>) -> BacktestResult {
+        BacktestResult {
+            initial_cash: 100.0,
+            equity_curve: equity
+                .iter()
+                .enumerate()
+                .map(|(i, e)| EquityPoint { time: i as u64 * DAY, equity: *e, position: if i % 2 == 0 { 1.0 } else { 0.0 } })
+                .collect(),
+            trades,
+        }
+    }
+
+    #[test]
+    fn pairs_round_trips() {
+        let trips = round_trips(&[
+            fill(0, Side::Buy, 1.0, 10.0, 0.1),
+            fill(1, Side::Buy, 1.0, 20.0, 0.1),
+            fill(2, Side::Sell, 2.0, 18.0, 0.2),
+            fill(3, Side::Buy, 1.0, 10.0, 0.0),
+            fill(4, Side::Sell, 1.0, 9.0, 0.0),
+            fill(5, Side::Buy, 1.0, 9.0, 0.0),
+        ]);
+        assert_eq!(trips.len(), 2);
+        assert_eq!(trips[0].entry_price, 15.0);
+        assert_eq!(trips[0].exit_time, 2);
+        assert!((trips[0].pnl - 5.6).abs() < 1e-9);
+        assert_eq!(trips[1].pnl, -1.0);
+    }
+
+    #[test]
+    fn drawdown_depth_and_duration() {
+        let metrics = Metrics::from_result(&result(&[110.0, 99.0, 105.0, 121.0, 120.0], vec![]));
+        assert!((metrics.max_drawdown - 0.1).abs() < 1e-9);
+        assert_eq!(metrics.max_drawdown_duration_ms, 2 * DAY);
+        assert!((metrics.total_return - 0.2).abs() < 1e-9);
+        assert_eq!(metrics.exposure, 0.6);
+    }
+
+    #[test]
+    fn trade_statistics() {
+        let trades = vec![
+            fill(0, Side::Buy, 1.0, 10.0, 0.5),
+            fill(DAY, Side::Sell, 1.0, 14.0, 0.5),
+            fill(2 * DAY, Side::Buy, 1.0, 10.0, 0.0),
+            fill(3 * DAY, Side::Sell, 1.0, 9.0, 0.0),
+        ];
+        let metrics = Metrics::from_result(&result(&[100.0, 103.0, 103.0, 102.0], trades));
+        assert_eq!(metrics.trades, 2);
+        assert_eq!(metrics.win_rate, 0.5);
+        assert_eq!(metrics.profit_factor, 3.0);
+        assert_eq!(metrics.average_trade, 1.0);
+        assert_eq!(metrics.fees_paid, 1.0);
+        assert!(metrics.sharpe > 0.0);
+        assert!(metrics.to_json().contains("\"profit_factor\": 3.0"));
+    }
+
+    #[test]
+    fn empty_result() {
+        let metrics = Metrics::from_result(&result(&[], vec![]));
+        assert_eq!(metrics.total_return, 0.0);
+        assert_eq!(metrics.sharpe, 0.0);
+        assert_eq!(metrics.trades, 0);
+    }
+}
@@ -0,0 +1,427 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use data_downloader::Kline;
+use polars::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use tracing::info;
+
+use crate::broker::BrokerConfig;
+use crate::engine::Backtester;
+use crate::error::{Error, Result};
+use crate::metrics::Metrics;
+use crate::strategy::Strategy;
+
+/// Named strategy parameters, e.g. `period`, `buy_below`, `sell_above`.
+pub type Params = BTreeMap<String, f64>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    TotalReturn,
+    Sharpe,
+    Sortino,
+    Calmar,
+    ProfitFactor,
+}
+
+impl Objective {
+    pub fn score(&self, metrics: &Metrics) -> f64 {
+        let score = match self {
+            Objective::TotalReturn => metrics.total_return,
+            Objective::Sharpe => metrics.sharpe,
+            Objective::Sortino => metrics.sortino,
+            Objective::Calmar => metrics.calmar,
+            Objective::ProfitFactor => metrics.profit_factor,
+        };
+        // keeps the ranking total: a profit factor without losing trades is infinitely good,
+        // NaN (nothing to measure) is as bad as it gets
+        if score.is_nan() {
+            f64::MIN
+        } else {
+            score.clamp(f64::MIN, f64::MAX)
+        }
+    }
+}
+
+/// Every combination of the listed values is evaluated.
+#[derive(Debug, Clone, Default)]
+pub struct ParamGrid {
+    values: BTreeMap<String, Vec<f64>>,
+}
+
+impl ParamGrid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn param(mut self, name: &str, values: &[f64]) -> Self {
+        self.values.insert(name.to_string(), values.to_vec());
+        self
+    }
+
+    /// Fails for a parameter without values, no combination would be left.
+    pub fn combinations(&self) -> Result<Vec<Params>> {
+        if let Some((name, _)) = self.values.iter().find(|(_, values)| values.is_empty()) {
+            return Err(Error::InvalidParams(format!("{name} has no values")));
+        }
+        Ok(self.values.iter().fold(vec![Params::new()], |combos, (name, values)| {
+            combos
+                .iter()
+                .flat_map(|combo| values.iter().map(move |v| {
+                    let mut combo = combo.clone();
+                    combo.insert(name.clone(), *v);
+                    combo
+                }))
+                .collect()
+        }))
+    }
+}
+
+/// Uniform ranges sampled by random search. Integer parameters are rounded.
+#[derive(Debug, Clone, Default)]
+pub struct ParamSpace {
+    ranges: BTreeMap<String, (f64, f64, bool)>,
+}
+
+impl ParamSpace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn float(mut self, name: &str, min: f64, max: f64) -> Self {
+        self.ranges.insert(name.to_string(), (min, max, false));
+        self
+    }
+
+    pub fn integer(mut self, name: &str, min: i64, max: i64) -> Self {
+        self.ranges.insert(name.to_string(), (min as f64, max as f64, true));
+        self
+    }
+
+    /// Fails for ranges that are empty or not finite, e.g. a minimum above the maximum.
+    pub fn sample(&self, samples: usize, seed: u64) -> Result<Vec<Params>> {
+        if let Some((name, (min, max, _))) = self.ranges.iter().find(|(_, (min, max, _))| !(min.is_finite() && max.is_finite() && min <= max)) {
+            return Err(Error::InvalidParams(format!("{name} has the empty range {min}..={max}")));
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        Ok((0..samples)
+            .map(|_| {
+                self.ranges
+                    .iter()
+                    .map(|(name, &(min, max, integer))| {
+                        let value = if integer {
+                            rng.gen_range(min as i64..=max as i64) as f64
+                        } else {
+                            rng.gen_range(min..=max)
+                        };
+                        (name.clone(), value)
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    pub params: Params,
+    pub metrics: Metrics,
+    pub score: f64,
+}
+
+/// Rolling in-sample/out-of-sample windows over the `open_time` range of the data.
+/// Each window starts `out_of_sample_ms` after the previous one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WalkForward {
+    pub in_sample_ms: u64,
+    pub out_of_sample_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkForwardWindow {
+    pub in_sample_start: u64,
+    pub out_of_sample_start: u64,
+    pub out_of_sample_end: u64,
+    /// Best in-sample parameters, replayed over the out-of-sample range.
+    pub best: Evaluation,
+    pub out_of_sample: Metrics,
+}
+
+/// Runs backtests for many parameter sets in parallel.
+#[derive(Debug, Clone)]
+pub struct Optimizer {
+    pub broker: BrokerConfig,
+    pub objective: Objective,
+}
+
+impl Optimizer {
+    pub fn new(broker: BrokerConfig, objective: Objective) -> Self {
+        Self { broker, objective }
+    }
+
+    pub fn evaluate<S, F>(&self, data: &[Kline], params: &Params, build: &F) -> Evaluation
+    where
+        S: Strategy,
+        F: Fn(&Params) -> S,
+    {
+        let result = Backtester::new(build(params), self.broker.clone()).run(data);
+        let metrics = Metrics::from_result(&result);
+        Evaluation {
+            params: params.clone(),
+            score: self.objective.score(&metrics),
+            metrics,
+        }
+    }
+
+    /// Evaluates every parameter set and returns them ranked, best first.
+    pub fn search<S, F>(&self, data: &[Kline], candidates: &[Params], build: &F) -> Vec<Evaluation>
+    where
+        S: Strategy,
+        F: Fn(&Params) -> S + Sync,
+    {
+        info!("Evaluating {} parameter sets over {} bars", candidates.len(), data.len());
+        let mut evaluations: Vec<Evaluation> = candidates
+            .par_iter()
+            .map(|params| self.evaluate(data, params, build))
+            .collect();
+        evaluations.sort_by(|a, b| b.score.total_cmp(&a.score));
+        evaluations
+    }
+
+    pub fn grid_search<S, F>(&self, data: &[Kline], grid: &ParamGrid, build: &F) -> Result<Vec<Evaluation>>
+    where
+        S: Strategy,
+        F: Fn(&Params) -> S + Sync,
+    {
+        Ok(self.search(data, &grid.combinations()?, build))
+    }
+
+    pub fn random_search<S, F>(&self, data: &[Kline], space: &ParamSpace, samples: usize, seed: u64, build: &F) -> Result<Vec<Evaluation>>
+    where
+        S: Strategy,
+        F: Fn(&Params) -> S + Sync,
+    {
+        Ok(self.search(data, &space.sample(samples, seed)?, build))
+    }
+
+    /// Optimizes over each in-sample window and replays the winner out-of-sample.
+    /// Fails for windows of zero length, they would never move forward.
+    pub fn walk_forward<S, F>(&self, data: &[Kline], candidates: &[Params], windows: WalkForward, build: &F) -> Result<Vec<WalkForwardWindow>>
+    where
+        S: Strategy,
+        F: Fn(&Params) -> S + Sync,
+    {
+        if windows.in_sample_ms == 0 || windows.out_of_sample_ms == 0 {
+            return Err(Error::WalkForward(format!("windows must not be empty: {windows:?}")));
+        }
+        let (Some(first), Some(last)) = (data.first(), data.last()) else {
+            return Ok(Vec::new());
+        };
+        let slice = |from: u64, to: u64| {
+            let start = data.partition_point(|k| k.open_time < from);
+            let end = data.partition_point(|k| k.open_time < to);
+            &data[start..end]
+        };
+
+        let mut results = Vec::new();
+        let mut start = first.open_time;
+        while start + windows.in_sample_ms <= last.open_time {
+            let split = start + windows.in_sample_ms;
+            let end = split + windows.out_of_sample_ms;
+
+            let Some(best) = self.search(slice(start, split), candidates, build).into_iter().next() else {
+                break;
+            };
+            let out_of_sample = self.evaluate(slice(split, end), &best.params, build).metrics;
+            results.push(WalkForwardWindow {
+                in_sample_start: start,
+                out_of_sample_start: split,
+                out_of_sample_end: end,
+                best,
+                out_of_sample,
+            });
+            start += windows.out_of_sample_ms;
+        }
+        Ok(results)
+    }
+}
+
+/// Ranked results table: one row per parameter set, parameters and metrics as columns.
+pub fn evaluations_df(evaluations: &[Evaluation]) -> Result<DataFrame> {
+    let mut columns = vec![
+        Series::new("rank", (1..=evaluations.len() as u32).collect::<Vec<u32>>()),
+        Series::new("score", evaluations.iter().map(|e| e.score).collect::<Vec<f64>>()),
+    ];
+    columns.extend(param_columns(evaluations.iter().map(|e| &e.params)));
+    columns.extend(metric_columns("", evaluations.iter().map(|e| &e.metrics)));
+    Ok(DataFrame::new(columns)?)
+}
+
+pub fn walk_forward_df(windows: &[WalkForwardWindow]) -> Result<DataFrame> {
+    let mut columns = vec![
+        Series::new("in_sample_start", windows.iter().map(|w| w.in_sample_start).collect::<Vec<u64>>()),
+        Series::new("out_of_sample_start", windows.iter().map(|w| w.out_of_sample_start).collect::<Vec<u64>>()),
+        Series::new("out_of_sample_end", windows.iter().map(|w| w.out_of_sample_end).collect::<Vec<u64>>()),
+        Series::new("in_sample_score", windows.iter().map(|w| w.best.score).collect::<Vec<f64>>()),
+    ];
+    columns.extend(param_columns(windows.iter().map(|w| &w.best.params)));
+    columns.extend(metric_columns("oos_", windows.iter().map(|w| &w.out_of_sample)));
+    Ok(DataFrame::new(columns)?)
+}
+
+pub fn write_df<P: AsRef<Path>>(path: P, df: &mut DataFrame) -> Result<()> {
+    info!("Saving optimization results to {}", path.as_ref().display());
+    let mut file = std::fs::File::create(path)?;
+    ParquetWriter::new(&mut file).finish(df)?;
+    Ok(())
+}
+
+fn param_columns<'a>(params: impl Iterator<Item = &'a Params> + Clone) -> Vec<Series> {
+    let mut names: Vec<&String> = params.clone().flat_map(|p| p.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| Series::new(name, params.clone().map(|p| p.get(name).copied()).collect::<Vec<Option<f64>>>()))
+        .collect()
+}
+
+fn metric_columns<'a>(prefix: &str, metrics: impl Iterator<Item = &'a Metrics> + Clone) -> Vec<Series> {
+    let column = |name: &str, f: fn(&Metrics) -> f64| {
+        Series::new(&format!("{prefix}{name}"), metrics.clone().map(f).collect::<Vec<f64>>())
+    };
+    vec![
+        column("total_return", |m| m.total_return),
+        column("annualized_return", |m| m.annualized_return),
+        column("sharpe", |m| m.sharpe),
+        column("sortino", |m| m.sortino),
+        column("calmar", |m| m.calmar),
+        column("max_drawdown", |m| m.max_drawdown),
+        column("win_rate", |m| m.win_rate),
+        column("profit_factor", |m| m.profit_factor),
+        column("trades", |m| m.trades as f64),
+        column("fees_paid", |m| m.fees_paid),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{BacktestResult, EquityPoint};
+    use crate::order::{Fill, Side};
+    use crate::strategies::{RsiThreshold, RsiThresholdConfig};
+
+    const HOUR: u64 = 3_600_000;
+
+    fn data() -> Vec<Kline> {
+        (0..400u64)
+            .map(|i| {
+                let close = 100.0 + (i as f64 / 6.0).sin() * 10.0;
+                Kline {
+                    open_time: i * HOUR,
+                    open: close,
+                    high: close + 0.5,
+                    low: close - 0.5,
+                    close,
+                    volume: 1.0,
+                    close_time: (i + 1) * HOUR - 1,
+                    quote_asset_volume: close,
+                    trade_number: 1,
+                    buy_base: 0.5,
+                    buy_quote: close / 2.0,
+                }
+            })
+            .collect()
+    }
+
+    fn build(params: &Params) -> RsiThreshold {
+        RsiThreshold::new(RsiThresholdConfig::from_params(params))
+    }
+
+    #[test]
+    fn grid_combinations() {
+        let grid = ParamGrid::new().param("period", &[7.0, 14.0]).param("buy_below", &[20.0, 25.0, 30.0]);
+        let combos = grid.combinations().unwrap();
+        assert_eq!(combos.len(), 6);
+        assert!(combos.iter().all(|c| c.len() == 2));
+        assert!(matches!(grid.param("sell_above", &[]).combinations(), Err(Error::InvalidParams(_))));
+    }
+
+    #[test]
+    fn random_samples_are_reproducible() {
+        let space = ParamSpace::new().integer("period", 5, 30).float("buy_below", 10.0, 40.0);
+        let a = space.sample(20, 7).unwrap();
+        assert_eq!(a, space.sample(20, 7).unwrap());
+        assert!(a.iter().all(|p| p["period"].fract() == 0.0 && (5.0..=30.0).contains(&p["period"])));
+        assert!(a.iter().all(|p| (10.0..=40.0).contains(&p["buy_below"])));
+    }
+
+    #[test]
+    fn rejects_empty_ranges() {
+        for space in [
+            ParamSpace::new().integer("period", 30, 5),
+            ParamSpace::new().float("buy_below", 40.0, 10.0),
+            ParamSpace::new().float("buy_below", f64::NAN, 10.0),
+        ] {
+            assert!(matches!(space.sample(1, 7), Err(Error::InvalidParams(_))));
+        }
+        assert_eq!(ParamSpace::new().integer("period", 14, 14).sample(2, 7).unwrap()[1]["period"], 14.0);
+    }
+
+    #[test]
+    fn search_is_ranked() {
+        let optimizer = Optimizer::new(BrokerConfig::default(), Objective::TotalReturn);
+        let grid = ParamGrid::new().param("period", &[3.0, 5.0, 8.0]).param("sell_above", &[60.0, 70.0]);
+        let evaluations = optimizer.grid_search(&data(), &grid, &build).unwrap();
+        assert_eq!(evaluations.len(), 6);
+        assert!(evaluations.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let df = evaluations_df(&evaluations).unwrap();
+        assert_eq!(df.height(), 6);
+        assert!(df.column("period").is_ok());
+        assert!(df.column("sharpe").is_ok());
+    }
+
+    #[test]
+    fn walk_forward_windows() {
+        let optimizer = Optimizer::new(BrokerConfig::default(), Objective::TotalReturn);
+        let candidates = ParamGrid::new().param("period", &[3.0, 6.0]).combinations().unwrap();
+        let windows = optimizer.walk_forward(
+            &data(),
+            &candidates,
+            WalkForward { in_sample_ms: 200 * HOUR, out_of_sample_ms: 50 * HOUR },
+            &build,
+        ).unwrap();
+        assert_eq!(windows.len(), 4);
+        assert_eq!(windows[1].in_sample_start, 50 * HOUR);
+        assert_eq!(windows[1].out_of_sample_start, 250 * HOUR);
+        assert_eq!(walk_forward_df(&windows).unwrap().height(), 4);
+
+        for empty in [WalkForward { in_sample_ms: 200 * HOUR, out_of_sample_ms: 0 }, WalkForward { in_sample_ms: 0, out_of_sample_ms: 50 * HOUR }] {
+            assert!(matches!(optimizer.walk_forward(&data(), &candidates, empty, &build), Err(Error::WalkForward(_))));
+        }
+    }
+
+    #[test]
+    fn no_losing_trades_rank_first() {
+        let point = |time, equity| EquityPoint { time, equity, position: 0.0 };
+        let result = BacktestResult {
+            initial_cash: 100.0,
+            equity_curve: vec![point(0, 100.0), point(HOUR, 101.0)],
+            trades: vec![
+                Fill { order_id: 1, side: Side::Buy, quantity: 1.0, price: 10.0, fee: 0.0, time: 0 },
+                Fill { order_id: 2, side: Side::Sell, quantity: 1.0, price: 11.0, fee: 0.0, time: HOUR },
+            ],
+        };
+        let no_losses = Metrics::from_result(&result);
+        assert_eq!(no_losses.profit_factor, f64::INFINITY);
+        assert_eq!(Objective::ProfitFactor.score(&no_losses), f64::MAX);
+
+        let broken = Metrics { profit_factor: f64::NAN, ..no_losses.clone() };
+        let losing = Metrics { profit_factor: f64::NEG_INFINITY, ..no_losses };
+        assert_eq!(Objective::ProfitFactor.score(&broken), f64::MIN);
+        assert_eq!(Objective::ProfitFactor.score(&losing), f64::MIN);
+    }
+}
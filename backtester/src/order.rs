@@ -0,0 +1,42 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderType {
+    /// Fills at the open of the next bar.
+    Market,
+    /// Fills once the price trades at the limit or better.
+    Limit(f64),
+    /// Becomes a market order once the price trades through the stop.
+    Stop(f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    pub id: u64,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub quantity: f64,
+    /// Open time of the bar the order was submitted on.
+    pub submitted_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub order_id: u64,
+    pub side: Side,
+    pub quantity: f64,
+    pub price: f64,
+    /// Paid in the quote asset.
+    pub fee: f64,
+    pub time: u64,
+}
+
+impl Fill {
+    pub fn notional(&self) -> f64 {
+        self.quantity * self.price
+    }
+}
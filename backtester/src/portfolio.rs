@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use data_downloader::Kline;
+use tracing::{info, warn};
+
+use crate::accounting::Ledger;
+use crate::broker::{fill_price, settle, valid_quantity, BrokerConfig, EPSILON, QUOTE};
+use crate::engine::EquityPoint;
+use crate::error::Result;
+use crate::metrics::{round_trips, Metrics, RoundTrip};
+use crate::order::{Fill, Order, OrderType, Side};
+
+/// Bars of every symbol merged on `open_time`.
+///
+/// A slice only holds the symbols that have a bar at that time, so symbols listed later
+/// or with gaps in their history simply do not show up in the affected slices.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    slices: BTreeMap<u64, BTreeMap<String, Kline>>,
+}
+
+impl Timeline {
+    pub fn new(data: BTreeMap<String, Vec<Kline>>) -> Self {
+        let mut slices: BTreeMap<u64, BTreeMap<String, Kline>> = BTreeMap::new();
+        for (symbol, klines) in data {
+            for kline in klines {
+                slices.entry(kline.open_time).or_default().insert(symbol.clone(), kline);
+            }
+        }
+        Self { slices }
+    }
+
+    /// Loads one kline Parquet file per symbol.
+    pub fn load<P: AsRef<Path>>(files: &[(&str, P)]) -> Result<Self> {
+        let mut data = BTreeMap::new();
+        for (symbol, path) in files {
+            data.insert(symbol.to_string(), data_downloader::read_klines(path)?);
+        }
+        Ok(Self::new(data))
+    }
+
+    pub fn len(&self) -> usize {
+        self.slices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slices.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &BTreeMap<String, Kline>)> {
+        self.slices.iter().map(|(time, bars)| (*time, bars))
+    }
+}
+
+/// Simulated spot account trading several symbols out of one quote balance.
+/// Fills are booked in a [`Ledger`] holding every symbol as an asset.
+#[derive(Debug, Clone)]
+pub struct PortfolioBroker {
+    config: BrokerConfig,
+    ledger: Ledger,
+    open_orders: Vec<(String, Order)>,
+    next_order_id: u64,
+    time: u64,
+}
+
+impl PortfolioBroker {
+    pub fn new(config: BrokerConfig) -> Self {
+        Self {
+            ledger: Ledger::new(QUOTE).with_balance(QUOTE, config.initial_cash),
+            config,
+            open_orders: Vec::new(),
+            next_order_id: 1,
+            time: 0,
+        }
+    }
+
+    pub fn config(&self) -> &BrokerConfig {
+        &self.config
+    }
+
+    /// Balances, PnL and fees of the fills so far, the quote asset is `QUOTE`.
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    pub fn cash(&self) -> f64 {
+        self.ledger.balance(QUOTE)
+    }
+
+    pub fn position(&self, symbol: &str) -> f64 {
+        self.ledger.balance(symbol)
+    }
+
+    /// Quantity held per symbol, without the symbols sold off.
+    pub fn positions(&self) -> BTreeMap<String, f64> {
+        self.ledger
+            .balances()
+            .iter()
+            .filter(|(asset, qty)| *asset != QUOTE && qty.abs() >= EPSILON)
+            .map(|(asset, qty)| (asset.clone(), *qty))
+            .collect()
+    }
+
+    /// Last close seen for `symbol`, carried over slices where it has no bar.
+    pub fn last_price(&self, symbol: &str) -> Option<f64> {
+        self.ledger.price(symbol)
+    }
+
+    /// Quote value of all positions at their last close.
+    pub fn invested(&self) -> f64 {
+        self.equity() - self.cash()
+    }
+
+    pub fn equity(&self) -> f64 {
+        self.ledger.equity()
+    }
+
+    pub fn open_orders(&self) -> &[(String, Order)] {
+        &self.open_orders
+    }
+
+    /// Queues an order for `symbol`, see [`crate::Broker::submit`].
+    pub fn submit(&mut self, symbol: &str, side: Side, order_type: OrderType, quantity: f64) -> Option<u64> {
+        if !valid_quantity(quantity) {
+            warn!("Rejecting {side:?} order of {quantity} {symbol}");
+            return None;
+        }
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+        self.open_orders.push((symbol.to_string(), Order {
+            id,
+            side,
+            order_type,
+            quantity,
+            submitted_at: self.time,
+        }));
+        Some(id)
+    }
+
+    pub fn cancel(&mut self, order_id: u64) -> bool {
+        let before = self.open_orders.len();
+        self.open_orders.retain(|(_, o)| o.id != order_id);
+        before != self.open_orders.len()
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.open_orders.clear();
+    }
+
+    /// Replaces the open orders with market orders moving every symbol to `weights`,
+    /// given as fractions of the current equity. Held symbols missing from `weights` are sold.
+    pub fn rebalance(&mut self, weights: &BTreeMap<String, f64>) {
+        self.cancel_all();
+        let equity = self.equity();
+        // buys are sized so the fees of the whole rebalance still fit into the cash
+        let cost = 1.0 + self.config.fee_rate + self.config.slippage;
+
+        let mut symbols: Vec<String> = weights.keys().cloned().chain(self.positions().into_keys()).collect();
+        symbols.sort();
+        symbols.dedup();
+
+        let mut buys = Vec::new();
+        for symbol in symbols {
+            let Some(price) = self.last_price(&symbol).filter(|p| *p > 0.0) else {
+                warn!("No price for {symbol}, skipping it in the rebalance");
+                continue;
+            };
+            let target = equity * weights.get(&symbol).copied().unwrap_or_default();
+            let current = self.position(&symbol) * price;
+            let diff = target - current;
+            if diff < -EPSILON {
+                let quantity = (-diff / price).min(self.position(&symbol));
+                self.submit(&symbol, Side::Sell, OrderType::Market, quantity);
+            } else if diff > EPSILON {
+                buys.push((symbol, diff / (price * cost)));
+            }
+        }
+        for (symbol, quantity) in buys {
+            self.submit(&symbol, Side::Buy, OrderType::Market, quantity);
+        }
+    }
+
+    /// Matches the resting orders of every symbol that has a bar in `bars`.
+    /// Sells are processed first so their proceeds can pay for the buys.
+    pub(crate) fn match_orders(&mut self, bars: &BTreeMap<String, Kline>) -> Vec<(String, Fill)> {
+        let mut orders = std::mem::take(&mut self.open_orders);
+        orders.sort_by_key(|(_, o)| o.side == Side::Buy);
+
+        let mut fills = Vec::new();
+        for (symbol, order) in orders {
+            let Some((bar, price)) = bars.get(&symbol).and_then(|bar| Some((bar, fill_price(&order, bar, self.config.slippage)?))) else {
+                self.open_orders.push((symbol, order));
+                continue;
+            };
+
+            if let Some(fill) = settle(&order, &symbol, price, bar.open_time, self.config.fee_rate, &mut self.ledger) {
+                fills.push((symbol, fill));
+            }
+        }
+        fills
+    }
+
+    pub(crate) fn mark(&mut self, time: u64, bars: &BTreeMap<String, Kline>) {
+        self.time = time;
+        for (symbol, bar) in bars {
+            self.ledger.mark(symbol, bar.close);
+        }
+    }
+}
+
+/// A strategy trading several symbols at once, called once per timeline slice.
+pub trait PortfolioStrategy {
+    /// `bars` only holds the symbols that have a bar at `time`.
+    fn on_bars(&mut self, time: u64, bars: &BTreeMap<String, Kline>, broker: &mut PortfolioBroker);
+
+    fn on_fill(&mut self, _symbol: &str, _fill: &Fill, _broker: &mut PortfolioBroker) {}
+}
+
+#[derive(Debug, Clone)]
+pub struct PortfolioResult {
+    pub initial_cash: f64,
+    /// `position` holds the quote value of all open positions.
+    pub equity_curve: Vec<EquityPoint>,
+    pub trades: BTreeMap<String, Vec<Fill>>,
+}
+
+impl PortfolioResult {
+    pub fn round_trips(&self) -> Vec<RoundTrip> {
+        let mut trips: Vec<RoundTrip> = self.trades.values().flat_map(|fills| round_trips(fills)).collect();
+        trips.sort_by_key(|t| t.exit_time);
+        trips
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        let fills: Vec<Fill> = self.trades.values().flatten().cloned().collect();
+        Metrics::compute(self.initial_cash, &self.equity_curve, &self.round_trips(), &fills)
+    }
+}
+
+/// Replays an aligned [`Timeline`] through a [`PortfolioStrategy`].
+pub struct PortfolioBacktester<S: PortfolioStrategy> {
+    strategy: S,
+    broker: PortfolioBroker,
+    equity_curve: Vec<EquityPoint>,
+    trades: BTreeMap<String, Vec<Fill>>,
+}
+
+impl<S: PortfolioStrategy> PortfolioBacktester<S> {
+    pub fn new(strategy: S, config: BrokerConfig) -> Self {
+        Self {
+            strategy,
+            broker: PortfolioBroker::new(config),
+            equity_curve: Vec::new(),
+            trades: BTreeMap::new(),
+        }
+    }
+
+    pub fn broker(&self) -> &PortfolioBroker {
+        &self.broker
+    }
+
+    pub fn step(&mut self, time: u64, bars: &BTreeMap<String, Kline>) {
+        let fills = self.broker.match_orders(bars);
+        self.broker.mark(time, bars);
+        for (symbol, fill) in fills {
+            self.strategy.on_fill(&symbol, &fill, &mut self.broker);
+            self.trades.entry(symbol).or_default().push(fill);
+        }
+
+        self.strategy.on_bars(time, bars, &mut self.broker);
+        self.equity_curve.push(EquityPoint {
+            time: bars.values().map(|b| b.close_time).max().unwrap_or(time),
+            equity: self.broker.equity(),
+            position: self.broker.invested(),
+        });
+    }
+
+    pub fn run(mut self, timeline: &Timeline) -> PortfolioResult {
+        info!("Backtesting portfolio over {} slices", timeline.len());
+        for (time, bars) in timeline.iter() {
+            self.step(time, bars);
+        }
+        self.into_result()
+    }
+
+    pub fn into_result(self) -> PortfolioResult {
+        PortfolioResult {
+            initial_cash: self.broker.config().initial_cash,
+            equity_curve: self.equity_curve,
+            trades: self.trades,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 3_600_000;
+
+    fn bar(i: u64, close: f64) -> Kline {
+        Kline {
+            open_time: i * HOUR,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1.0,
+            close_time: (i + 1) * HOUR - 1,
+            quote_asset_volume: close,
+            trade_number: 1,
+            buy_base: 0.5,
+            buy_quote: close / 2.0,
+        }
+    }
+
+    fn timeline() -> Timeline {
+        let mut data = BTreeMap::new();
+        data.insert("BTCUSDT".to_string(), [100.0, 100.0, 100.0, 100.0, 110.0, 120.0].iter().enumerate().map(|(i, c)| bar(i as u64, *c)).collect());
+        // listed at hour 2 and missing hour 4
+        data.insert("NEWUSDT".to_string(), [2, 3, 5].iter().map(|i| bar(*i, 10.0)).collect());
+        Timeline::new(data)
+    }
+
+    #[test]
+    fn aligns_listing_dates_and_gaps() {
+        let timeline = timeline();
+        let symbols: Vec<usize> = timeline.iter().map(|(_, bars)| bars.len()).collect();
+        assert_eq!(symbols, vec![1, 1, 2, 2, 1, 2]);
+    }
+
+    /// Puts half of the equity in every symbol as soon as both are trading.
+    struct Halves;
+
+    impl PortfolioStrategy for Halves {
+        fn on_bars(&mut self, _time: u64, bars: &BTreeMap<String, Kline>, broker: &mut PortfolioBroker) {
+            if bars.len() == 2 && broker.positions().is_empty() {
+                let weights = bars.keys().map(|s| (s.clone(), 0.5)).collect();
+                broker.rebalance(&weights);
+            }
+        }
+    }
+
+    #[test]
+    fn shared_cash_and_rebalancing() {
+        let config = BrokerConfig { initial_cash: 1_000.0, fee_rate: 0.0, slippage: 0.0 };
+        let result = PortfolioBacktester::new(Halves, config).run(&timeline());
+
+        assert_eq!(result.trades["BTCUSDT"].len(), 1);
+        assert_eq!(result.trades["NEWUSDT"].len(), 1);
+        // rebalanced at hour 2 and filled at hour 3
+        assert_eq!(result.trades["BTCUSDT"][0].quantity, 5.0);
+        assert_eq!(result.trades["NEWUSDT"][0].quantity, 50.0);
+        assert_eq!(result.trades["NEWUSDT"][0].time, 3 * HOUR);
+        // NEW has no bar at hour 4, it keeps its last price
+        assert_eq!(result.equity_curve[4].equity, 5.0 * 110.0 + 50.0 * 10.0);
+        assert_eq!(result.equity_curve[4].position, 1_050.0);
+        assert!(result.metrics().total_return > 0.0);
+    }
+
+    #[test]
+    fn equal_weight_picks_up_new_listings() {
+        let config = BrokerConfig { initial_cash: 1_000.0, fee_rate: 0.0, slippage: 0.0 };
+        let result = PortfolioBacktester::new(crate::strategies::EqualWeight::new(2), config).run(&timeline());
+        // all in BTC at hour 0, split with NEW at hour 2, BTC trimmed after its rise at hour 4
+        assert_eq!(result.trades["BTCUSDT"].len(), 3);
+        assert_eq!(result.trades["BTCUSDT"][1].side, Side::Sell);
+        assert_eq!(result.trades["BTCUSDT"][2].side, Side::Sell);
+        assert_eq!(result.trades["NEWUSDT"][0].time, 3 * HOUR);
+    }
+
+    #[test]
+    fn equal_weight_holds_through_gaps() {
+        let config = BrokerConfig { initial_cash: 1_000.0, fee_rate: 0.0, slippage: 0.0 };
+        let result = PortfolioBacktester::new(crate::strategies::EqualWeight::new(1), config).run(&timeline());
+        // NEW has no bar at hour 4, the rebalance then keeps it instead of selling it
+        assert!(result.trades["NEWUSDT"].iter().all(|fill| fill.side == Side::Buy));
+    }
+
+    #[test]
+    fn rebalance_sells_before_buying() {
+        let config = BrokerConfig { initial_cash: 0.0, fee_rate: 0.0, slippage: 0.0 };
+        let mut broker = PortfolioBroker::new(config);
+        let mut bars = BTreeMap::new();
+        bars.insert("AUSDT".to_string(), bar(0, 10.0));
+        bars.insert("BUSDT".to_string(), bar(0, 20.0));
+        broker.mark(0, &bars);
+        broker.ledger.set_balance("AUSDT", 10.0);
+
+        let weights = [("BUSDT".to_string(), 1.0)].into_iter().collect();
+        broker.rebalance(&weights);
+        let fills = broker.match_orders(&bars);
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[0].0, "AUSDT");
+        assert_eq!(broker.position("BUSDT"), 5.0);
+        assert_eq!(broker.position("AUSDT"), 0.0);
+        assert_eq!(broker.cash(), 0.0);
+    }
+}
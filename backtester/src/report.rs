@@ -0,0 +1,217 @@
+use std::path::Path;
+
+use plotters::prelude::*;
+use tracing::info;
+
+use crate::engine::BacktestResult;
+use crate::error::{Error, Result};
+use crate::metrics::{self, Metrics};
+
+const CHART_SIZE: (u32, u32) = (960, 320);
+
+/// Self-contained HTML page with the metrics table, the trade list and SVG charts.
+/// Everything is inlined so the report can be opened offline.
+pub struct Report<'a> {
+    result: &'a BacktestResult,
+    metrics: Metrics,
+    title: String,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(title: &str, result: &'a BacktestResult) -> Self {
+        Self {
+            result,
+            metrics: Metrics::from_result(result),
+            title: title.to_string(),
+        }
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn equity_svg(&self) -> Result<String> {
+        let points: Vec<(u64, f64)> = self.result.equity_curve.iter().map(|p| (p.time, p.equity)).collect();
+        line_chart("Equity", &points, &BLUE)
+    }
+
+    pub fn drawdown_svg(&self) -> Result<String> {
+        let points: Vec<(u64, f64)> = self.result.equity_curve
+            .iter()
+            .zip(metrics::drawdowns(self.result.initial_cash, &self.result.equity_curve))
+            .map(|(p, dd)| (p.time, -dd * 100.0))
+            .collect();
+        line_chart("Drawdown %", &points, &RED)
+    }
+
+    pub fn to_html(&self) -> Result<String> {
+        let m = &self.metrics;
+        let rows = [
+            ("Initial equity", format!("{:.2}", m.initial_equity)),
+            ("Final equity", format!("{:.2}", m.final_equity)),
+            ("Total return", percent(m.total_return)),
+            ("Annualized return", percent(m.annualized_return)),
+            ("Sharpe", format!("{:.2}", m.sharpe)),
+            ("Sortino", format!("{:.2}", m.sortino)),
+            ("Calmar", format!("{:.2}", m.calmar)),
+            ("Max drawdown", percent(m.max_drawdown)),
+            ("Max drawdown duration", format!("{:.1} days", m.max_drawdown_duration_ms as f64 / 86_400_000.0)),
+            ("Trades", m.trades.to_string()),
+            ("Win rate", percent(m.win_rate)),
+            ("Profit factor", format!("{:.2}", m.profit_factor)),
+            ("Average trade", format!("{:.2}", m.average_trade)),
+            ("Exposure", percent(m.exposure)),
+            ("Turnover", format!("{:.2}", m.turnover)),
+            ("Fees paid", format!("{:.2}", m.fees_paid)),
+        ];
+        let metrics_rows: String = rows
+            .iter()
+            .map(|(name, value)| format!("<tr><th>{name}</th><td>{value}</td></tr>\n"))
+            .collect();
+        let trade_rows: String = metrics::round_trips(&self.result.trades)
+            .iter()
+            .map(|t| format!(
+                "<tr><td>{}</td><td>{}</td><td>{:.8}</td><td>{:.8}</td><td>{:.8}</td><td>{:.2}</td></tr>\n",
+                t.entry_time, t.exit_time, t.quantity, t.entry_price, t.exit_price, t.pnl
+            ))
+            .collect();
+
+        Ok(format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; margin-bottom: 2em; }}
+th, td {{ border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: right; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<h2>Metrics</h2>
+<table>
+{metrics_rows}</table>
+<h2>Equity</h2>
+{equity}
+<h2>Drawdown</h2>
+{drawdown}
+<h2>Trades</h2>
+<table>
+<tr><th>Entry time</th><th>Exit time</th><th>Quantity</th><th>Entry price</th><th>Exit price</th><th>PnL</th></tr>
+{trade_rows}</table>
+</body>
+</html>
+"#,
+            title = escape_html(&self.title),
+            equity = self.equity_svg()?,
+            drawdown = self.drawdown_svg()?,
+        ))
+    }
+
+    pub fn write_html<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        info!("Writing report to {}", path.as_ref().display());
+        std::fs::write(path, self.to_html()?)?;
+        Ok(())
+    }
+
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.metrics.to_json())?;
+        Ok(())
+    }
+}
+
+/// Escapes text placed between tags or in attribute values.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn percent(value: f64) -> String {
+    format!("{:.2}%", value * 100.0)
+}
+
+fn line_chart(caption: &str, points: &[(u64, f64)], color: &RGBColor) -> Result<String> {
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, CHART_SIZE).into_drawing_area();
+        root.fill(&WHITE).map_err(plot_error)?;
+
+        // plotters' integer axes overflow on millisecond timestamps
+        let x_min = points.first().map_or(0.0, |p| p.0 as f64);
+        let x_max = points.last().map_or(1.0, |p| p.0 as f64).max(x_min + 1.0);
+        let y_min = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+        let y_max = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+        let (y_min, y_max) = match (y_min.is_finite(), y_max > y_min) {
+            (false, _) => (-1.0, 1.0),
+            (true, false) => (y_min - 1.0, y_max + 1.0),
+            (true, true) => (y_min, y_max),
+        };
+
+        let mut chart = ChartBuilder::on(&root)
+            .caption(caption, ("sans-serif", 16))
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(60)
+            .build_cartesian_2d(x_min..x_max, y_min..y_max)
+            .map_err(plot_error)?;
+        chart.configure_mesh()
+            .disable_x_mesh()
+            .x_labels(0)
+            .draw()
+            .map_err(plot_error)?;
+        chart.draw_series(LineSeries::new(points.iter().map(|(x, y)| (*x as f64, *y)), color))
+            .map_err(plot_error)?;
+        root.present().map_err(plot_error)?;
+    }
+    Ok(svg)
+}
+
+fn plot_error<E: std::error::Error + Send + Sync>(err: DrawingAreaErrorKind<E>) -> Error {
+    Error::Report(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::EquityPoint;
+    use crate::order::{Fill, Side};
+
+    #[test]
+    fn html_is_self_contained() {
+        let result = BacktestResult {
+            initial_cash: 100.0,
+            equity_curve: (0..10)
+                .map(|i| EquityPoint { time: i * 3_600_000, equity: 100.0 + (i as f64).sin(), position: 1.0 })
+                .collect(),
+            trades: vec![
+                Fill { order_id: 1, side: Side::Buy, quantity: 1.0, price: 10.0, fee: 0.0, time: 0 },
+                Fill { order_id: 2, side: Side::Sell, quantity: 1.0, price: 11.0, fee: 0.0, time: 3_600_000 },
+            ],
+        };
+        let html = Report::new("test run", &result).to_html().unwrap();
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert!(html.contains("<title>test run</title>"));
+        assert!(!html.contains("<script"));
+
+        let html = Report::new("runs/<script>&\"x\".parquet", &result).to_html().unwrap();
+        assert!(html.contains("<h1>runs/&lt;script&gt;&amp;&quot;x&quot;.parquet</h1>"));
+        assert!(!html.contains("<script"));
+    }
+
+    #[test]
+    fn empty_curve_renders() {
+        let result = BacktestResult { initial_cash: 100.0, equity_curve: vec![], trades: vec![] };
+        assert!(Report::new("empty", &result).to_html().is_ok());
+    }
+}
@@ -0,0 +1,69 @@
+use crate::metrics::RoundTrip;
+
+/// Quantity worth `fraction` of `equity` at `price`.
+pub fn fixed_fraction(equity: f64, fraction: f64, price: f64) -> f64 {
+    if price <= 0.0 {
+        return 0.0;
+    }
+    equity * fraction / price
+}
+
+/// Volatility targeting: the quantity that loses `risk` of `equity` when the price moves against it
+/// by `atr_multiple` times the ATR, e.g. a stop placed two ATRs away.
+pub fn volatility_target(equity: f64, risk: f64, atr: f64, atr_multiple: f64) -> f64 {
+    let distance = atr * atr_multiple;
+    if distance <= 0.0 {
+        return 0.0;
+    }
+    equity * risk / distance
+}
+
+/// Kelly fraction `p - (1 - p) / b` of equity to bet, for the win rate `p` and the ratio `b` of the
+/// average win to the average loss. Zero when the edge is negative.
+pub fn kelly_fraction(win_rate: f64, win_loss_ratio: f64) -> f64 {
+    if win_loss_ratio <= 0.0 {
+        return 0.0;
+    }
+    (win_rate - (1.0 - win_rate) / win_loss_ratio).clamp(0.0, 1.0)
+}
+
+/// Kelly fraction estimated from closed trades, `None` until there were both wins and losses.
+/// Full Kelly is aggressive for estimated odds, scale the result down, e.g. by half.
+pub fn kelly_from_round_trips(round_trips: &[RoundTrip]) -> Option<f64> {
+    let (wins, losses): (Vec<f64>, Vec<f64>) = round_trips.iter().map(|t| t.pnl).partition(|pnl| *pnl > 0.0);
+    if wins.is_empty() || losses.is_empty() {
+        return None;
+    }
+    let avg_win = wins.iter().sum::<f64>() / wins.len() as f64;
+    let avg_loss = -losses.iter().sum::<f64>() / losses.len() as f64;
+    let win_rate = wins.len() as f64 / round_trips.len() as f64;
+    Some(kelly_fraction(win_rate, avg_win / avg_loss))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trip(pnl: f64) -> RoundTrip {
+        RoundTrip { entry_time: 0, exit_time: 1, quantity: 1.0, entry_price: 100.0, exit_price: 100.0 + pnl, pnl }
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(fixed_fraction(10_000.0, 0.5, 50.0), 100.0);
+        assert_eq!(fixed_fraction(10_000.0, 0.5, 0.0), 0.0);
+        // 1% of 10k at risk with a stop 2 ATRs of 25 away
+        assert_eq!(volatility_target(10_000.0, 0.01, 25.0, 2.0), 2.0);
+        assert_eq!(volatility_target(10_000.0, 0.01, 0.0, 2.0), 0.0);
+    }
+
+    #[test]
+    fn kelly() {
+        assert_eq!(kelly_fraction(0.6, 1.0), 0.6 - 0.4);
+        assert_eq!(kelly_fraction(0.3, 1.0), 0.0);
+        // two wins of 20, two losses of 10: p = 0.5, b = 2
+        let trips = [trip(20.0), trip(-10.0), trip(20.0), trip(-10.0)];
+        assert_eq!(kelly_from_round_trips(&trips), Some(0.25));
+        assert_eq!(kelly_from_round_trips(&trips[..1]), None);
+    }
+}
@@ -0,0 +1,36 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use data_downloader::Kline;
+
+use crate::portfolio::{PortfolioBroker, PortfolioStrategy};
+
+/// Rotates the whole account equally into every symbol trading at the time,
+/// rebalancing every `every` slices. Newly listed symbols join at the next rebalance, held symbols
+/// missing a bar keep their share.
+#[derive(Debug, Clone)]
+pub struct EqualWeight {
+    every: usize,
+    slices: usize,
+}
+
+impl EqualWeight {
+    pub fn new(every: usize) -> Self {
+        Self { every: every.max(1), slices: 0 }
+    }
+}
+
+impl PortfolioStrategy for EqualWeight {
+    fn on_bars(&mut self, _time: u64, bars: &BTreeMap<String, Kline>, broker: &mut PortfolioBroker) {
+        let slice = self.slices;
+        self.slices += 1;
+        if !slice.is_multiple_of(self.every) || bars.is_empty() {
+            return;
+        }
+        // a gap in the data is no reason to sell, a held symbol with a price stays in
+        let held = broker.positions().into_keys().filter(|symbol| broker.last_price(symbol).is_some());
+        let symbols: BTreeSet<String> = bars.keys().cloned().chain(held).collect();
+        let weight = 1.0 / symbols.len() as f64;
+        let weights = symbols.into_iter().map(|symbol| (symbol, weight)).collect();
+        broker.rebalance(&weights);
+    }
+}
@@ -0,0 +1,5 @@
+mod equal_weight;
+mod rsi_threshold;
+
+pub use equal_weight::EqualWeight;
+pub use rsi_threshold::{RsiThreshold, RsiThresholdConfig};
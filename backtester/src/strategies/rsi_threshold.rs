@@ -0,0 +1,131 @@
+use data_downloader::Kline;
+use tracing::debug;
+
+use crate::broker::Broker;
+use crate::indicator::Rsi;
+use crate::optimize::Params;
+use crate::order::{OrderType, Side};
+use crate::sizing;
+use crate::strategy::Strategy;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RsiThresholdConfig {
+    pub period: usize,
+    /// Buy when the RSI crosses below this level.
+    pub buy_below: f64,
+    /// Sell the whole position when the RSI crosses above this level.
+    pub sell_above: f64,
+    /// Fraction of the cash spent per entry, leaves room for fees and the gap to the next open.
+    pub allocation: f64,
+}
+
+impl Default for RsiThresholdConfig {
+    fn default() -> Self {
+        Self {
+            period: 14,
+            buy_below: 30.0,
+            sell_above: 70.0,
+            allocation: 0.99,
+        }
+    }
+}
+
+impl RsiThresholdConfig {
+    /// Reads `period`, `buy_below`, `sell_above` and `allocation`, missing ones keep their default.
+    pub fn from_params(params: &Params) -> Self {
+        let default = Self::default();
+        Self {
+            period: params.get("period").map_or(default.period, |p| *p as usize),
+            buy_below: params.get("buy_below").copied().unwrap_or(default.buy_below),
+            sell_above: params.get("sell_above").copied().unwrap_or(default.sell_above),
+            allocation: params.get("allocation").copied().unwrap_or(default.allocation),
+        }
+    }
+}
+
+/// Reference long-only strategy: enters on oversold and exits on overbought RSI crossings.
+#[derive(Debug, Clone)]
+pub struct RsiThreshold {
+    config: RsiThresholdConfig,
+    rsi: Rsi,
+    prev_rsi: Option<f64>,
+}
+
+impl RsiThreshold {
+    pub fn new(config: RsiThresholdConfig) -> Self {
+        Self {
+            rsi: Rsi::new(config.period),
+            config,
+            prev_rsi: None,
+        }
+    }
+
+    pub fn config(&self) -> &RsiThresholdConfig {
+        &self.config
+    }
+}
+
+impl Strategy for RsiThreshold {
+    fn on_bar(&mut self, bar: &Kline, broker: &mut Broker) {
+        let rsi = self.rsi.update(bar.close);
+        let prev = std::mem::replace(&mut self.prev_rsi, rsi);
+        let (Some(prev), Some(rsi)) = (prev, rsi) else {
+            return;
+        };
+        if !broker.open_orders().is_empty() {
+            return;
+        }
+
+        let flat = broker.position() <= 0.0;
+        if flat && prev >= self.config.buy_below && rsi < self.config.buy_below {
+            let fee_rate = broker.config().fee_rate + broker.config().slippage;
+            let quantity = sizing::fixed_fraction(broker.cash(), self.config.allocation, bar.close * (1.0 + fee_rate));
+            debug!("RSI {rsi:.2} crossed below {}, buying {quantity}", self.config.buy_below);
+            broker.submit(Side::Buy, OrderType::Market, quantity);
+        } else if !flat && prev <= self.config.sell_above && rsi > self.config.sell_above {
+            debug!("RSI {rsi:.2} crossed above {}, selling {}", self.config.sell_above, broker.position());
+            broker.submit(Side::Sell, OrderType::Market, broker.position());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::BrokerConfig;
+    use crate::engine::Backtester;
+
+    fn bar(i: u64, close: f64) -> Kline {
+        Kline {
+            open_time: i * 60_000,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1.0,
+            close_time: i * 60_000 + 59_999,
+            quote_asset_volume: close,
+            trade_number: 1,
+            buy_base: 0.5,
+            buy_quote: close / 2.0,
+        }
+    }
+
+    #[test]
+    fn buys_oversold_and_sells_overbought() {
+        // choppy warmup, a sell-off and a rally
+        let mut closes = vec![100.0, 101.0, 100.0, 101.0, 100.0];
+        closes.extend((1..=5).map(|i| 100.0 - i as f64 * 3.0));
+        closes.extend((1..=10).map(|i| 85.0 + i as f64 * 3.0));
+        let data: Vec<Kline> = closes.iter().enumerate().map(|(i, c)| bar(i as u64, *c)).collect();
+
+        let config = RsiThresholdConfig { period: 3, ..Default::default() };
+        let result = Backtester::new(RsiThreshold::new(config), BrokerConfig::default()).run(&data);
+
+        let sides: Vec<Side> = result.trades.iter().map(|f| f.side).collect();
+        assert_eq!(sides, vec![Side::Buy, Side::Sell]);
+        assert!(result.trades[0].price < 100.0);
+        assert!(result.trades[1].price > result.trades[0].price);
+        assert!(result.final_equity() > 10_000.0);
+    }
+}
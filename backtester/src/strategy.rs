@@ -0,0 +1,15 @@
+use data_downloader::Kline;
+
+use crate::broker::Broker;
+use crate::order::Fill;
+
+/// A trading strategy replayed bar-by-bar.
+///
+/// Orders submitted from either callback are matched starting with the next bar,
+/// so a strategy never trades on prices it has not seen yet.
+pub trait Strategy {
+    /// Called once per closed bar, after the fills of that bar were reported.
+    fn on_bar(&mut self, bar: &Kline, broker: &mut Broker);
+
+    fn on_fill(&mut self, _fill: &Fill, _broker: &mut Broker) {}
+}
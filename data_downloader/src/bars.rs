@@ -0,0 +1,191 @@
+use std::path::Path;
+
+use polars::prelude::*;
+
+use crate::error::{Error, Result};
+use crate::kline::Kline;
+use crate::storage;
+
+/// Alternative sampling of a kline series. Threshold bars close as soon as the
+/// accumulated quantity reaches the threshold, using whole klines as the smallest unit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarType {
+    /// Base asset volume per bar.
+    Volume(f64),
+    /// Quote asset volume per bar.
+    Dollar(f64),
+    /// Number of trades per bar, taken from `Kline::trade_number`.
+    Tick(usize),
+    HeikinAshi,
+}
+
+impl BarType {
+    /// Value of the `bar_type` column, e.g. `volume[1000]`.
+    pub fn tag(&self) -> String {
+        match self {
+            BarType::Volume(threshold) => format!("volume[{threshold}]"),
+            BarType::Dollar(threshold) => format!("dollar[{threshold}]"),
+            BarType::Tick(threshold) => format!("tick[{threshold}]"),
+            BarType::HeikinAshi => "heikin_ashi".to_string(),
+        }
+    }
+
+    /// Fails for thresholds that are not a positive number.
+    pub fn build(&self, data: &[Kline]) -> Result<Vec<Kline>> {
+        let valid = match *self {
+            BarType::Volume(threshold) | BarType::Dollar(threshold) => threshold.is_finite() && threshold > 0.0,
+            BarType::Tick(threshold) => threshold > 0,
+            BarType::HeikinAshi => true,
+        };
+        if !valid {
+            return Err(Error::InvalidBarType(format!("{} needs a positive threshold", self.tag())));
+        }
+        Ok(match *self {
+            BarType::Volume(threshold) => threshold_bars(data, threshold, |k| k.volume),
+            BarType::Dollar(threshold) => threshold_bars(data, threshold, |k| k.quote_asset_volume),
+            BarType::Tick(threshold) => threshold_bars(data, threshold as f64, |k| k.trade_number as f64),
+            BarType::HeikinAshi => heikin_ashi(data),
+        })
+    }
+
+    /// Builds the bars and writes them in the stored kline schema plus a `bar_type` column.
+    pub fn write_bars<P: AsRef<Path>>(&self, path: P, data: &[Kline]) -> Result<()> {
+        let bars = self.build(data)?;
+        let mut df = storage::klines_to_df(&bars)?;
+        df.with_column(Series::new("bar_type", vec![self.tag(); bars.len()]))?;
+        storage::write_parquet(path, &mut df)
+    }
+}
+
+/// Groups consecutive klines until `measure` adds up to `threshold`.
+/// The trailing, not yet complete bar is dropped.
+fn threshold_bars(data: &[Kline], threshold: f64, measure: impl Fn(&Kline) -> f64) -> Vec<Kline> {
+    let mut bars = Vec::new();
+    let mut current: Option<Kline> = None;
+    let mut accumulated = 0.0;
+
+    for kline in data {
+        match current.as_mut() {
+            Some(bar) => bar.absorb(kline),
+            None => current = Some(kline.clone()),
+        }
+        accumulated += measure(kline);
+        if accumulated >= threshold {
+            bars.extend(current.take());
+            accumulated = 0.0;
+        }
+    }
+    bars
+}
+
+fn heikin_ashi(data: &[Kline]) -> Vec<Kline> {
+    let mut bars: Vec<Kline> = Vec::with_capacity(data.len());
+    for kline in data {
+        let close = (kline.open + kline.high + kline.low + kline.close) / 4.0;
+        let open = match bars.last() {
+            Some(prev) => (prev.open + prev.close) / 2.0,
+            None => (kline.open + kline.close) / 2.0,
+        };
+        bars.push(Kline {
+            open,
+            high: kline.high.max(open).max(close),
+            low: kline.low.min(open).min(close),
+            close,
+            ..kline.clone()
+        });
+    }
+    bars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kline(i: u64, open: f64, high: f64, low: f64, close: f64, volume: f64) -> Kline {
+        Kline {
+            open_time: i * 60_000,
+            open,
+            high,
+            low,
+            close,
+            volume,
+            close_time: i * 60_000 + 59_999,
+            quote_asset_volume: volume * close,
+            trade_number: volume as usize * 10,
+            buy_base: volume / 2.0,
+            buy_quote: volume * close / 2.0,
+        }
+    }
+
+    fn series() -> Vec<Kline> {
+        vec![
+            kline(0, 10.0, 12.0, 9.0, 11.0, 1.0),
+            kline(1, 11.0, 13.0, 10.0, 12.0, 2.0),
+            kline(2, 12.0, 12.5, 8.0, 9.0, 3.0),
+            kline(3, 9.0, 10.0, 7.0, 8.0, 1.0),
+            kline(4, 8.0, 9.0, 7.5, 8.5, 1.0),
+        ]
+    }
+
+    #[test]
+    fn volume_bars() {
+        let bars = BarType::Volume(3.0).build(&series()).unwrap();
+        assert_eq!(bars.len(), 2);
+        assert_eq!((bars[0].open, bars[0].high, bars[0].low, bars[0].close), (10.0, 13.0, 9.0, 12.0));
+        assert_eq!(bars[0].volume, 3.0);
+        assert_eq!((bars[0].open_time, bars[0].close_time), (0, 119_999));
+        assert_eq!(bars[1].volume, 3.0);
+        assert_eq!(bars[1].open_time, 120_000);
+    }
+
+    #[test]
+    fn tick_and_dollar_bars() {
+        let ticks = BarType::Tick(40).build(&series()).unwrap();
+        assert_eq!(ticks.iter().map(|b| b.trade_number).collect::<Vec<_>>(), vec![60]);
+
+        let dollars = BarType::Dollar(60.0).build(&series()).unwrap();
+        assert_eq!(dollars.len(), 1);
+        assert_eq!(dollars[0].quote_asset_volume, 11.0 + 24.0 + 27.0);
+    }
+
+    #[test]
+    fn heikin_ashi_bars() {
+        let bars = BarType::HeikinAshi.build(&series()).unwrap();
+        assert_eq!(bars.len(), 5);
+        assert_eq!(bars[0].open, 10.5);
+        assert_eq!(bars[0].close, 10.5);
+        assert_eq!(bars[1].open, 10.5);
+        assert_eq!(bars[1].close, 11.5);
+        assert_eq!(bars[1].high, 13.0);
+        assert_eq!(bars[2].open, 11.0);
+        assert_eq!(bars[2].low, 8.0);
+        assert_eq!(bars[0].volume, 1.0);
+    }
+
+    #[test]
+    fn rejects_thresholds_that_are_not_positive() {
+        for bar_type in [BarType::Volume(0.0), BarType::Volume(-1.0), BarType::Dollar(f64::NAN), BarType::Tick(0)] {
+            assert!(matches!(bar_type.build(&series()), Err(Error::InvalidBarType(_))), "{bar_type:?}");
+        }
+    }
+
+    #[test]
+    fn written_bars_read_back_with_their_tag() {
+        let path = std::env::temp_dir().join(format!("iambot-bars-{}.parquet", std::process::id()));
+        let bar_type = BarType::Volume(3.0);
+        bar_type.write_bars(&path, &series()).unwrap();
+
+        let df = storage::read_parquet(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let tags: Vec<Option<&str>> = df.column("bar_type").unwrap().str().unwrap().into_iter().collect();
+        assert_eq!(tags, vec![Some("volume[3]"); 2]);
+        assert_eq!(storage::df_to_klines(&df).unwrap(), bar_type.build(&series()).unwrap());
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(BarType::Volume(1000.0).tag(), "volume[1000]");
+        assert_eq!(BarType::Tick(500).tag(), "tick[500]");
+        assert_eq!(BarType::HeikinAshi.tag(), "heikin_ashi");
+    }
+}
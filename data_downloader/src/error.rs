@@ -2,9 +2,56 @@ pub type Result<T> = core::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
-    ParseIntervalError
+    ParseIntervalError,
+    ParseFeatureSpecError(String),
+    UnsupportedSpecFormat(String),
+    ResampleError(String),
+    /// A bar threshold that is not a positive number, no bar would ever hold anything.
+    InvalidBarType(String),
+    Polars(polars::error::PolarsError),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    InvalidUrl(String),
+    Http(reqwest::Error),
+    /// A price or amount of a stream message that is no finite number.
+    InvalidNumber(String),
+    /// Non-success response of a REST endpoint.
+    Status { status: u16, body: String },
+    // boxed, tungstenite's error is large
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
 }
 
+// region:    - Froms
+impl From<polars::error::PolarsError> for Error {
+    fn from(err: polars::error::PolarsError) -> Self {
+        Error::Polars(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(err))
+    }
+}
+// endregion: - Froms
+
 // region:    - Error impl
 impl core::fmt::Display for Error {
     fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
@@ -13,4 +60,4 @@ impl core::fmt::Display for Error {
 }
 
 impl std::error::Error for Error {}
-// endregion:  - Error impl
\ No newline at end of file
+// endregion:  - Error impl
@@ -0,0 +1,186 @@
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::error::{Error, Result};
+use crate::storage;
+use crate::util;
+
+/// Declarative description of the derived columns added to a kline dataset.
+///
+/// ```toml
+/// version = 2
+/// rsi = [14, 21]
+/// ema = [9, 21, 200]
+/// returns = "log"
+/// ```
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureSpec {
+    #[serde(default = "default_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub rsi: Vec<usize>,
+    #[serde(default)]
+    pub ema: Vec<usize>,
+    #[serde(default)]
+    pub returns: Option<Returns>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Returns {
+    Simple,
+    Log,
+}
+
+fn default_version() -> u32 {
+    1
+}
+
+impl Default for FeatureSpec {
+    /// The columns the downloader used to write on its own.
+    fn default() -> Self {
+        Self {
+            version: default_version(),
+            rsi: vec![14],
+            ema: Vec::new(),
+            returns: None,
+        }
+    }
+}
+
+impl FeatureSpec {
+    pub fn from_toml_str(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|e| Error::ParseFeatureSpecError(e.to_string()))
+    }
+
+    pub fn from_yaml_str(s: &str) -> Result<Self> {
+        serde_yaml::from_str(s).map_err(|e| Error::ParseFeatureSpecError(e.to_string()))
+    }
+
+    /// Loads a spec, picking the format from the file extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml_str(&content),
+            Some("yaml") | Some("yml") => Self::from_yaml_str(&content),
+            _ => Err(Error::UnsupportedSpecFormat(path.display().to_string())),
+        }
+    }
+
+    /// Appends every configured feature column to `df`.
+    /// Rows without enough history are left as nulls.
+    pub fn apply(&self, df: &mut DataFrame) -> Result<()> {
+        let height = df.height();
+
+        for &period in &self.rsi {
+            let values = if period > 0 && height > period {
+                util::calculate_rsi(df, period)
+            } else {
+                Vec::new()
+            };
+            df.with_column(Series::new(&format!("RSI[{period}]"), pad_front(values, height)))?;
+        }
+
+        for &period in &self.ema {
+            let values = util::calculate_ema(df, period);
+            df.with_column(Series::new(&format!("EMA[{period}]"), pad_front(values, height)))?;
+        }
+
+        if let Some(returns) = self.returns {
+            let (name, log) = match returns {
+                Returns::Simple => ("RETURN[simple]", false),
+                Returns::Log => ("RETURN[log]", true),
+            };
+            let values = util::calculate_returns(df, log);
+            df.with_column(Series::new(name, pad_front(values, height)))?;
+        }
+
+        Ok(())
+    }
+
+    /// `data/BTCUSDT.parquet` becomes `data/BTCUSDT.features.v{version}.parquet`.
+    pub fn output_path<P: AsRef<Path>>(&self, input: P) -> PathBuf {
+        let input = input.as_ref();
+        let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("klines");
+        input.with_file_name(format!("{stem}.features.v{}.parquet", self.version))
+    }
+
+    /// Reads a stored kline dataset, applies the spec and writes the versioned feature file next to it.
+    pub fn run<P: AsRef<Path>>(&self, input: P) -> Result<PathBuf> {
+        let input = input.as_ref();
+        info!("Applying feature spec v{} to {}", self.version, input.display());
+        let mut df = storage::read_parquet(input)?;
+        self.apply(&mut df)?;
+        let output = self.output_path(input);
+        storage::write_parquet(&output, &mut df)?;
+        Ok(output)
+    }
+}
+
+fn pad_front(values: Vec<f64>, height: usize) -> Vec<Option<f64>> {
+    let mut padded = vec![None; height.saturating_sub(values.len())];
+    padded.extend(values.into_iter().map(Some));
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closes(values: &[f64]) -> DataFrame {
+        df!("close" => values).unwrap()
+    }
+
+    #[test]
+    fn parse_toml_and_yaml() {
+        let toml = FeatureSpec::from_toml_str("version = 3\nrsi = [14, 21]\nema = [9, 21, 200]\nreturns = \"log\"").unwrap();
+        let yaml = FeatureSpec::from_yaml_str("version: 3\nrsi: [14, 21]\nema: [9, 21, 200]\nreturns: log").unwrap();
+        assert_eq!(toml, yaml);
+        assert_eq!(toml.rsi, vec![14, 21]);
+        assert_eq!(toml.returns, Some(Returns::Log));
+
+        assert!(FeatureSpec::from_toml_str("macd = [12]").is_err());
+        assert_eq!(FeatureSpec::from_toml_str("").unwrap().version, 1);
+    }
+
+    #[test]
+    fn apply_pads_warmup_rows() {
+        let spec = FeatureSpec::from_toml_str("rsi = [3]\nema = [2]\nreturns = \"simple\"").unwrap();
+        let mut df = closes(&[1.0, 2.0, 1.0, 2.0, 4.0]);
+        spec.apply(&mut df).unwrap();
+
+        let rsi = df.column("RSI[3]").unwrap();
+        assert_eq!(rsi.null_count(), 3);
+
+        let ema: Vec<Option<f64>> = df.column("EMA[2]").unwrap().f64().unwrap().into_iter().collect();
+        assert_eq!(ema[0], None);
+        assert_eq!(ema[1], Some(1.5));
+        assert!((ema[2].unwrap() - 1.1666666).abs() < 1e-6);
+
+        let ret: Vec<Option<f64>> = df.column("RETURN[simple]").unwrap().f64().unwrap().into_iter().collect();
+        assert_eq!(ret, vec![None, Some(1.0), Some(-0.5), Some(1.0), Some(1.0)]);
+    }
+
+    #[test]
+    fn short_dataset_is_all_null() {
+        let spec = FeatureSpec::from_toml_str("rsi = [14]\nema = [9]").unwrap();
+        let mut df = closes(&[1.0, 2.0]);
+        spec.apply(&mut df).unwrap();
+        assert_eq!(df.column("RSI[14]").unwrap().null_count(), 2);
+        assert_eq!(df.column("EMA[9]").unwrap().null_count(), 2);
+    }
+
+    #[test]
+    fn versioned_output_path() {
+        let spec = FeatureSpec { version: 4, ..Default::default() };
+        assert_eq!(
+            spec.output_path("data/BTCUSDT.parquet"),
+            PathBuf::from("data/BTCUSDT.features.v4.parquet")
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use std::str::FromStr;
 use crate::error::Error;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Interval {
     OneMinute =       60,
     ThreeMinute =    180,
@@ -45,6 +45,107 @@ impl FromStr for Interval {
     }
 }
 
+const DAY_MS: u64 = 86_400_000;
+// 1970-01-01 was a Thursday, Binance weeks start on Monday 00:00 UTC
+const WEEK_OFFSET_MS: u64 = 4 * DAY_MS;
+
+impl Interval {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::OneMinute => "1m",
+            Interval::ThreeMinute => "3m",
+            Interval::FiveMinute => "5m",
+            Interval::FifteenMinute => "15m",
+            Interval::ThirtyMinute => "30m",
+            Interval::OneHour => "1h",
+            Interval::TwoHour => "2h",
+            Interval::FourHour => "4h",
+            Interval::SixHour => "6h",
+            Interval::EightHour => "8h",
+            Interval::TwelweHour => "12h",
+            Interval::OneDay => "1d",
+            Interval::ThreeDay => "3d",
+            Interval::OneWeek => "1w",
+            Interval::OneMonth => "1M",
+        }
+    }
+
+    /// Nominal length in milliseconds, a month counts as 30 days.
+    pub fn duration_ms(&self) -> u64 {
+        *self as u64 * 1000
+    }
+
+    /// Open time of the candle containing `ts`, aligned the way Binance aligns its candles:
+    /// to the epoch for intraday and 3d candles, to Monday for weeks and to the first of the month for months.
+    pub fn candle_start(&self, ts: u64) -> u64 {
+        match self {
+            Interval::OneWeek => {
+                let week = self.duration_ms();
+                (ts + week - WEEK_OFFSET_MS) / week * week - (week - WEEK_OFFSET_MS)
+            }
+            Interval::OneMonth => {
+                let (year, month, _) = civil_from_days((ts / DAY_MS) as i64);
+                days_from_civil(year, month, 1) as u64 * DAY_MS
+            }
+            _ => ts / self.duration_ms() * self.duration_ms(),
+        }
+    }
+
+    /// Open time of the candle following the one containing `ts`.
+    pub fn next_candle_start(&self, ts: u64) -> u64 {
+        match self {
+            Interval::OneMonth => {
+                let (year, month, _) = civil_from_days((ts / DAY_MS) as i64);
+                let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                days_from_civil(year, month, 1) as u64 * DAY_MS
+            }
+            _ => self.candle_start(ts) + self.duration_ms(),
+        }
+    }
+
+    /// Whether every `self` candle is made of whole `lower` candles.
+    pub fn is_multiple_of(&self, lower: Interval) -> bool {
+        match (self, lower) {
+            (a, b) if *a == b => true,
+            (Interval::OneWeek, _) | (Interval::OneMonth, _) => {
+                lower.duration_ms() <= DAY_MS && DAY_MS.is_multiple_of(lower.duration_ms())
+            }
+            (_, Interval::OneWeek) | (_, Interval::OneMonth) => false,
+            _ => self.duration_ms().is_multiple_of(lower.duration_ms()),
+        }
+    }
+}
+
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,4 +231,38 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn display_roundtrip() {
+        for s in ["1m", "3m", "5m", "15m", "30m", "1h", "2h", "4h", "6h", "8h", "12h", "1d", "3d", "1w", "1M"] {
+            assert_eq!(s.parse::<Interval>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn candle_alignment() {
+        // 2024-01-03 13:37:00 UTC, a Wednesday
+        let ts = 1_704_289_020_000;
+        assert_eq!(Interval::FourHour.candle_start(ts), 1_704_283_200_000);
+        assert_eq!(Interval::OneDay.candle_start(ts), 1_704_240_000_000);
+        // Monday 2024-01-01
+        assert_eq!(Interval::OneWeek.candle_start(ts), 1_704_067_200_000);
+        assert_eq!(Interval::OneWeek.next_candle_start(ts), 1_704_672_000_000);
+        // 2024-01-01 and 2024-02-01
+        assert_eq!(Interval::OneMonth.candle_start(ts), 1_704_067_200_000);
+        assert_eq!(Interval::OneMonth.next_candle_start(ts), 1_706_745_600_000);
+        // December rolls over into the next year
+        assert_eq!(Interval::OneMonth.next_candle_start(1_703_980_800_000), 1_704_067_200_000);
+    }
+
+    #[test]
+    fn multiples() {
+        assert!(Interval::FifteenMinute.is_multiple_of(Interval::FiveMinute));
+        assert!(Interval::OneMonth.is_multiple_of(Interval::OneHour));
+        assert!(Interval::OneWeek.is_multiple_of(Interval::OneDay));
+        assert!(!Interval::FiveMinute.is_multiple_of(Interval::ThreeMinute));
+        assert!(!Interval::OneMonth.is_multiple_of(Interval::OneWeek));
+        assert!(!Interval::OneWeek.is_multiple_of(Interval::ThreeDay));
+        assert!(!Interval::OneHour.is_multiple_of(Interval::FourHour));
+    }
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use serde::{de::{self, Visitor}, Deserialize};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Kline {
     pub open_time: u64,
     pub open: f64,
@@ -15,6 +15,22 @@ pub struct Kline {
     pub buy_quote: f64,
 }
 
+impl Kline {
+    /// Extends this candle with a later one: keeps the open, widens high/low,
+    /// takes the later close and close_time and sums volumes and trade counts.
+    pub(crate) fn absorb(&mut self, later: &Kline) {
+        self.high = self.high.max(later.high);
+        self.low = self.low.min(later.low);
+        self.close = later.close;
+        self.close_time = later.close_time;
+        self.volume += later.volume;
+        self.quote_asset_volume += later.quote_asset_volume;
+        self.trade_number += later.trade_number;
+        self.buy_base += later.buy_base;
+        self.buy_quote += later.buy_quote;
+    }
+}
+
 impl<'de> Deserialize<'de> for Kline {
     fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
@@ -2,35 +2,59 @@
 // Created by Jenei Andras 2024
 
 #![crate_name = "data_downloader"]
-use polars::{df, io::parquet::ParquetWriter};
-use polars::prelude::*;
 use reqwest::{Client, Url};
 
+mod bars;
 mod error;
+mod feature;
 mod interval;
 mod kline;
+mod resample;
+mod storage;
+mod stream;
 mod util;
 
-use error::{Error, Result};
-use interval::Interval;
-use kline::Kline;
+pub use bars::BarType;
+pub use error::{Error, Result};
+pub use feature::{FeatureSpec, Returns};
+pub use interval::Interval;
+pub use kline::Kline;
+pub use resample::resample;
+pub use storage::{read_klines, write_klines};
+pub use stream::{KlineEvent, StreamKline};
 use tracing::{debug, info};
 
-pub const BINANCE_KLINE_API: &str = "https://api.binance.com/api/v3/klines";
+pub const BINANCE_API: &str = "https://api.binance.com";
+pub const BINANCE_WS_API: &str = "wss://stream.binance.com:9443/ws";
 
 pub struct Requester {
     client: Client,
+    base_url: String,
+    ws_url: String,
 }
 
 impl Default for Requester {
     fn default() -> Self {
         Self {
             client: Client::new(),
+            base_url: BINANCE_API.to_string(),
+            ws_url: BINANCE_WS_API.to_string(),
         }
     }
 }
 
 impl Requester {
+    /// Points the REST requests at another server, e.g. a local mock exchange.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_ws_url(mut self, ws_url: &str) -> Self {
+        self.ws_url = ws_url.trim_end_matches('/').to_string();
+        self
+    }
+
     pub async fn get_historical_data(
         &self,
         symbol: &str,
@@ -38,13 +62,13 @@ impl Requester {
         start_time: usize,
         end_time: usize,
         output_path: &str,
-    ) {
+    ) -> Result<()> {
         // TODO: multithreading?
         info!("Beginning to download historical data:\n\tsymbol: {symbol}\n\tinterval: {interval}\n\tstart_time: {start_time}\n\tend_time: {end_time}\n\toutput_path: {output_path}");
         
         let limit = 1000;
 
-        let interval_sec = interval.parse::<Interval>().unwrap() as usize;
+        let interval_sec = interval.parse::<Interval>()? as usize;
         let interval_ms = interval_sec * 1000;
 
         let chunks = (start_time..end_time)
@@ -59,14 +83,17 @@ impl Requester {
         let data: Vec<Kline> = futures::future::join_all(chunks)
             .await
             .into_iter()
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
             .flatten()
             .collect();
 
-        self.save_to_file(output_path, data).unwrap();
+        self.save_to_file(output_path, data)
     }
 
-    pub async fn connect_to_ws() {
-        // TODO: implement
+    /// Streams the closed candles of `symbol` into `sender` until the connection drops.
+    pub async fn connect_to_ws(&self, symbol: &str, interval: &str, sender: tokio::sync::mpsc::Sender<Kline>) -> Result<()> {
+        stream::stream_klines(&self.ws_url, symbol, interval, sender).await
     }
 
     async fn download_chunk(
@@ -76,11 +103,11 @@ impl Requester {
         start_time: usize,
         end_time: usize,
         limit: usize,
-    ) -> Vec<Kline> {
+    ) -> Result<Vec<Kline>> {
         info!("Downloading chunk:\n\tsymbol: {symbol}\n\tinterval: {interval}\n\tstart_time: {start_time}\n\tend_time: {end_time}\n\t");
 
         let url = Url::parse_with_params(
-            BINANCE_KLINE_API,
+            &format!("{}/api/v3/klines", self.base_url),
             &[
                 ("symbol", symbol),
                 ("interval", interval),
@@ -89,49 +116,21 @@ impl Requester {
                 ("limit", limit.to_string().as_str()),
             ],
         )
-        .unwrap();
+        .map_err(|e| Error::InvalidUrl(e.to_string()))?;
         debug!("URL: {url}");
         let resp = self.client.get(url)
             .send()
-            .await.unwrap()
-            .text()
-            .await.unwrap();
-        serde_json::from_str(&resp).unwrap()
+            .await?;
+        let status = resp.status();
+        let body = resp.text().await?;
+        if !status.is_success() {
+            return Err(Error::Status { status: status.as_u16(), body });
+        }
+        Ok(serde_json::from_str(&body)?)
     }
 
     fn save_to_file(&self, output_path: &str, data: Vec<Kline>) -> Result<()> {
-        info!("Saving file to {output_path}");
-        let mut df = df!(
-            "open_time" => data.iter().map(|k| k.open_time).collect::<Vec<u64>>(),
-            "open" => data.iter().map(|k| k.open).collect::<Vec<f64>>(),
-            "high" => data.iter().map(|k| k.high).collect::<Vec<f64>>(),
-            "low" => data.iter().map(|k| k.low).collect::<Vec<f64>>(),
-            "close" => data.iter().map(|k| k.close).collect::<Vec<f64>>(),
-            "volume" => data.iter().map(|k| k.volume).collect::<Vec<f64>>(),
-            "close_time" => data.iter().map(|k| k.close_time).collect::<Vec<u64>>(),
-            "quote_asset_volume" => data.iter().map(|k| k.quote_asset_volume).collect::<Vec<f64>>(),
-            "trade_number" => data.iter().map(|k| k.trade_number as u64).collect::<Vec<u64>>(),
-            "buy_base" => data.iter().map(|k| k.buy_base).collect::<Vec<f64>>(),
-            "buy_quote" => data.iter().map(|k| k.buy_quote).collect::<Vec<f64>>(),
-        )
-        .unwrap();
-        // TODO: not the task of the data_downloader, implement elsewhere
-        let rsi_values = util::calculate_rsi(&df, 14);
-        let mut extended_rsi = vec![None; df.height() - rsi_values.len()];
-        extended_rsi.extend(rsi_values.iter().cloned().map(Some));
-        df.with_column(Series::new("RSI[14]", &extended_rsi)).unwrap();
-        //TODO: error handling
-        let mut file = std::fs::File::create(output_path).expect("Could not create file");
-        ParquetWriter::new(&mut file)
-            .finish(&mut df)
-            .expect("Could not write to file");
-        Ok(())
+        let mut df = storage::klines_to_df(&data)?;
+        storage::write_parquet(output_path, &mut df)
     }
 }
-
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    // cant test this 
-}
\ No newline at end of file
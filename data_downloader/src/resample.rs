@@ -0,0 +1,143 @@
+use crate::error::{Error, Result};
+use crate::interval::Interval;
+use crate::kline::Kline;
+
+/// Aggregates a `from` kline series into `to` candles.
+///
+/// open is the first open, high/low the extremes, close the last close and the volumes and
+/// trade counts are summed. Candles are aligned the way Binance aligns them (see [`Interval::candle_start`]),
+/// so the output can be compared to directly downloaded `to` data. The last candle is kept
+/// even if the input ends before it closes, like the live candle Binance returns.
+/// Input that is not strictly increasing in open_time, e.g. a repeated kline, is rejected.
+pub fn resample(data: &[Kline], from: Interval, to: Interval) -> Result<Vec<Kline>> {
+    if !to.is_multiple_of(from) {
+        return Err(Error::ResampleError(format!("{to} candles can not be built from {from} candles")));
+    }
+    if let Some(pair) = data.windows(2).find(|pair| pair[1].open_time <= pair[0].open_time) {
+        return Err(Error::ResampleError(format!(
+            "klines are not strictly increasing in open_time ({} after {})",
+            pair[1].open_time, pair[0].open_time
+        )));
+    }
+    if from == to {
+        return Ok(data.to_vec());
+    }
+
+    let mut resampled: Vec<Kline> = Vec::new();
+    for kline in data {
+        let start = to.candle_start(kline.open_time);
+        match resampled.last_mut() {
+            Some(current) if current.open_time == start => {
+                let close_time = current.close_time;
+                current.absorb(kline);
+                current.close_time = close_time;
+            }
+            _ => resampled.push(Kline {
+                open_time: start,
+                close_time: to.next_candle_start(start) - 1,
+                ..kline.clone()
+            }),
+        }
+    }
+    Ok(resampled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{a} != {b}");
+    }
+
+    fn day(open_time: u64, close: f64) -> Kline {
+        Kline {
+            open_time,
+            open: close,
+            high: close + 1.0,
+            low: close - 1.0,
+            close,
+            volume: 1.0,
+            close_time: open_time + 86_399_999,
+            quote_asset_volume: close,
+            trade_number: 10,
+            buy_base: 0.5,
+            buy_quote: close / 2.0,
+        }
+    }
+
+    fn fixture(json: &str) -> Vec<Kline> {
+        serde_json::from_str(json).expect("kline fixtures are valid JSON")
+    }
+
+    /// The fixtures are made up in Binance's REST format, three hours of `BTCUSDT` from 2024-01-01
+    /// 00:00 UTC, the hourly rows summed up from the minutes independently of this code.
+    #[test]
+    fn matches_the_hourly_fixture() {
+        let minutes = fixture(include_str!("../fixtures/BTCUSDT-1m-20240101.json"));
+        let expected = fixture(include_str!("../fixtures/BTCUSDT-1h-20240101.json"));
+        assert_eq!(minutes.len(), 180);
+
+        let resampled = resample(&minutes, Interval::OneMinute, Interval::OneHour).unwrap();
+        assert_eq!(resampled.len(), expected.len());
+        for (r, e) in resampled.iter().zip(&expected) {
+            assert_eq!(r.open_time, e.open_time);
+            assert_eq!(r.close_time, e.close_time);
+            assert_eq!(r.trade_number, e.trade_number);
+            assert_close(r.open, e.open);
+            assert_close(r.high, e.high);
+            assert_close(r.low, e.low);
+            assert_close(r.close, e.close);
+            assert_close(r.volume, e.volume);
+            assert_close(r.quote_asset_volume, e.quote_asset_volume);
+            assert_close(r.buy_base, e.buy_base);
+            assert_close(r.buy_quote, e.buy_quote);
+        }
+    }
+
+    #[test]
+    fn calendar_months() {
+        // 2024-01-30 .. 2024-02-02
+        let days: Vec<Kline> = (0..4)
+            .map(|i| day(1_706_572_800_000 + i * 86_400_000, 100.0 + i as f64))
+            .collect();
+
+        let months = resample(&days, Interval::OneDay, Interval::OneMonth).unwrap();
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0].open_time, 1_704_067_200_000);
+        assert_eq!(months[0].close_time, 1_706_745_599_999);
+        assert_eq!(months[0].close, 101.0);
+        assert_eq!(months[0].trade_number, 20);
+        assert_eq!(months[1].open_time, 1_706_745_600_000);
+        // 2024 is a leap year, February has 29 days
+        assert_eq!(months[1].close_time, 1_709_251_199_999);
+        assert_eq!(months[1].open, 102.0);
+        assert_eq!(months[1].high, 104.0);
+        assert_eq!(months[1].low, 101.0);
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        // Saturday 2024-01-06 .. Tuesday 2024-01-09
+        let days: Vec<Kline> = (0..4)
+            .map(|i| day(1_704_499_200_000 + i * 86_400_000, 100.0))
+            .collect();
+        let weeks = resample(&days, Interval::OneDay, Interval::OneWeek).unwrap();
+        assert_eq!(weeks.iter().map(|k| k.open_time).collect::<Vec<_>>(), vec![1_704_067_200_000, 1_704_672_000_000]);
+    }
+
+    #[test]
+    fn rejects_repeated_open_times() {
+        let days = [day(1_704_067_200_000, 100.0), day(1_704_153_600_000, 101.0), day(1_704_153_600_000, 101.0)];
+        assert!(resample(&days, Interval::OneDay, Interval::OneWeek).is_err());
+        assert!(resample(&days, Interval::OneDay, Interval::OneDay).is_err());
+        assert!(resample(&[days[1].clone(), days[0].clone()], Interval::OneDay, Interval::OneWeek).is_err());
+    }
+
+    #[test]
+    fn rejects_misaligned_intervals() {
+        assert!(resample(&[], Interval::ThreeMinute, Interval::FiveMinute).is_err());
+        assert!(resample(&[], Interval::FourHour, Interval::OneHour).is_err());
+        assert!(resample(&[], Interval::OneWeek, Interval::OneMonth).is_err());
+    }
+}
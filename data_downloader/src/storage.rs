@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use polars::prelude::*;
+use tracing::info;
+
+use crate::error::Result;
+use crate::kline::Kline;
+
+/// Builds the raw kline `DataFrame` with the column layout used by every stored dataset.
+pub fn klines_to_df(data: &[Kline]) -> Result<DataFrame> {
+    let df = df!(
+        "open_time" => data.iter().map(|k| k.open_time).collect::<Vec<u64>>(),
+        "open" => data.iter().map(|k| k.open).collect::<Vec<f64>>(),
+        "high" => data.iter().map(|k| k.high).collect::<Vec<f64>>(),
+        "low" => data.iter().map(|k| k.low).collect::<Vec<f64>>(),
+        "close" => data.iter().map(|k| k.close).collect::<Vec<f64>>(),
+        "volume" => data.iter().map(|k| k.volume).collect::<Vec<f64>>(),
+        "close_time" => data.iter().map(|k| k.close_time).collect::<Vec<u64>>(),
+        "quote_asset_volume" => data.iter().map(|k| k.quote_asset_volume).collect::<Vec<f64>>(),
+        "trade_number" => data.iter().map(|k| k.trade_number as u64).collect::<Vec<u64>>(),
+        "buy_base" => data.iter().map(|k| k.buy_base).collect::<Vec<f64>>(),
+        "buy_quote" => data.iter().map(|k| k.buy_quote).collect::<Vec<f64>>(),
+    )?;
+    Ok(df)
+}
+
+/// Inverse of [`klines_to_df`], extra columns (features, tags) are ignored.
+pub fn df_to_klines(df: &DataFrame) -> Result<Vec<Kline>> {
+    let open_time = df.column("open_time")?.u64()?;
+    let open = df.column("open")?.f64()?;
+    let high = df.column("high")?.f64()?;
+    let low = df.column("low")?.f64()?;
+    let close = df.column("close")?.f64()?;
+    let volume = df.column("volume")?.f64()?;
+    let close_time = df.column("close_time")?.u64()?;
+    let quote_asset_volume = df.column("quote_asset_volume")?.f64()?;
+    let trade_number = df.column("trade_number")?.u64()?;
+    let buy_base = df.column("buy_base")?.f64()?;
+    let buy_quote = df.column("buy_quote")?.f64()?;
+
+    Ok((0..df.height())
+        .map(|i| Kline {
+            open_time: open_time.get(i).unwrap_or_default(),
+            open: open.get(i).unwrap_or_default(),
+            high: high.get(i).unwrap_or_default(),
+            low: low.get(i).unwrap_or_default(),
+            close: close.get(i).unwrap_or_default(),
+            volume: volume.get(i).unwrap_or_default(),
+            close_time: close_time.get(i).unwrap_or_default(),
+            quote_asset_volume: quote_asset_volume.get(i).unwrap_or_default(),
+            trade_number: trade_number.get(i).unwrap_or_default() as usize,
+            buy_base: buy_base.get(i).unwrap_or_default(),
+            buy_quote: buy_quote.get(i).unwrap_or_default(),
+        })
+        .collect())
+}
+
+pub fn read_klines<P: AsRef<Path>>(path: P) -> Result<Vec<Kline>> {
+    df_to_klines(&read_parquet(path)?)
+}
+
+pub fn write_klines<P: AsRef<Path>>(path: P, data: &[Kline]) -> Result<()> {
+    write_parquet(path, &mut klines_to_df(data)?)
+}
+
+pub fn read_parquet<P: AsRef<Path>>(path: P) -> Result<DataFrame> {
+    info!("Reading file {}", path.as_ref().display());
+    let file = std::fs::File::open(path)?;
+    Ok(ParquetReader::new(file).finish()?)
+}
+
+pub fn write_parquet<P: AsRef<Path>>(path: P, df: &mut DataFrame) -> Result<()> {
+    info!("Saving file to {}", path.as_ref().display());
+    let mut file = std::fs::File::create(path)?;
+    ParquetWriter::new(&mut file).finish(df)?;
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use futures::StreamExt;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, info, warn};
+
+use crate::error::{Error, Result};
+use crate::kline::Kline;
+
+/// Payload of a `<symbol>@kline_<interval>` stream message.
+#[derive(Debug, Deserialize)]
+pub struct KlineEvent {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "k")]
+    pub kline: StreamKline,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamKline {
+    #[serde(rename = "t")]
+    pub open_time: u64,
+    #[serde(rename = "T")]
+    pub close_time: u64,
+    #[serde(rename = "i")]
+    pub interval: String,
+    #[serde(rename = "o")]
+    pub open: String,
+    #[serde(rename = "h")]
+    pub high: String,
+    #[serde(rename = "l")]
+    pub low: String,
+    #[serde(rename = "c")]
+    pub close: String,
+    #[serde(rename = "v")]
+    pub volume: String,
+    #[serde(rename = "q")]
+    pub quote_asset_volume: String,
+    #[serde(rename = "n")]
+    pub trade_number: usize,
+    #[serde(rename = "V")]
+    pub buy_base: String,
+    #[serde(rename = "Q")]
+    pub buy_quote: String,
+    /// Whether this is the final update of the candle.
+    #[serde(rename = "x")]
+    pub closed: bool,
+}
+
+impl StreamKline {
+    /// Fails on prices and amounts that are no finite number, instead of trading on made-up zeros.
+    pub fn to_kline(&self) -> Result<Kline> {
+        let parse = |s: &str| s.parse::<f64>().ok().filter(|n| n.is_finite()).ok_or_else(|| Error::InvalidNumber(s.to_string()));
+        Ok(Kline {
+            open_time: self.open_time,
+            open: parse(&self.open)?,
+            high: parse(&self.high)?,
+            low: parse(&self.low)?,
+            close: parse(&self.close)?,
+            volume: parse(&self.volume)?,
+            close_time: self.close_time,
+            quote_asset_volume: parse(&self.quote_asset_volume)?,
+            trade_number: self.trade_number,
+            buy_base: parse(&self.buy_base)?,
+            buy_quote: parse(&self.buy_quote)?,
+        })
+    }
+}
+
+/// Subscribes to the kline stream of `symbol` and forwards every closed candle to `sender`.
+/// Returns when the server closes the connection or the receiver is dropped.
+pub async fn stream_klines(base_url: &str, symbol: &str, interval: &str, sender: mpsc::Sender<Kline>) -> Result<()> {
+    let url = format!("{base_url}/{}@kline_{interval}", symbol.to_lowercase());
+    info!("Connecting to {url}");
+    let (mut ws, _) = connect_async(url.as_str()).await?;
+
+    while let Some(message) = ws.next().await {
+        let text = match message? {
+            Message::Text(text) => text,
+            Message::Close(frame) => {
+                info!("Stream closed: {frame:?}");
+                break;
+            }
+            _ => continue,
+        };
+        let event: KlineEvent = match serde_json::from_str(&text) {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Could not parse stream message: {e}");
+                continue;
+            }
+        };
+        if !event.kline.closed {
+            continue;
+        }
+        let kline = match event.kline.to_kline() {
+            Ok(kline) => kline,
+            Err(e) => {
+                warn!("Dropping candle {} {}: {e}", event.symbol, event.kline.open_time);
+                continue;
+            }
+        };
+        debug!("Closed candle {} {}", event.symbol, kline.open_time);
+        if sender.send(kline).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_kline_event() {
+        let json = r#"{"e":"kline","E":1704067260001,"s":"BTCUSDT","k":{"t":1704067200000,"T":1704067259999,"s":"BTCUSDT","i":"1m","f":100,"L":200,"o":"42283.58","c":"42300.00","h":"42310.10","l":"42280.00","v":"12.5","n":101,"x":true,"q":"528000.5","V":"6.25","Q":"264000.25","B":"0"}}"#;
+        let event: KlineEvent = serde_json::from_str(json).unwrap();
+        assert!(event.kline.closed);
+        let kline = event.kline.to_kline().unwrap();
+        assert_eq!(kline.open_time, 1704067200000);
+        assert_eq!(kline.close, 42300.0);
+        assert_eq!(kline.trade_number, 101);
+        assert_eq!(kline.buy_quote, 264000.25);
+
+        for broken in [r#""c":"""#, r#""c":"4230O.00""#, r#""c":"NaN""#] {
+            let event: KlineEvent = serde_json::from_str(&json.replace(r#""c":"42300.00""#, broken)).unwrap();
+            assert!(matches!(event.kline.to_kline(), Err(Error::InvalidNumber(_))), "{broken}");
+        }
+    }
+}
@@ -74,4 +74,45 @@ pub fn calculate_rsi(data: &DataFrame, period: usize) -> Vec<f64> {
 
     rsi_values.extend(rsi_iter);
     rsi_values
-}
\ No newline at end of file
+}
+
+/// Exponential moving average of the close prices, seeded with the simple average of the
+/// first `period` closes. The first value corresponds to row `period - 1`.
+pub fn calculate_ema(data: &DataFrame, period: usize) -> Vec<f64> {
+    let close_prices: Vec<f64> = data.column("close")
+        .expect("Close prices column not found")
+        .f64()
+        .expect("Close prices are not floats")
+        .into_no_null_iter()
+        .collect();
+
+    if period == 0 || close_prices.len() < period {
+        return Vec::new();
+    }
+
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let seed = close_prices[..period].iter().sum::<f64>() / period as f64;
+
+    let mut ema_values = Vec::with_capacity(close_prices.len() - period + 1);
+    ema_values.push(seed);
+    let mut ema = seed;
+    for close in &close_prices[period..] {
+        ema = alpha * close + (1.0 - alpha) * ema;
+        ema_values.push(ema);
+    }
+    ema_values
+}
+
+/// Close-to-close returns. The first value corresponds to row 1.
+pub fn calculate_returns(data: &DataFrame, log: bool) -> Vec<f64> {
+    let close_prices: Vec<f64> = data.column("close")
+        .expect("Close prices column not found")
+        .f64()
+        .expect("Close prices are not floats")
+        .into_no_null_iter()
+        .collect();
+
+    close_prices.windows(2)
+        .map(|w| if log { (w[1] / w[0]).ln() } else { w[1] / w[0] - 1.0 })
+        .collect()
+}
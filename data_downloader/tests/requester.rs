@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use data_downloader::{read_klines, Error, Kline, Requester};
+use mock_exchange::{Faults, MockConfig, MockExchange};
+use tokio::sync::mpsc;
+
+// 2024-01-01 00:00 to 04:00 UTC, the 1h fixture
+const START: usize = 1704067200000;
+const END: usize = 1704081600000;
+
+fn requester(mock: &MockExchange) -> Requester {
+    Requester::default().with_base_url(&mock.url()).with_ws_url(&mock.ws_url())
+}
+
+fn output_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("iambot-{name}-{}.parquet", std::process::id()));
+    path.to_string_lossy().into_owned()
+}
+
+async fn stream(mock: &MockExchange) -> (data_downloader::Result<()>, Vec<Kline>) {
+    let (sender, mut receiver) = mpsc::channel(16);
+    let result = requester(mock).connect_to_ws("BTCUSDT", "1h", sender).await;
+    let mut klines = Vec::new();
+    while let Some(kline) = receiver.recv().await {
+        klines.push(kline);
+    }
+    (result, klines)
+}
+
+#[tokio::test]
+async fn downloads_historical_klines() {
+    let mock = MockExchange::start(MockConfig {
+        faults: Faults { latency: Duration::from_millis(20), ..Faults::default() },
+        ..MockConfig::default()
+    })
+    .await
+    .unwrap();
+    let path = output_path("download");
+
+    requester(&mock).get_historical_data("BTCUSDT", "1h", START, END, &path).await.unwrap();
+
+    let klines = read_klines(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(klines.len(), 4);
+    assert_eq!(klines[0].open_time, START as u64);
+    assert_eq!(klines[0].close, 42475.23);
+    assert!(klines.windows(2).all(|w| w[0].open_time < w[1].open_time));
+}
+
+#[tokio::test]
+async fn surfaces_rate_limits_and_malformed_payloads() {
+    let mock = MockExchange::start(MockConfig::default()).await.unwrap();
+    let path = output_path("faults");
+
+    mock.set_faults(Faults { rate_limit: Some(0), ..Faults::default() });
+    match requester(&mock).get_historical_data("BTCUSDT", "1h", START, END, &path).await {
+        Err(Error::Status { status: 429, .. }) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+
+    mock.set_faults(Faults { malformed: true, ..Faults::default() });
+    match requester(&mock).get_historical_data("BTCUSDT", "1h", START, END, &path).await {
+        Err(Error::Json(_)) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+    assert_eq!(mock.request_count(), 2);
+    assert!(!std::path::Path::new(&path).exists());
+}
+
+#[tokio::test]
+async fn streams_closed_candles() {
+    let mock = MockExchange::start(MockConfig::default()).await.unwrap();
+    let (result, klines) = stream(&mock).await;
+    result.unwrap();
+    assert_eq!(klines.len(), 4);
+    assert_eq!(klines[3].open_time, 1704078000000);
+    assert_eq!(klines[0].trade_number, 47134);
+}
+
+#[tokio::test]
+async fn stream_faults() {
+    let mock = MockExchange::start(MockConfig::default()).await.unwrap();
+
+    mock.set_faults(Faults { disconnect_after: Some(2), ..Faults::default() });
+    let (result, klines) = stream(&mock).await;
+    assert!(matches!(result, Err(Error::WebSocket(_))), "unexpected {result:?}");
+    assert_eq!(klines.len(), 2);
+
+    // unparsable messages are skipped
+    mock.set_faults(Faults { malformed: true, ..Faults::default() });
+    let (result, klines) = stream(&mock).await;
+    result.unwrap();
+    assert!(klines.is_empty());
+}
@@ -0,0 +1,98 @@
+//! One-way klines from one thread to another, through the fifo handles and through the shared
+//! memory ring. Run with `cargo bench -p ipc_messager`.
+
+use std::time::{Duration, Instant};
+
+use ipc_messager::fifo::{Fifo, FifoHandle};
+use ipc_messager::message::{KlineRecord, Message};
+use ipc_messager::shm::{ShmConsumer, ShmProducer};
+
+const MESSAGES: u64 = 200_000;
+
+/// `open_time` carries the nanoseconds since `start` at which the record was sent.
+fn record(start: Instant) -> KlineRecord {
+    KlineRecord {
+        open_time: start.elapsed().as_nanos() as u64,
+        open: 42_000.0,
+        high: 42_100.0,
+        low: 41_900.0,
+        close: 42_050.0,
+        volume: 12.5,
+        close_time: 0,
+        quote_asset_volume: 525_000.0,
+        trade_number: 1_000,
+        buy_base: 6.0,
+        buy_quote: 252_000.0,
+    }
+}
+
+struct Stats {
+    total: Duration,
+    latencies: Vec<u64>,
+}
+
+impl Stats {
+    fn print(mut self, name: &str) {
+        self.latencies.sort_unstable();
+        let percentile = |p: f64| self.latencies[((self.latencies.len() - 1) as f64 * p) as usize];
+        println!(
+            "{name:>5}: {:>10.0} msg/s, latency p50 {:>7} ns, p99 {:>8} ns, max {:>9} ns",
+            self.latencies.len() as f64 / self.total.as_secs_f64(),
+            percentile(0.5),
+            percentile(0.99),
+            self.latencies.last().unwrap(),
+        );
+    }
+}
+
+fn fifo() -> Stats {
+    let path = std::env::temp_dir().join(format!("iam-bench-fifo.{}", std::process::id()));
+    let fifo = Fifo::new(path.clone()).unwrap();
+    let start = Instant::now();
+    let producer = std::thread::spawn(move || {
+        let mut handle = FifoHandle::open(path).unwrap();
+        for _ in 0..MESSAGES {
+            let message = Message::Published { topic: "BTCUSDT@1m".to_string(), kline: record(start), dropped: 0 };
+            handle.send_message(&message).unwrap();
+        }
+    });
+    let mut handle = fifo.open().unwrap();
+    let mut latencies = Vec::with_capacity(MESSAGES as usize);
+    let began = Instant::now();
+    for _ in 0..MESSAGES {
+        let Message::Published { kline, .. } = handle.recv_message().unwrap() else {
+            panic!("unexpected message");
+        };
+        latencies.push(start.elapsed().as_nanos() as u64 - kline.open_time);
+    }
+    let total = began.elapsed();
+    producer.join().unwrap();
+    Stats { total, latencies }
+}
+
+fn shm() -> Stats {
+    let path = std::env::temp_dir().join(format!("iam-bench-shm.{}", std::process::id()));
+    // large enough that the consumer never falls a full ring behind
+    let mut producer = ShmProducer::<KlineRecord>::create(&path, MESSAGES).unwrap();
+    let mut consumer = ShmConsumer::<KlineRecord>::open(&path).unwrap();
+    let start = Instant::now();
+    let reader = std::thread::spawn(move || {
+        let mut latencies = Vec::with_capacity(MESSAGES as usize);
+        let began = Instant::now();
+        for _ in 0..MESSAGES {
+            let kline = consumer.next_blocking();
+            latencies.push(start.elapsed().as_nanos() as u64 - kline.open_time);
+        }
+        Stats { total: began.elapsed(), latencies }
+    });
+    for _ in 0..MESSAGES {
+        producer.push(&record(start));
+    }
+    reader.join().unwrap()
+}
+
+fn main() {
+    println!("{MESSAGES} klines from one thread to another");
+    fifo().print("fifo");
+    shm().print("shm");
+}
@@ -0,0 +1,449 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use data_downloader::Kline;
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result};
+use crate::message::{Backpressure, Message};
+use crate::server::AbortOnDrop;
+use crate::transport::{Connection, Listener, MessageReader, MessageWriter, Transport};
+
+/// How long a new bus connection may take to say what it subscribes to.
+const SUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most klines queued for a subscriber of another process, whatever capacity it asks for.
+pub const MAX_REMOTE_CAPACITY: usize = 4096;
+
+/// Topic of the klines of `symbol` at `interval`, e.g. `BTCUSDT@1m`.
+pub fn topic(symbol: &str, interval: &str) -> String {
+    format!("{symbol}@{interval}")
+}
+
+/// Whether `topic` is one of the topics `filter` asks for, `*` matching any symbol or interval:
+/// `BTCUSDT@*`, `*@1m` or just `*`.
+pub fn matches(filter: &str, topic: &str) -> bool {
+    let (filter_symbol, filter_interval) = filter.split_once('@').unwrap_or((filter, "*"));
+    let (symbol, interval) = topic.split_once('@').unwrap_or((topic, ""));
+    (filter_symbol == "*" || filter_symbol == symbol) && (filter_interval == "*" || filter_interval == interval)
+}
+
+/// A kline of a topic, `dropped` counts the klines the subscriber missed before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub topic: String,
+    pub kline: Kline,
+    pub dropped: u64,
+}
+
+/// Topic based fan-out of klines to in-process [`Subscription`]s and, through [`Bus::serve`], to
+/// other processes. Each in-process subscriber picks its own [`Backpressure`], a slow one only holds
+/// up the publisher when it asked for [`Backpressure::Block`].
+#[derive(Clone, Default)]
+pub struct Bus {
+    subscribers: Arc<StdMutex<Vec<Arc<Subscriber>>>>,
+}
+
+struct Subscriber {
+    filters: Vec<String>,
+    queue: Queue,
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        // the last bus handle is gone, wake the subscription so it ends
+        if let Queue::DropOldest(ring) = &self.queue {
+            ring.state.lock().expect("ring lock").bus_gone = true;
+            ring.notify.notify_one();
+        }
+    }
+}
+
+enum Queue {
+    Block(mpsc::Sender<(String, Kline)>),
+    DropOldest(Arc<Ring>),
+}
+
+impl Queue {
+    fn is_closed(&self) -> bool {
+        match self {
+            Queue::Block(sender) => sender.is_closed(),
+            Queue::DropOldest(ring) => ring.state.lock().expect("ring lock").closed,
+        }
+    }
+}
+
+/// Bounded queue making room by dropping its oldest entry.
+struct Ring {
+    state: StdMutex<RingState>,
+    notify: Notify,
+    capacity: usize,
+}
+
+struct RingState {
+    items: VecDeque<(String, Kline)>,
+    dropped: u64,
+    /// The subscription was dropped.
+    closed: bool,
+    /// The bus was dropped, nothing more arrives.
+    bus_gone: bool,
+}
+
+impl Ring {
+    fn push(&self, topic: &str, kline: &Kline) {
+        let mut state = self.state.lock().expect("ring lock");
+        if state.items.len() >= self.capacity {
+            state.items.pop_front();
+            state.dropped += 1;
+        }
+        state.items.push_back((topic.to_string(), kline.clone()));
+        drop(state);
+        self.notify.notify_one();
+    }
+
+    /// Oldest queued kline, `None` once the bus is gone and the queue drained.
+    async fn pop(&self) -> Option<Delivery> {
+        loop {
+            {
+                let mut state = self.state.lock().expect("ring lock");
+                if let Some((topic, kline)) = state.items.pop_front() {
+                    return Some(Delivery { topic, kline, dropped: std::mem::take(&mut state.dropped) });
+                }
+                if state.bus_gone {
+                    return None;
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to the topics matching any of `filters`, see [`matches`].
+    pub fn subscribe(&self, filters: &[String], policy: Backpressure) -> Subscription {
+        let (queue, receiver) = match policy {
+            Backpressure::Block { capacity } => {
+                let (sender, receiver) = mpsc::channel(capacity.max(1));
+                (Queue::Block(sender), Receiver::Block(receiver))
+            }
+            Backpressure::DropOldest { capacity } => {
+                let ring = Arc::new(Ring {
+                    state: StdMutex::new(RingState { items: VecDeque::new(), dropped: 0, closed: false, bus_gone: false }),
+                    notify: Notify::new(),
+                    capacity: capacity.max(1),
+                });
+                (Queue::DropOldest(ring.clone()), Receiver::DropOldest(ring))
+            }
+        };
+        let subscriber = Arc::new(Subscriber { filters: filters.to_vec(), queue });
+        self.subscribers.lock().expect("subscribers lock").push(subscriber);
+        Subscription { receiver }
+    }
+
+    /// Subscriptions still alive.
+    pub fn subscribers(&self) -> usize {
+        let mut subscribers = self.subscribers.lock().expect("subscribers lock");
+        subscribers.retain(|s| !s.queue.is_closed());
+        subscribers.len()
+    }
+
+    /// Hands `kline` to every subscriber of `topic`, waiting for those that block.
+    pub async fn publish(&self, topic: &str, kline: &Kline) {
+        let targets: Vec<_> = {
+            let mut subscribers = self.subscribers.lock().expect("subscribers lock");
+            subscribers.retain(|s| !s.queue.is_closed());
+            subscribers
+                .iter()
+                .filter(|s| s.filters.iter().any(|f| matches(f, topic)))
+                .cloned()
+                .collect()
+        };
+        for subscriber in targets {
+            match &subscriber.queue {
+                // a subscriber gone meanwhile is pruned on the next publish
+                Queue::Block(sender) => {
+                    let _ = sender.send((topic.to_string(), kline.clone())).await;
+                }
+                Queue::DropOldest(ring) => ring.push(topic, kline),
+            }
+        }
+    }
+
+    /// Serves subscribers of other processes on `listener` until `cancel` is cancelled. A client opens
+    /// with [`Message::BusSubscribe`] and then receives [`Message::Published`], see [`RemoteSubscription`].
+    /// Clients asking for [`Backpressure::Block`] are disconnected, another process never holds up the publisher.
+    pub async fn serve<L: Listener + 'static>(&self, listener: L, cancel: CancellationToken) -> Result<()> {
+        let mut connections = JoinSet::new();
+        let result = loop {
+            let connection = tokio::select! {
+                _ = cancel.cancelled() => break Ok(()),
+                connection = listener.accept() => connection,
+            };
+            match connection {
+                Ok(connection) => {
+                    let (bus, cancel) = (self.clone(), cancel.clone());
+                    connections.spawn(async move {
+                        if let Err(e) = bus.serve_subscriber(connection, cancel).await {
+                            if !e.is_disconnect() {
+                                warn!("Bus subscriber failed: {e}");
+                            }
+                        }
+                    });
+                }
+                Err(Error::PeerRejected { uid }) => warn!("Rejected bus subscriber of user {uid}"),
+                Err(Error::Handshake(e)) => warn!("Bus subscriber failed the handshake: {e}"),
+                Err(e) => break Err(e),
+            }
+            while connections.try_join_next().is_some() {}
+        };
+        connections.shutdown().await;
+        result
+    }
+
+    async fn serve_subscriber<C: Connection>(&self, mut connection: C, cancel: CancellationToken) -> Result<()> {
+        let (topics, policy) = match tokio::time::timeout(SUBSCRIBE_TIMEOUT, connection.recv_message()).await {
+            Ok(Ok(Message::BusSubscribe { topics, policy })) => (topics, policy),
+            Ok(Ok(other)) => {
+                warn!("Expected a bus subscription, got {other:?}");
+                return Ok(());
+            }
+            Ok(Err(e)) => return Err(e),
+            Err(_) => {
+                debug!("Bus client never subscribed");
+                return Ok(());
+            }
+        };
+        // another process never holds up the publisher nor reserves unbounded memory
+        let capacity = match policy {
+            Backpressure::DropOldest { capacity } => capacity.min(MAX_REMOTE_CAPACITY),
+            Backpressure::Block { .. } => return Err(Error::RemoteBlock),
+        };
+        let mut subscription = self.subscribe(&topics, Backpressure::DropOldest { capacity });
+        let (mut reader, mut writer) = connection.into_split();
+        // subscribers send nothing more, whatever arrives means the connection is gone
+        let mut closed = AbortOnDrop(tokio::spawn(async move {
+            let _ = reader.recv_message().await;
+        }));
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return Ok(()),
+                _ = &mut closed.0 => return Ok(()),
+                delivery = subscription.next() => {
+                    let Some(Delivery { topic, kline, dropped }) = delivery else {
+                        return Ok(());
+                    };
+                    writer.send_message(&Message::Published { topic, kline: (&kline).into(), dropped }).await?;
+                }
+            }
+        }
+    }
+}
+
+/// Klines of the topics subscribed to on a [`Bus`], in the order they were published.
+pub struct Subscription {
+    receiver: Receiver,
+}
+
+enum Receiver {
+    Block(mpsc::Receiver<(String, Kline)>),
+    DropOldest(Arc<Ring>),
+}
+
+impl Subscription {
+    /// Next kline, `None` once the bus is gone.
+    pub async fn next(&mut self) -> Option<Delivery> {
+        match &mut self.receiver {
+            Receiver::Block(receiver) => receiver.recv().await.map(|(topic, kline)| Delivery { topic, kline, dropped: 0 }),
+            Receiver::DropOldest(ring) => ring.pop().await,
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Receiver::DropOldest(ring) = &self.receiver {
+            ring.state.lock().expect("ring lock").closed = true;
+        }
+    }
+}
+
+/// Subscription to the [`Bus`] of another process, queued as [`Backpressure::DropOldest`] of at most
+/// [`MAX_REMOTE_CAPACITY`] klines.
+pub struct RemoteSubscription<C> {
+    connection: C,
+}
+
+impl<C: Connection> RemoteSubscription<C> {
+    /// Fails with [`Error::RemoteBlock`] for [`Backpressure::Block`], the bus does not wait for other processes.
+    pub async fn connect<T>(transport: &T, path: &std::path::Path, topics: &[String], policy: Backpressure) -> Result<Self>
+    where
+        T: Transport<Connection = C>,
+    {
+        if matches!(policy, Backpressure::Block { .. }) {
+            return Err(Error::RemoteBlock);
+        }
+        let mut connection = transport.connect(path).await?;
+        connection.send_message(&Message::BusSubscribe { topics: topics.to_vec(), policy }).await?;
+        Ok(Self { connection })
+    }
+
+    pub async fn next(&mut self) -> Result<Delivery> {
+        loop {
+            if let Message::Published { topic, kline, dropped } = self.connection.recv_message().await? {
+                return Ok(Delivery { topic, kline: kline.into(), dropped });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unix::UnixTransport;
+
+    fn kline(open_time: u64) -> Kline {
+        Kline {
+            open_time,
+            open: 1.0,
+            high: 1.0,
+            low: 1.0,
+            close: 1.0,
+            volume: 1.0,
+            close_time: open_time + 59_999,
+            quote_asset_volume: 1.0,
+            trade_number: 1,
+            buy_base: 0.5,
+            buy_quote: 0.5,
+        }
+    }
+
+    #[test]
+    fn topic_filters() {
+        assert!(matches("BTCUSDT@1m", &topic("BTCUSDT", "1m")));
+        assert!(matches("BTCUSDT@*", "BTCUSDT@1h"));
+        assert!(matches("BTCUSDT", "BTCUSDT@1h"));
+        assert!(matches("*@1m", "ETHUSDT@1m"));
+        assert!(matches("*", "ETHUSDT@1m"));
+        assert!(!matches("BTCUSDT@1m", "BTCUSDT@1h"));
+        assert!(!matches("*@1h", "ETHUSDT@1m"));
+    }
+
+    #[tokio::test]
+    async fn backpressure_policies() {
+        let bus = Bus::new();
+        let mut lossy = bus.subscribe(&["BTCUSDT@1m".to_string()], Backpressure::DropOldest { capacity: 2 });
+        let mut blocking = bus.subscribe(&["*@1m".to_string()], Backpressure::Block { capacity: 1 });
+
+        bus.publish("BTCUSDT@1m", &kline(0)).await;
+        bus.publish("BTCUSDT@1h", &kline(1)).await;
+        // the blocking subscriber is full, the publisher waits for it
+        let eth = kline(2);
+        let publish = bus.publish("ETHUSDT@1m", &eth);
+        tokio::pin!(publish);
+        assert!(tokio::time::timeout(Duration::from_millis(50), &mut publish).await.is_err());
+        assert_eq!(blocking.next().await.unwrap().kline, kline(0));
+        publish.await;
+        assert_eq!(blocking.next().await.unwrap().topic, "ETHUSDT@1m");
+        // gone subscribers hold up nobody
+        drop(blocking);
+
+        for t in 3..6 {
+            bus.publish("BTCUSDT@1m", &kline(t)).await;
+        }
+        let delivery = lossy.next().await.unwrap();
+        assert_eq!((delivery.kline.open_time, delivery.dropped), (4, 2));
+        assert_eq!(lossy.next().await.unwrap().dropped, 0);
+
+        drop(lossy);
+        assert_eq!(bus.subscribers(), 0);
+    }
+
+    #[tokio::test]
+    async fn subscriptions_end_with_the_bus() {
+        let bus = Bus::new();
+        let mut lossy = bus.subscribe(&["*".to_string()], Backpressure::DropOldest { capacity: 2 });
+        let mut blocking = bus.subscribe(&["*".to_string()], Backpressure::Block { capacity: 2 });
+        bus.publish("BTCUSDT@1m", &kline(0)).await;
+        let waiting = tokio::spawn(async move {
+            let first = lossy.next().await.map(|d| d.kline);
+            (first, lossy.next().await)
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(bus);
+
+        let (first, second) = tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+        assert_eq!((first, second), (Some(kline(0)), None));
+        assert_eq!(blocking.next().await.unwrap().kline, kline(0));
+        assert_eq!(blocking.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn remote_subscribers() {
+        let path = std::env::temp_dir().join(format!("iam-bus-test.{}", std::process::id()));
+        let transport = UnixTransport::default();
+        let bus = Bus::new();
+        let cancel = CancellationToken::new();
+        let server = tokio::spawn({
+            let (bus, listener, cancel) = (bus.clone(), transport.bind(&path).unwrap(), cancel.clone());
+            async move { bus.serve(listener, cancel).await }
+        });
+
+        let policy = Backpressure::DropOldest { capacity: 8 };
+        let mut btc = RemoteSubscription::connect(&transport, &path, &["BTCUSDT@*".to_string()], policy).await.unwrap();
+        let mut all = RemoteSubscription::connect(&transport, &path, &["*@1m".to_string()], policy).await.unwrap();
+        while bus.subscribers() < 2 {
+            tokio::task::yield_now().await;
+        }
+        bus.publish("ETHUSDT@1m", &kline(0)).await;
+        bus.publish("BTCUSDT@1m", &kline(60_000)).await;
+        assert_eq!(btc.next().await.unwrap(), Delivery { topic: "BTCUSDT@1m".to_string(), kline: kline(60_000), dropped: 0 });
+        assert_eq!(all.next().await.unwrap().topic, "ETHUSDT@1m");
+        assert_eq!(all.next().await.unwrap().topic, "BTCUSDT@1m");
+
+        // asking to block is refused, by the client and by the bus
+        let blocking = Backpressure::Block { capacity: 8 };
+        assert!(matches!(RemoteSubscription::connect(&transport, &path, &["*".to_string()], blocking).await, Err(Error::RemoteBlock)));
+        let mut raw = transport.connect(&path).await.unwrap();
+        raw.send_message(&Message::BusSubscribe { topics: vec!["*".to_string()], policy: blocking }).await.unwrap();
+        assert!(raw.recv_message().await.unwrap_err().is_disconnect());
+
+        // a stalled remote subscriber is not waited for, and keeps only the newest klines
+        let mut stalled = RemoteSubscription::connect(&transport, &path, &["*@1h".to_string()], Backpressure::DropOldest { capacity: usize::MAX })
+            .await
+            .unwrap();
+        while bus.subscribers() < 3 {
+            tokio::task::yield_now().await;
+        }
+        let published = async {
+            for t in 0..20_000 {
+                bus.publish("BTCUSDT@1h", &kline(t)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), published).await.unwrap();
+        let mut dropped = 0;
+        let mut last = 0;
+        while last < 19_999 {
+            let delivery = stalled.next().await.unwrap();
+            dropped += delivery.dropped;
+            last = delivery.kline.open_time;
+        }
+        assert!(dropped > 0);
+        drop(stalled);
+
+        drop(btc);
+        while bus.subscribers() > 1 {
+            tokio::task::yield_now().await;
+        }
+        cancel.cancel();
+        server.await.unwrap().unwrap();
+        assert!(all.next().await.unwrap_err().is_disconnect());
+    }
+}
@@ -0,0 +1,295 @@
+//! How [`Message`]s are turned into frame payloads.
+//!
+//! Right after connecting, the client sends a frame with the names of the codecs it speaks, most
+//! preferred first and separated by commas, e.g. `json,bincode`. The server answers with the first
+//! of them it supports, or an empty frame when there is none. Everything after that uses the chosen
+//! codec, so a Python client only needs the frame format and JSON.
+
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::error::{Error, Result};
+use crate::frame;
+use crate::message::Message;
+
+/// How long the server waits for a client's offer, so a silent client cannot stall accepting others.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Turns messages into frame payloads and back.
+pub trait Codec {
+    /// Name used during the handshake.
+    fn name(&self) -> &'static str;
+
+    fn encode(&self, message: &Message) -> Result<Vec<u8>>;
+
+    fn decode(&self, bytes: &[u8]) -> Result<Message>;
+}
+
+/// Compact and fast, for Rust peers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bincode;
+
+impl Codec for Bincode {
+    fn name(&self) -> &'static str {
+        "bincode"
+    }
+
+    fn encode(&self, message: &Message) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(message)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Message> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// serde's externally tagged JSON, e.g. `{"Request":{"id":1,"command":"ListDatasets"}}`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl Codec for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn encode(&self, message: &Message) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(message)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Message> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// MessagePack with the same shape as [`Json`]: structs and enum variants are maps keyed by name.
+/// Unlike JSON it carries non-finite floats.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePack;
+
+impl Codec for MessagePack {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn encode(&self, message: &Message) -> Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec_named(message)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Message> {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+}
+
+/// The codecs a connection can agree on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CodecKind {
+    #[default]
+    Bincode,
+    Json,
+    MessagePack,
+}
+
+impl CodecKind {
+    /// Every codec, in the order Rust peers prefer them.
+    pub const ALL: [CodecKind; 3] = [CodecKind::Bincode, CodecKind::Json, CodecKind::MessagePack];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    fn codec(&self) -> &'static dyn Codec {
+        match self {
+            CodecKind::Bincode => &Bincode,
+            CodecKind::Json => &Json,
+            CodecKind::MessagePack => &MessagePack,
+        }
+    }
+}
+
+impl Codec for CodecKind {
+    fn name(&self) -> &'static str {
+        self.codec().name()
+    }
+
+    fn encode(&self, message: &Message) -> Result<Vec<u8>> {
+        self.codec().encode(message)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Message> {
+        self.codec().decode(bytes)
+    }
+}
+
+/// What both ends of a connection agreed on.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Wire {
+    pub(crate) codec: CodecKind,
+    pub(crate) max_frame_len: usize,
+}
+
+impl Wire {
+    pub(crate) async fn send<W: AsyncWrite + Unpin>(&self, writer: &mut W, message: &Message) -> Result<()> {
+        frame::write_frame_async(writer, &self.codec.encode(message)?, self.max_frame_len).await
+    }
+
+    pub(crate) async fn recv<R: AsyncRead + Unpin>(&self, reader: &mut R) -> Result<Message> {
+        self.codec.decode(&frame::read_frame_async(reader, self.max_frame_len).await?)
+    }
+
+    pub(crate) fn send_blocking<W: Write>(&self, writer: &mut W, message: &Message) -> Result<()> {
+        frame::write_frame(writer, &self.codec.encode(message)?, self.max_frame_len)
+    }
+
+    pub(crate) fn recv_blocking<R: Read>(&self, reader: &mut R) -> Result<Message> {
+        self.codec.decode(&frame::read_frame(reader, self.max_frame_len)?)
+    }
+
+    /// Client side of the handshake, offering `codecs`.
+    pub(crate) async fn offer<R, W>(reader: &mut R, writer: &mut W, codecs: &[CodecKind], max_frame_len: usize) -> Result<Self>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        frame::write_frame_async(writer, offer(codecs).as_bytes(), max_frame_len).await?;
+        let answer = frame::read_frame_async(reader, max_frame_len).await?;
+        Ok(Self { codec: accepted(codecs, &answer)?, max_frame_len })
+    }
+
+    /// Server side of the handshake, accepting any of `codecs`. Fails with [`Error::Handshake`].
+    pub(crate) async fn answer<R, W>(reader: &mut R, writer: &mut W, codecs: &[CodecKind], max_frame_len: usize) -> Result<Self>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let answer = async {
+            let offered = tokio::time::timeout(HANDSHAKE_TIMEOUT, frame::read_frame_async(reader, max_frame_len))
+                .await
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+            let codec = choose(codecs, &offered);
+            frame::write_frame_async(writer, codec.map_or("", |c| c.name()).as_bytes(), max_frame_len).await?;
+            Ok(Self { codec: codec.ok_or_else(|| no_common_codec(&offered))?, max_frame_len })
+        };
+        answer.await.map_err(|e| Error::Handshake(Box::new(e)))
+    }
+
+    pub(crate) fn offer_blocking<R: Read, W: Write>(reader: &mut R, writer: &mut W, codecs: &[CodecKind], max_frame_len: usize) -> Result<Self> {
+        frame::write_frame(writer, offer(codecs).as_bytes(), max_frame_len)?;
+        let answer = frame::read_frame(reader, max_frame_len)?;
+        Ok(Self { codec: accepted(codecs, &answer)?, max_frame_len })
+    }
+
+    pub(crate) fn answer_blocking<R: Read, W: Write>(reader: &mut R, writer: &mut W, codecs: &[CodecKind], max_frame_len: usize) -> Result<Self> {
+        let mut answer = || {
+            let offered = frame::read_frame(reader, max_frame_len)?;
+            let codec = choose(codecs, &offered);
+            frame::write_frame(writer, codec.map_or("", |c| c.name()).as_bytes(), max_frame_len)?;
+            Ok(Self { codec: codec.ok_or_else(|| no_common_codec(&offered))?, max_frame_len })
+        };
+        answer().map_err(|e| Error::Handshake(Box::new(e)))
+    }
+}
+
+fn offer(codecs: &[CodecKind]) -> String {
+    codecs.iter().map(|c| c.name()).collect::<Vec<_>>().join(",")
+}
+
+/// First offered codec the server supports.
+fn choose(supported: &[CodecKind], offered: &[u8]) -> Option<CodecKind> {
+    String::from_utf8_lossy(offered)
+        .split(',')
+        .filter_map(|name| CodecKind::from_name(name.trim()))
+        .find(|kind| supported.contains(kind))
+}
+
+fn accepted(offered: &[CodecKind], answer: &[u8]) -> Result<CodecKind> {
+    std::str::from_utf8(answer)
+        .ok()
+        .and_then(CodecKind::from_name)
+        .filter(|kind| offered.contains(kind))
+        .ok_or_else(|| no_common_codec(offer(offered).as_bytes()))
+}
+
+fn no_common_codec(offered: &[u8]) -> Error {
+    Error::NoCommonCodec(String::from_utf8_lossy(offered).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{Command, CommandError, DownloadJob, Event, JobState, JobStatus, LogLevel, LogLine, PnlReport, Reply};
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message::Ack,
+            Message::request(1, Command::StartDownload(DownloadJob {
+                symbol: "BTCUSDT".to_string(),
+                interval: "1m".to_string(),
+                start: Some(1_704_067_200_000),
+                end: None,
+            })),
+            Message::response(2, Ok(Reply::Pnl(PnlReport {
+                quote: "USDT".to_string(),
+                realized: -12.5,
+                unrealized: 3.25,
+                fees: 0.4,
+                equity: 10_009.1,
+            }))),
+            Message::response(3, Err(CommandError::InvalidArgument("x".repeat(300)))),
+            Message::Event(Event::Log(LogLine { level: LogLevel::Info, target: "trader".to_string(), message: "ünïcode".to_string() })),
+            Message::Event(Event::Missed(u64::MAX)),
+        ]
+    }
+
+    #[test]
+    fn roundtrips() {
+        for kind in CodecKind::ALL {
+            for message in messages() {
+                let bytes = kind.encode(&message).unwrap();
+                assert_eq!(kind.decode(&bytes).unwrap(), message, "{}", kind.name());
+            }
+        }
+        let json = Json.encode(&Message::request(7, Command::ListDatasets)).unwrap();
+        assert_eq!(json, br#"{"Request":{"id":7,"command":"ListDatasets"}}"#);
+    }
+
+    #[test]
+    fn msgpack_format() {
+        let bytes = MessagePack.encode(&Message::request(7, Command::ListDatasets)).unwrap();
+        let mut expected = vec![0x81, 0xa7];
+        expected.extend_from_slice(b"Request");
+        expected.extend_from_slice(&[0x82, 0xa2, b'i', b'd', 0x07, 0xa7]);
+        expected.extend_from_slice(b"command");
+        expected.push(0xac);
+        expected.extend_from_slice(b"ListDatasets");
+        assert_eq!(bytes, expected);
+
+        // what JSON cannot carry
+        let running = |progress| Message::Event(Event::JobProgress(JobStatus {
+            id: 1,
+            job: DownloadJob { symbol: "BTCUSDT".to_string(), interval: "1m".to_string(), start: None, end: None },
+            state: JobState::Running { progress },
+        }));
+        let bytes = MessagePack.encode(&running(f64::INFINITY)).unwrap();
+        assert_eq!(MessagePack.decode(&bytes).unwrap(), running(f64::INFINITY));
+        let Message::Event(Event::JobProgress(JobStatus { state: JobState::Running { progress }, .. })) =
+            MessagePack.decode(&MessagePack.encode(&running(f64::NAN)).unwrap()).unwrap()
+        else {
+            panic!("not a job progress");
+        };
+        assert!(progress.is_nan());
+
+        assert!(matches!(MessagePack.decode(&[0xdd, 0xff, 0xff, 0xff, 0xff]), Err(Error::MessagePackDecode(_))));
+        assert!(matches!(MessagePack.decode(&[0x91; 100]), Err(Error::MessagePackDecode(_))));
+    }
+
+    #[test]
+    fn negotiation() {
+        assert_eq!(choose(&CodecKind::ALL, b"msgpack,json"), Some(CodecKind::MessagePack));
+        assert_eq!(choose(&[CodecKind::Json], b"bincode, json"), Some(CodecKind::Json));
+        assert_eq!(choose(&[CodecKind::Bincode], b"cbor,json"), None);
+        assert_eq!(accepted(&[CodecKind::Json], b"json").unwrap(), CodecKind::Json);
+        assert!(matches!(accepted(&[CodecKind::Json], b""), Err(Error::NoCommonCodec(o)) if o == "json"));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::message::{Message, RequestId};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Bincode(bincode::Error),
+    Json(serde_json::Error),
+    MessagePackEncode(rmp_serde::encode::Error),
+    /// The payload is no MessagePack encoded message.
+    MessagePackDecode(rmp_serde::decode::Error),
+    /// The server speaks none of the codecs the client offered, listed comma separated.
+    NoCommonCodec(String),
+    /// A client connected but failed the handshake, the listener stays usable.
+    Handshake(Box<Error>),
+    /// The frame does not start with [`crate::frame::MAGIC`], the peer speaks something else.
+    BadMagic([u8; 4]),
+    UnsupportedVersion(u8),
+    FrameTooLarge { len: usize, max: usize },
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// The peer answered a request with anything but its response.
+    UnexpectedResponse { id: RequestId, got: Box<Message> },
+    /// A user not allowed to talk to the server connected.
+    PeerRejected { uid: u32 },
+    /// The runtime directory belongs to another user.
+    InsecureRuntimeDir(std::path::PathBuf),
+    /// The instance name is no plain directory name, e.g. an absolute path or `..`.
+    InvalidInstance(String),
+    /// The shared memory file is no ring buffer of the expected records.
+    ShmLayout(String),
+    /// Subscribers of another process can not hold up the publisher, they have to use
+    /// [`crate::Backpressure::DropOldest`].
+    RemoteBlock,
+}
+
+impl Error {
+    /// Whether the peer hung up, as opposed to sending something broken.
+    pub fn is_disconnect(&self) -> bool {
+        matches!(self, Error::Io(e) if matches!(
+            e.kind(),
+            std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::ConnectionReset
+        ))
+    }
+}
+
+// region:    - Froms
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(err: bincode::Error) -> Self {
+        Error::Bincode(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<rmp_serde::encode::Error> for Error {
+    fn from(err: rmp_serde::encode::Error) -> Self {
+        Error::MessagePackEncode(err)
+    }
+}
+
+impl From<rmp_serde::decode::Error> for Error {
+    fn from(err: rmp_serde::decode::Error) -> Self {
+        Error::MessagePackDecode(err)
+    }
+}
+// endregion: - Froms
+
+// region:    - Error impl
+impl core::fmt::Display for Error {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for Error {}
+// endregion:  - Error impl
@@ -2,16 +2,83 @@ use libc::{mkfifo, c_char};
 use std::fs::{File, OpenOptions};
 use std::path::{Path, PathBuf};
 use std::os::unix::ffi::OsStrExt;
-use std::io::{Error, Read, Result, Write};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
 
-use crate::message::Message;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::unix::pipe;
+use tokio::sync::Mutex;
+
+use crate::codec::{CodecKind, Wire, HANDSHAKE_TIMEOUT};
+use crate::error::{Error, Result};
+use crate::frame::DEFAULT_MAX_FRAME_LEN;
+use crate::message::{Command, CommandError, Message, Reply, RequestId};
+use crate::transport::{response, Connection, Listener, MessageReader, MessageWriter, Transport};
+
+// how often to retry opening the write end of a fifo nobody reads yet
+const OPEN_RETRY: Duration = Duration::from_millis(5);
+
+/// Only the owner may read or write, other users have no business with the bot.
+const FIFO_MODE: libc::mode_t = 0o600;
+
+static NEXT_CLIENT: AtomicU32 = AtomicU32::new(0);
+
+/// Names the fifo pair of one connection: the client's pid and a per-process counter, so one process
+/// can hold several connections. Sent as the handshake, 8 little-endian bytes.
+#[derive(Debug, Clone, Copy)]
+struct ClientId {
+    pid: u32,
+    n: u32,
+}
+
+impl ClientId {
+    fn next() -> Self {
+        Self { pid: std::process::id(), n: NEXT_CLIENT.fetch_add(1, Ordering::Relaxed) }
+    }
+
+    fn to_bytes(self) -> [u8; 8] {
+        let mut bytes = [0; 8];
+        bytes[..4].copy_from_slice(&self.pid.to_le_bytes());
+        bytes[4..].copy_from_slice(&self.n.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: [u8; 8]) -> Self {
+        Self {
+            pid: u32::from_le_bytes(bytes[..4].try_into().expect("4 bytes")),
+            n: u32::from_le_bytes(bytes[4..].try_into().expect("4 bytes")),
+        }
+    }
+
+    /// Fifo the server reads from, next to the server's fifo at `server`.
+    fn read_path(&self, server: &Path) -> PathBuf {
+        sibling(server, &format!("read.{}.{}", self.pid, self.n))
+    }
+
+    /// Fifo the server writes to.
+    fn write_path(&self, server: &Path) -> PathBuf {
+        sibling(server, &format!("write.{}.{}", self.pid, self.n))
+    }
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!("-{suffix}"));
+    path.with_file_name(name)
+}
 
 pub struct Fifo {
-    path: PathBuf
+    path: PathBuf,
+    /// Read end kept open between [`Fifo::accept`] calls so no connection request gets lost.
+    listener: Mutex<Option<pipe::Receiver>>,
+    /// Codecs clients may pick, see [`crate::codec`].
+    codecs: Vec<CodecKind>,
 }
 pub struct FifoHandle {
     read: File,
     write: File,
+    wire: Wire,
 }
 impl Fifo {
     pub fn new(path: PathBuf) -> Result<Self> {
@@ -21,31 +88,70 @@ impl Fifo {
         bytes.extend_from_slice(slice);
         bytes.push(0); // zero terminated string
         let _ = std::fs::remove_file(&path);
-        if unsafe { mkfifo((&bytes[0]) as *const u8 as *const c_char, 0o644) } != 0 {
-            Err(Error::last_os_error())
+        if unsafe { mkfifo((&bytes[0]) as *const u8 as *const c_char, FIFO_MODE) } != 0 {
+            Err(std::io::Error::last_os_error().into())
         } else {
-            Ok(Fifo { path })
+            Ok(Fifo { path, listener: Mutex::new(None), codecs: CodecKind::ALL.to_vec() })
         }
     }
-    /// Blocks until anyone connects to this fifo.
+
+    /// Replaces the codecs clients may pick, all of them by default.
+    pub fn with_codecs(mut self, codecs: &[CodecKind]) -> Self {
+        self.codecs = codecs.to_vec();
+        self
+    }
+
+    /// Blocks until anyone connects to this fifo. Fails with [`Error::Handshake`] for clients that
+    /// went away halfway, the fifo stays usable.
     pub fn open(&self) -> Result<FifoHandle> {
         let mut pipe = OpenOptions::new()
             .read(true)
             .open(&self.path)?;
 
-        let mut pid_bytes = [0u8; 4];
-        pipe.read_exact(&mut pid_bytes)?;
-        let pid = u32::from_ne_bytes(pid_bytes);
+        let mut id_bytes = [0u8; 8];
+        pipe.read_exact(&mut id_bytes)?;
+        let id = ClientId::from_bytes(id_bytes);
 
-        let read = OpenOptions::new()
-            .read(true)
-            .open(format!("/tmp/rust-fifo-read.{}", pid))?;
+        let connect = || -> Result<(File, File)> {
+            let read = OpenOptions::new()
+                .read(true)
+                .open(id.read_path(&self.path))?;
 
-        let write = OpenOptions::new()
-            .write(true)
-            .open(format!("/tmp/rust-fifo-write.{}", pid))?;
+            let write = OpenOptions::new()
+                .write(true)
+                .open(id.write_path(&self.path))?;
+            Ok((read, write))
+        };
+        let (mut read, mut write) = connect().map_err(|e| Error::Handshake(Box::new(e)))?;
+
+        let wire = Wire::answer_blocking(&mut read, &mut write, &self.codecs, DEFAULT_MAX_FRAME_LEN)?;
+        Ok(FifoHandle { read, write, wire })
+    }
+
+    /// Waits for the next client like [`Fifo::open`], without blocking the runtime. Clients that do
+    /// not open their end within [`HANDSHAKE_TIMEOUT`] are given up on.
+    pub async fn accept(&self) -> Result<AsyncFifoHandle> {
+        let id = {
+            let mut listener = self.listener.lock().await;
+            if listener.is_none() {
+                // read-write, so the listener never sees EOF between clients
+                *listener = Some(pipe::OpenOptions::new().read_write(true).open_receiver(&self.path)?);
+            }
+            let mut id_bytes = [0u8; 8];
+            listener.as_mut().expect("listener was just opened").read_exact(&mut id_bytes).await?;
+            ClientId::from_bytes(id_bytes)
+        };
 
-        Ok(FifoHandle { read, write })
+        let connect = async {
+            let read = pipe::OpenOptions::new().open_receiver(id.read_path(&self.path))?;
+            let write = tokio::time::timeout(HANDSHAKE_TIMEOUT, open_sender(id.write_path(&self.path)))
+                .await
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+            Ok::<_, Error>((read, write))
+        };
+        let (mut read, mut write) = connect.await.map_err(|e| Error::Handshake(Box::new(e)))?;
+        let wire = Wire::answer(&mut read, &mut write, &self.codecs, DEFAULT_MAX_FRAME_LEN).await?;
+        Ok(AsyncFifoHandle { read, write, wire, _fifos: Vec::new() })
     }
 }
 
@@ -57,48 +163,261 @@ impl Drop for Fifo {
 
 impl FifoHandle {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let pid = std::process::id();
-
-        let read_fifo_path = format!("/tmp/rust-fifo-write.{}", pid);
-        let read_fifo = Fifo::new(read_fifo_path.into())?;
+        Self::open_with_codecs(path, &CodecKind::ALL)
+    }
 
-        let write_fifo_path = format!("/tmp/rust-fifo-read.{}", pid);
-        let write_fifo = Fifo::new(write_fifo_path.into())?;
+    /// Connects offering `codecs`, most preferred first.
+    pub fn open_with_codecs<P: AsRef<Path>>(path: P, codecs: &[CodecKind]) -> Result<Self> {
+        let id = ClientId::next();
+        let read_fifo = Fifo::new(id.write_path(path.as_ref()))?;
+        let write_fifo = Fifo::new(id.read_path(path.as_ref()))?;
 
         let mut pipe = OpenOptions::new()
             .write(true)
             .open(path.as_ref())?;
 
-        let pid_bytes: [u8; 4] = u32::to_ne_bytes(pid);
-        pipe.write_all(&pid_bytes)?;
+        pipe.write_all(&id.to_bytes())?;
         pipe.flush()?;
 
-        let write = OpenOptions::new()
+        let mut write = OpenOptions::new()
             .write(true)
             .open(&write_fifo.path)?;
 
-        let read = OpenOptions::new()
+        let mut read = OpenOptions::new()
             .read(true)
             .open(&read_fifo.path)?;
 
-        Ok(Self { read, write })
+        let wire = Wire::offer_blocking(&mut read, &mut write, codecs, DEFAULT_MAX_FRAME_LEN)?;
+        Ok(Self { read, write, wire })
+    }
+
+    /// Largest message accepted from and sent to the peer, see [`crate::frame`].
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.wire.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// Codec agreed on during the handshake.
+    pub fn codec(&self) -> CodecKind {
+        self.wire.codec
     }
 
     pub fn send_message(&mut self, msg: &Message) -> Result<()> {
-        let msg = bincode::serialize(msg).expect("Serialization failed");
-        self.write.write_all(&usize::to_ne_bytes(msg.len()))?;
-        self.write.write_all(&msg)?;
-        self.write.flush()
+        self.wire.send_blocking(&mut self.write, msg)
     }
 
     pub fn recv_message(&mut self) -> Result<Message> {
-        let mut len_bytes = [0u8; std::mem::size_of::<usize>()];
-        self.read.read_exact(&mut len_bytes)?;
-        let len = usize::from_ne_bytes(len_bytes);
+        self.wire.recv_blocking(&mut self.read)
+    }
+
+    /// Sends `command` and waits for the response carrying the same `id`.
+    pub fn request(&mut self, id: RequestId, command: Command) -> Result<std::result::Result<Reply, CommandError>> {
+        self.send_message(&Message::request(id, command))?;
+        response(id, self.recv_message()?)
+    }
+}
+
+/// Tokio counterpart of [`FifoHandle`], speaking the same protocol so either side may be blocking.
+pub struct AsyncFifoHandle {
+    read: pipe::Receiver,
+    write: pipe::Sender,
+    wire: Wire,
+    /// The client's fifos, removed once the connection is dropped.
+    _fifos: Vec<Fifo>,
+}
+
+impl AsyncFifoHandle {
+    /// Connects to the server listening on `path`, waiting until it accepts.
+    pub async fn connect<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::connect_with_codecs(path, &CodecKind::ALL).await
+    }
+
+    /// Connects offering `codecs`, most preferred first.
+    pub async fn connect_with_codecs<P: AsRef<Path>>(path: P, codecs: &[CodecKind]) -> Result<Self> {
+        let id = ClientId::next();
+        let read_fifo = Fifo::new(id.write_path(path.as_ref()))?;
+        let write_fifo = Fifo::new(id.read_path(path.as_ref()))?;
 
-        let mut buf = vec![0; len];
-        self.read.read_exact(&mut buf)?;
+        // the read end first, the server opens its write end without waiting for us
+        let mut read = pipe::OpenOptions::new().open_receiver(&read_fifo.path)?;
+        let mut pipe = open_sender(path.as_ref()).await?;
+        pipe.write_all(&id.to_bytes()).await?;
+        pipe.flush().await?;
 
-        Ok(bincode::deserialize(&buf).expect("Deserialization failed"))
+        let mut write = open_sender(&write_fifo.path).await?;
+        let wire = Wire::offer(&mut read, &mut write, codecs, DEFAULT_MAX_FRAME_LEN).await?;
+        Ok(Self { read, write, wire, _fifos: vec![read_fifo, write_fifo] })
     }
-}
\ No newline at end of file
+
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.wire.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// Codec agreed on during the handshake.
+    pub fn codec(&self) -> CodecKind {
+        self.wire.codec
+    }
+
+    pub async fn send_message(&mut self, msg: &Message) -> Result<()> {
+        self.wire.send(&mut self.write, msg).await
+    }
+
+    pub async fn recv_message(&mut self) -> Result<Message> {
+        self.wire.recv(&mut self.read).await
+    }
+}
+
+impl Connection for AsyncFifoHandle {
+    type Reader = FifoReader;
+    type Writer = FifoWriter;
+
+    async fn send_message(&mut self, msg: &Message) -> Result<()> {
+        AsyncFifoHandle::send_message(self, msg).await
+    }
+
+    async fn recv_message(&mut self) -> Result<Message> {
+        AsyncFifoHandle::recv_message(self).await
+    }
+
+    fn into_split(self) -> (FifoReader, FifoWriter) {
+        let AsyncFifoHandle { read, write, wire, _fifos } = self;
+        (FifoReader { read, wire, _fifos }, FifoWriter { write, wire })
+    }
+}
+
+pub struct FifoReader {
+    read: pipe::Receiver,
+    wire: Wire,
+    _fifos: Vec<Fifo>,
+}
+
+impl MessageReader for FifoReader {
+    async fn recv_message(&mut self) -> Result<Message> {
+        self.wire.recv(&mut self.read).await
+    }
+}
+
+pub struct FifoWriter {
+    write: pipe::Sender,
+    wire: Wire,
+}
+
+impl MessageWriter for FifoWriter {
+    async fn send_message(&mut self, msg: &Message) -> Result<()> {
+        self.wire.send(&mut self.write, msg).await
+    }
+}
+
+impl Listener for Fifo {
+    type Connection = AsyncFifoHandle;
+
+    async fn accept(&self) -> Result<AsyncFifoHandle> {
+        Fifo::accept(self).await
+    }
+}
+
+/// The fifo pair handshake of [`Fifo`] and [`AsyncFifoHandle`].
+#[derive(Debug, Clone)]
+pub struct FifoTransport {
+    codecs: Vec<CodecKind>,
+}
+
+impl Default for FifoTransport {
+    fn default() -> Self {
+        Self { codecs: CodecKind::ALL.to_vec() }
+    }
+}
+
+impl FifoTransport {
+    /// Codecs servers accept and clients offer, most preferred first.
+    pub fn with_codecs(mut self, codecs: &[CodecKind]) -> Self {
+        self.codecs = codecs.to_vec();
+        self
+    }
+}
+
+impl Transport for FifoTransport {
+    type Connection = AsyncFifoHandle;
+    type Listener = Fifo;
+
+    fn bind(&self, path: &Path) -> Result<Fifo> {
+        Ok(Fifo::new(path.to_path_buf())?.with_codecs(&self.codecs))
+    }
+
+    async fn connect(&self, path: &Path) -> Result<AsyncFifoHandle> {
+        AsyncFifoHandle::connect_with_codecs(path, &self.codecs).await
+    }
+}
+
+/// Opens the write end of the fifo at `path`, waiting until the other side opened the read end.
+/// Opening a fifo without reader fails with `ENXIO` in non-blocking mode.
+async fn open_sender<P: AsRef<Path>>(path: P) -> Result<pipe::Sender> {
+    loop {
+        match pipe::OpenOptions::new().open_sender(path.as_ref()) {
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) => tokio::time::sleep(OPEN_RETRY).await,
+            other => return Ok(other?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::LogLevel;
+
+    #[tokio::test]
+    async fn async_and_blocking_clients() {
+        let path = std::env::temp_dir().join(format!("iam-fifo-test.{}", std::process::id()));
+        let fifo = Fifo::new(path.clone()).unwrap();
+
+        let client = tokio::spawn(AsyncFifoHandle::connect(path.clone()));
+        let mut server = fifo.accept().await.unwrap();
+        let mut client = client.await.unwrap().unwrap();
+        client.send_message(&Message::Print("hello".to_string())).await.unwrap();
+        assert!(matches!(server.recv_message().await.unwrap(), Message::Print(p) if p == "hello"));
+        server.send_message(&Message::Ack).await.unwrap();
+        assert!(matches!(client.recv_message().await.unwrap(), Message::Ack));
+
+        let request = tokio::spawn(async move {
+            let reply = client.request(5, Command::SetLogLevel(LogLevel::Debug)).await;
+            (client, reply)
+        });
+        assert_eq!(server.recv_message().await.unwrap(), Message::request(5, Command::SetLogLevel(LogLevel::Debug)));
+        server.send_message(&Message::response(5, Ok(Reply::Done))).await.unwrap();
+        let (client, reply) = request.await.unwrap();
+        assert_eq!(reply.unwrap(), Ok(Reply::Done));
+        drop(client);
+        assert!(server.recv_message().await.unwrap_err().is_disconnect());
+
+        // the blocking handle speaks the same protocol
+        let client = tokio::task::spawn_blocking(move || {
+            let mut handle = FifoHandle::open_with_codecs(path, &[CodecKind::MessagePack, CodecKind::Json])?;
+            assert_eq!(handle.codec(), CodecKind::MessagePack);
+            handle.send_message(&Message::Print("blocking".to_string()))?;
+            handle.recv_message()
+        });
+        let mut server = fifo.accept().await.unwrap();
+        assert_eq!(server.codec(), CodecKind::MessagePack);
+        assert!(matches!(server.recv_message().await.unwrap(), Message::Print(p) if p == "blocking"));
+        server.send_message(&Message::Ack).await.unwrap();
+        assert!(matches!(client.await.unwrap().unwrap(), Message::Ack));
+    }
+
+    #[tokio::test]
+    async fn survives_vanished_clients() {
+        let path = std::env::temp_dir().join(format!("iam-fifo-vanished.{}", std::process::id()));
+        let fifo = Fifo::new(path.clone()).unwrap();
+
+        // a client that announced itself and was gone before its fifos were opened
+        let vanished = async {
+            let mut pipe = open_sender(&path).await.unwrap();
+            pipe.write_all(&ClientId { pid: u32::MAX, n: 0 }.to_bytes()).await.unwrap();
+        };
+        let ((), server) = tokio::join!(vanished, fifo.accept());
+        assert!(matches!(server, Err(Error::Handshake(_))));
+
+        let (client, server) = tokio::join!(AsyncFifoHandle::connect(&path), fifo.accept());
+        assert_eq!(client.unwrap().codec(), server.unwrap().codec());
+    }
+}
@@ -0,0 +1,130 @@
+//! Wire format of every message:
+//!
+//! | bytes | content                                   |
+//! |-------|-------------------------------------------|
+//! | 4     | [`MAGIC`]                                 |
+//! | 1     | [`VERSION`]                               |
+//! | 4     | payload length, little-endian `u32`       |
+//! | 4     | CRC-32 of the payload, little-endian `u32` |
+//! | len   | payload                                   |
+//!
+//! The header is checked before anything is allocated for the payload.
+
+use std::io::{Read, Write};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::error::{Error, Result};
+
+pub const MAGIC: [u8; 4] = *b"IAMQ";
+pub const VERSION: u8 = 1;
+pub const HEADER_LEN: usize = 13;
+/// Largest payload accepted unless configured otherwise, 16 MiB.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 16 << 20;
+
+/// Prefixes `payload` with its header.
+pub fn encode(payload: &[u8], max_frame_len: usize) -> Result<Vec<u8>> {
+    let len = payload.len();
+    if len > max_frame_len || len > u32::MAX as usize {
+        return Err(Error::FrameTooLarge { len, max: max_frame_len });
+    }
+    let mut frame = Vec::with_capacity(HEADER_LEN + len);
+    frame.extend_from_slice(&MAGIC);
+    frame.push(VERSION);
+    frame.extend_from_slice(&(len as u32).to_le_bytes());
+    frame.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+/// Validates a header, returning the payload length and checksum.
+pub fn decode_header(header: &[u8; HEADER_LEN], max_frame_len: usize) -> Result<(usize, u32)> {
+    let magic: [u8; 4] = header[..4].try_into().expect("4 bytes");
+    if magic != MAGIC {
+        return Err(Error::BadMagic(magic));
+    }
+    if header[4] != VERSION {
+        return Err(Error::UnsupportedVersion(header[4]));
+    }
+    let len = u32::from_le_bytes(header[5..9].try_into().expect("4 bytes")) as usize;
+    if len > max_frame_len {
+        return Err(Error::FrameTooLarge { len, max: max_frame_len });
+    }
+    Ok((len, u32::from_le_bytes(header[9..].try_into().expect("4 bytes"))))
+}
+
+fn check(payload: &[u8], expected: u32) -> Result<()> {
+    let actual = crc32fast::hash(payload);
+    if actual != expected {
+        return Err(Error::ChecksumMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, payload: &[u8], max_frame_len: usize) -> Result<()> {
+    writer.write_all(&encode(payload, max_frame_len)?)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_frame<R: Read>(reader: &mut R, max_frame_len: usize) -> Result<Vec<u8>> {
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    let (len, checksum) = decode_header(&header, max_frame_len)?;
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload)?;
+    check(&payload, checksum)?;
+    Ok(payload)
+}
+
+pub async fn write_frame_async<W: AsyncWrite + Unpin>(writer: &mut W, payload: &[u8], max_frame_len: usize) -> Result<()> {
+    writer.write_all(&encode(payload, max_frame_len)?).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+pub async fn read_frame_async<R: AsyncRead + Unpin>(reader: &mut R, max_frame_len: usize) -> Result<Vec<u8>> {
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header).await?;
+    let (len, checksum) = decode_header(&header, max_frame_len)?;
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload).await?;
+    check(&payload, checksum)?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(bytes: &[u8], max: usize) -> Result<Vec<u8>> {
+        read_frame(&mut &bytes[..], max)
+    }
+
+    #[test]
+    fn roundtrip_and_rejections() {
+        let frame = encode(b"hello", 1024).unwrap();
+        assert_eq!(frame.len(), HEADER_LEN + 5);
+        assert_eq!(read(&frame, 1024).unwrap(), b"hello");
+        assert!(matches!(encode(&[0; 11], 10), Err(Error::FrameTooLarge { len: 11, max: 10 })));
+
+        let mut bad = frame.clone();
+        bad[0] = b'X';
+        assert!(matches!(read(&bad, 1024), Err(Error::BadMagic(m)) if m == *b"XAMQ"));
+
+        let mut bad = frame.clone();
+        bad[4] = 9;
+        assert!(matches!(read(&bad, 1024), Err(Error::UnsupportedVersion(9))));
+
+        let mut bad = frame.clone();
+        *bad.last_mut().unwrap() ^= 1;
+        assert!(matches!(read(&bad, 1024), Err(Error::ChecksumMismatch { .. })));
+
+        // a hostile length is refused from the header alone
+        let mut bad = frame.clone();
+        bad[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(read(&bad, 1024), Err(Error::FrameTooLarge { len, .. }) if len == u32::MAX as usize));
+
+        assert!(read(&frame[..HEADER_LEN + 2], 1024).unwrap_err().is_disconnect());
+    }
+}
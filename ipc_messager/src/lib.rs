@@ -1,32 +1,79 @@
-use std::path::PathBuf;
-use std::io::Result;
-
-use fifo::{Fifo, FifoHandle};
-use message::Message;
+use error::{Error, Result};
+use fifo::{AsyncFifoHandle, Fifo, FifoHandle};
+use message::{CommandError, Message};
+use runtime::RuntimeDir;
+use server::{Handler, IpcServer};
+use tracing::warn;
 
+pub mod bus;
+pub mod codec;
+pub mod error;
 pub mod fifo;
+pub mod frame;
 pub mod message;
+pub mod runtime;
+pub mod server;
+pub mod session;
+#[cfg(feature = "shm")]
+pub mod shm;
+pub mod transport;
+pub mod unix;
 
-pub fn listen() -> Result<()> {
-    let fifo = Fifo::new(PathBuf::from("/tmp/rust-fifo"))?;
+/// Answers the fifo clients of `dir` on one thread each, printing what they send.
+pub fn listen(dir: &RuntimeDir) -> Result<()> {
+    let fifo = Fifo::new(dir.create()?.fifo_path())?;
     loop {
-        let mut handle = fifo.open()?;
+        let mut handle = match fifo.open() {
+            Ok(handle) => handle,
+            Err(Error::Handshake(e)) => {
+                warn!("Fifo client failed the handshake: {e}");
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
         std::thread::spawn(move || {
-            match handle.recv_message().expect("Failed to receive message") {
-                Message::Print(p) => println!("{}", p),
-                Message::Ack => panic!("Didn't expect Ack now."),
+            let reply = match handle.recv_message() {
+                Ok(Message::Print(p)) => {
+                    println!("{}", p);
+                    Message::Ack
+                }
+                Ok(Message::Request { id, .. }) => Message::response(id, Err(CommandError::Unsupported)),
+                Ok(other) => {
+                    warn!("Didn't expect {other:?} now.");
+                    return;
+                }
+                Err(e) => {
+                    warn!("Failed to receive message: {e}");
+                    return;
+                }
+            };
+            if let Err(e) = handle.send_message(&reply) {
+                warn!("Send message failed: {e}");
             }
-            handle.send_message(&Message::Ack).expect("Send message failed.");
         });
     }
 }
 
-pub fn send(s: String) -> Result<()> {
-    let mut handle = FifoHandle::open("/tmp/rust-fifo")?;
+/// Serves the fifo of `dir` with `handler` on the current tokio runtime, see [`IpcServer`].
+pub async fn listen_async<H: Handler>(dir: &RuntimeDir, handler: H) -> Result<()> {
+    let fifo = Fifo::new(dir.create()?.fifo_path())?;
+    IpcServer::new(fifo, handler).run().await
+}
+
+pub async fn send_async(dir: &RuntimeDir, s: String) -> Result<()> {
+    let mut handle = AsyncFifoHandle::connect(dir.fifo_path()).await?;
+    handle.send_message(&Message::Print(s)).await?;
+    if let Message::Print(p) = handle.recv_message().await? {
+        println!("{}", p);
+    }
+    Ok(())
+}
+
+pub fn send(dir: &RuntimeDir, s: String) -> Result<()> {
+    let mut handle = FifoHandle::open(dir.fifo_path())?;
     handle.send_message(&Message::Print(s))?;
-    match handle.recv_message()? {
-        Message::Print(p) => println!("{}", p),
-        Message::Ack => {},
+    if let Message::Print(p) = handle.recv_message()? {
+        println!("{}", p);
     }
     Ok(())
 }
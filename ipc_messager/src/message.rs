@@ -1,7 +1,335 @@
+use std::path::PathBuf;
+
+use data_downloader::Kline;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Correlates a [`Message::Response`] with the [`Message::Request`] it answers.
+pub type RequestId = u64;
+pub type JobId = u64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Message {
     Print(String),
-    Ack
+    Ack,
+    Request { id: RequestId, command: Command },
+    Response { id: RequestId, result: Result<Reply, CommandError> },
+    /// Pushed by the server to the clients that subscribed to its [`EventKind`].
+    Event(Event),
+    /// Turns the connection into a [`crate::bus`] subscription of the matching topics.
+    BusSubscribe { topics: Vec<String>, policy: Backpressure },
+    /// A kline of a bus topic, `dropped` counts what this subscriber missed before it.
+    Published { topic: String, kline: KlineRecord, dropped: u64 },
+}
+
+impl Message {
+    pub fn request(id: RequestId, command: Command) -> Self {
+        Message::Request { id, command }
+    }
+
+    pub fn response(id: RequestId, result: Result<Reply, CommandError>) -> Self {
+        Message::Response { id, result }
+    }
+}
+
+/// What a client can ask the manager to do.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Command {
+    StartDownload(DownloadJob),
+    StopDownload { job: JobId },
+    /// Status of one job, or of every job without an id.
+    JobStatus { job: Option<JobId> },
+    ListDatasets,
+    StartStrategy(StrategySpec),
+    StopStrategy { name: String },
+    /// Positions of a running strategy, or of the whole account without a name.
+    Positions { strategy: Option<String> },
+    Pnl { strategy: Option<String> },
+    SetLogLevel(LogLevel),
+    Shutdown,
+    /// Pushes the given kinds of [`Event`] over the same connection from now on.
+    Subscribe(Vec<EventKind>),
+    Unsubscribe(Vec<EventKind>),
+}
+
+/// Successful answer to a [`Command`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Reply {
+    /// The command was carried out and has nothing to report.
+    Done,
+    JobStarted { job: JobId },
+    Jobs(Vec<JobStatus>),
+    Datasets(Vec<DatasetInfo>),
+    Positions(Vec<PositionInfo>),
+    Pnl(PnlReport),
+}
+
+/// Why a [`Command`] was not carried out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum CommandError {
+    UnknownJob(JobId),
+    UnknownStrategy(String),
+    AlreadyRunning(String),
+    InvalidArgument(String),
+    /// The command is valid but nothing registered handles it.
+    Unsupported,
+    /// The command took too long, it may still complete.
+    Timeout,
+    /// The connection has too many requests in flight, it may try again once some are answered.
+    Busy,
+    Internal(String),
+}
+
+/// Klines of `symbol` at `interval` between two open times in ms, open ended without them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DownloadJob {
+    pub symbol: String,
+    pub interval: String,
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JobStatus {
+    pub id: JobId,
+    pub job: DownloadJob,
+    pub state: JobState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum JobState {
+    Queued,
+    /// Fraction of the requested range downloaded so far.
+    Running { progress: f64 },
+    Finished,
+    Stopped,
+    Failed(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DatasetInfo {
+    pub path: PathBuf,
+    pub symbol: String,
+    pub interval: String,
+    pub rows: u64,
+    /// Open times in ms of the first and last kline.
+    pub first: Option<u64>,
+    pub last: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StrategySpec {
+    /// Unique name the strategy is addressed by later on.
+    pub name: String,
+    /// Which strategy implementation to run, e.g. `rsi_threshold`.
+    pub kind: String,
+    pub symbol: String,
+    pub interval: String,
+    pub params: Vec<(String, f64)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PositionInfo {
+    pub asset: String,
+    pub quantity: f64,
+    pub avg_entry: f64,
+    pub last_price: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+}
+
+/// PnL in the quote asset.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PnlReport {
+    pub quote: String,
+    pub realized: f64,
+    pub unrealized: f64,
+    pub fees: f64,
+    pub equity: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventKind {
+    Candles,
+    Fills,
+    Jobs,
+    Logs,
+}
+
+/// What happens in the bot, for clients watching it live.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Event {
+    Candle(CandleEvent),
+    Fill(FillEvent),
+    JobProgress(JobStatus),
+    Log(LogLine),
+    /// This many events were dropped because the client did not keep up, sent whatever it subscribed to.
+    Missed(u64),
+}
+
+impl Event {
+    /// `None` for events every subscriber gets.
+    pub fn kind(&self) -> Option<EventKind> {
+        match self {
+            Event::Candle(_) => Some(EventKind::Candles),
+            Event::Fill(_) => Some(EventKind::Fills),
+            Event::JobProgress(_) => Some(EventKind::Jobs),
+            Event::Log(_) => Some(EventKind::Logs),
+            Event::Missed(_) => None,
+        }
+    }
+}
+
+/// A closed candle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CandleEvent {
+    pub symbol: String,
+    pub interval: String,
+    pub open_time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FillEvent {
+    pub symbol: String,
+    pub order_id: u64,
+    /// `BUY` or `SELL`.
+    pub side: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub time: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LogLine {
+    pub level: LogLevel,
+    pub target: String,
+    pub message: String,
+}
+
+/// What a bus subscriber that falls behind wants, both keep up to `capacity` klines queued.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Drops the oldest queued kline to make room, the publisher never waits.
+    DropOldest { capacity: usize },
+    /// Makes the publisher wait until the subscriber has caught up, only for subscribers of the same process.
+    Block { capacity: usize },
+}
+
+/// [`Kline`] as it travels over IPC, the kline itself only reads Binance's array format. Fixed layout,
+/// so it also fits the shared memory ring of [`crate::shm`].
+#[repr(C)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct KlineRecord {
+    pub open_time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub close_time: u64,
+    pub quote_asset_volume: f64,
+    pub trade_number: u64,
+    pub buy_base: f64,
+    pub buy_quote: f64,
+}
+
+impl From<&Kline> for KlineRecord {
+    fn from(kline: &Kline) -> Self {
+        Self {
+            open_time: kline.open_time,
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+            close_time: kline.close_time,
+            quote_asset_volume: kline.quote_asset_volume,
+            trade_number: kline.trade_number as u64,
+            buy_base: kline.buy_base,
+            buy_quote: kline.buy_quote,
+        }
+    }
+}
+
+impl From<KlineRecord> for Kline {
+    fn from(record: KlineRecord) -> Self {
+        Self {
+            open_time: record.open_time,
+            open: record.open,
+            high: record.high,
+            low: record.low,
+            close: record.close,
+            volume: record.volume,
+            close_time: record.close_time,
+            quote_asset_volume: record.quote_asset_volume,
+            trade_number: record.trade_number as usize,
+            buy_base: record.buy_base,
+            buy_quote: record.buy_quote,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    /// Name understood by `tracing_subscriber::EnvFilter` and `RUST_LOG`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let messages = [
+            Message::request(1, Command::StartDownload(DownloadJob {
+                symbol: "BTCUSDT".to_string(),
+                interval: "1m".to_string(),
+                start: Some(1_704_067_200_000),
+                end: None,
+            })),
+            Message::request(2, Command::SetLogLevel(LogLevel::Debug)),
+            Message::response(1, Ok(Reply::JobStarted { job: 7 })),
+            Message::response(3, Ok(Reply::Pnl(PnlReport {
+                quote: "USDT".to_string(),
+                realized: 12.5,
+                unrealized: -3.0,
+                fees: 0.4,
+                equity: 10_009.1,
+            }))),
+            Message::response(4, Err(CommandError::UnknownStrategy("rsi".to_string()))),
+            Message::request(5, Command::Subscribe(vec![EventKind::Candles, EventKind::Logs])),
+            Message::Event(Event::Log(LogLine {
+                level: LogLevel::Warn,
+                target: "trader".to_string(),
+                message: "Listen key expired".to_string(),
+            })),
+        ];
+        for message in messages {
+            let bytes = bincode::serialize(&message).unwrap();
+            assert_eq!(bincode::deserialize::<Message>(&bytes).unwrap(), message);
+            let json = serde_json::to_string(&message).unwrap();
+            assert_eq!(serde_json::from_str::<Message>(&json).unwrap(), message);
+        }
+    }
 }
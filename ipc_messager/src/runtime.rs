@@ -0,0 +1,155 @@
+use std::fs::{DirBuilder, Permissions};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+pub const DEFAULT_INSTANCE: &str = "default";
+
+/// Private directory holding the fifos and sockets of one bot instance, so a paper and a live bot
+/// can run side by side.
+///
+/// Defaults to `$XDG_RUNTIME_DIR/iam/<instance>`, or `/tmp/iam-<uid>/<instance>` where there is no
+/// runtime dir. Every directory below the runtime dir or `/tmp` has to belong to the current user
+/// and is only accessible by them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeDir {
+    /// Trusted directory the checks start below.
+    base: PathBuf,
+    path: PathBuf,
+}
+
+impl Default for RuntimeDir {
+    fn default() -> Self {
+        Self::for_instance(DEFAULT_INSTANCE).expect("the default instance is a valid name")
+    }
+}
+
+impl RuntimeDir {
+    /// `instance` has to be a plain directory name, no path leading out of the runtime dir.
+    pub fn for_instance(instance: &str) -> Result<Self> {
+        let mut components = Path::new(instance).components();
+        if instance.contains('/') || !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+            return Err(Error::InvalidInstance(instance.to_string()));
+        }
+        Ok(match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) if !dir.is_empty() => Self::below(dir.into(), Path::new("iam").join(instance)),
+            _ => Self::below(std::env::temp_dir(), Path::new(&format!("iam-{}", unsafe { libc::geteuid() })).join(instance)),
+        })
+    }
+
+    /// Uses `path` as is, only the directory itself is checked.
+    pub fn at<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Self { base, path }
+    }
+
+    /// `relative` inside `base`, e.g. `iam-1000/paper` inside the world-writable `/tmp`.
+    fn below(base: PathBuf, relative: PathBuf) -> Self {
+        Self { path: base.join(relative), base }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Creates the directory and its parents with mode `0700`. Every directory below the base, e.g.
+    /// `/tmp/iam-<uid>` and the instance inside it, has to be a real directory of the current user
+    /// before anything is created in it, their permissions are tightened.
+    pub fn create(&self) -> Result<&Self> {
+        DirBuilder::new().recursive(true).mode(0o700).create(&self.base)?;
+        let mut dir = self.base.clone();
+        for component in self.path.strip_prefix(&self.base).expect("base is a parent of path").components() {
+            dir.push(component);
+            match DirBuilder::new().mode(0o700).create(&dir) {
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                other => other?,
+            }
+            // not following symlinks, they could point anywhere
+            let metadata = std::fs::symlink_metadata(&dir)?;
+            if !metadata.is_dir() || metadata.uid() != unsafe { libc::geteuid() } {
+                return Err(Error::InsecureRuntimeDir(dir));
+            }
+            if metadata.mode() & 0o077 != 0 {
+                std::fs::set_permissions(&dir, Permissions::from_mode(0o700))?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Server fifo of [`crate::fifo::FifoTransport`], the clients' fifos are created next to it.
+    pub fn fifo_path(&self) -> PathBuf {
+        self.path.join("control.fifo")
+    }
+
+    /// Socket of [`crate::unix::UnixTransport`].
+    pub fn socket_path(&self) -> PathBuf {
+        self.path.join("control.sock")
+    }
+
+    /// Socket the [`crate::bus::Bus`] of market data is served on.
+    pub fn bus_path(&self) -> PathBuf {
+        self.path.join("bus.sock")
+    }
+
+    /// Shared memory ring named `name`, on tmpfs like the rest of `$XDG_RUNTIME_DIR`.
+    pub fn shm_path(&self, name: &str) -> PathBuf {
+        self.path.join(format!("{name}.shm"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fifo::{AsyncFifoHandle, Fifo};
+
+    #[tokio::test]
+    async fn private_dir_and_fifos() {
+        let root = std::env::temp_dir().join(format!("iam-runtime-test.{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let dir = RuntimeDir::at(root.join("paper"));
+        dir.create().unwrap();
+        std::fs::set_permissions(dir.path(), Permissions::from_mode(0o755)).unwrap();
+        dir.create().unwrap();
+        assert_eq!(std::fs::metadata(dir.path()).unwrap().mode() & 0o777, 0o700);
+
+        let fifo = Fifo::new(dir.fifo_path()).unwrap();
+        assert_eq!(std::fs::metadata(dir.fifo_path()).unwrap().mode() & 0o077, 0);
+        let (client, server) = tokio::join!(AsyncFifoHandle::connect(dir.fifo_path()), fifo.accept());
+        let (_client, _server) = (client.unwrap(), server.unwrap());
+        // the client's pair lives in the same private directory
+        let names: Vec<_> = std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names.len(), 3, "{names:?}");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn instances_are_plain_names() {
+        assert!(RuntimeDir::for_instance("paper").unwrap().path().ends_with("paper"));
+        for instance in ["", ".", "..", "/etc", "a/b", "../live", "paper/"] {
+            assert!(matches!(RuntimeDir::for_instance(instance), Err(Error::InvalidInstance(_))), "{instance:?}");
+        }
+    }
+
+    #[test]
+    fn checks_every_dir_below_the_base() {
+        let root = std::env::temp_dir().join(format!("iam-runtime-base.{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let dir = RuntimeDir::below(root.clone(), PathBuf::from("iam-test/paper"));
+        dir.create().unwrap();
+        std::fs::set_permissions(root.join("iam-test"), Permissions::from_mode(0o777)).unwrap();
+        dir.create().unwrap();
+        assert_eq!(std::fs::metadata(root.join("iam-test")).unwrap().mode() & 0o777, 0o700);
+
+        // someone else's directory swapped in halfway
+        std::fs::remove_dir_all(root.join("iam-test")).unwrap();
+        std::fs::create_dir(root.join("elsewhere")).unwrap();
+        std::os::unix::fs::symlink(root.join("elsewhere"), root.join("iam-test")).unwrap();
+        assert!(matches!(dir.create(), Err(Error::InsecureRuntimeDir(p)) if p == root.join("iam-test")));
+        assert!(!root.join("elsewhere/paper").exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
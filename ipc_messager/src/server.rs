@@ -0,0 +1,377 @@
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio::task::{Id, JoinError, JoinHandle, JoinSet};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result};
+use crate::message::{Command, CommandError, Event, EventKind, Message, Reply, RequestId};
+use crate::transport::{Connection, Listener, MessageReader, MessageWriter};
+
+/// Carries out the [`Command`]s of the clients of an [`IpcServer`]. Implemented for closures
+/// `Fn(Command) -> impl Future<Output = Result<Reply, CommandError>>`.
+pub trait Handler: Send + Sync + 'static {
+    fn handle(&self, command: Command) -> impl Future<Output = std::result::Result<Reply, CommandError>> + Send;
+}
+
+impl<F, Fut> Handler for F
+where
+    F: Fn(Command) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = std::result::Result<Reply, CommandError>> + Send,
+{
+    fn handle(&self, command: Command) -> impl Future<Output = std::result::Result<Reply, CommandError>> + Send {
+        self(command)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServerConfig {
+    /// Connections served at once, further clients wait to be accepted.
+    pub max_connections: usize,
+    /// A connection without a message for this long is closed.
+    pub idle_timeout: Duration,
+    /// Longest a handler may take for one command before the client gets [`CommandError::Timeout`].
+    pub request_timeout: Duration,
+    /// Requests one connection may have in flight, further ones are answered with [`CommandError::Busy`].
+    pub max_requests: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 16,
+            idle_timeout: Duration::from_secs(5 * 60),
+            request_timeout: Duration::from_secs(30),
+            max_requests: 32,
+        }
+    }
+}
+
+/// Accepts clients on a [`Listener`] and answers their requests with a [`Handler`], until cancelled.
+/// Connections stay open for any number of requests and can subscribe to pushed [`Event`]s. The
+/// requests of one connection are handled concurrently, events keep flowing meanwhile.
+pub struct IpcServer<L, H> {
+    listener: L,
+    handler: Arc<H>,
+    config: ServerConfig,
+    events: Option<Events>,
+    cancel: CancellationToken,
+}
+
+/// Where the events come from and which kinds are ever sent there.
+#[derive(Clone)]
+struct Events {
+    sender: broadcast::Sender<Event>,
+    kinds: BTreeSet<EventKind>,
+}
+
+impl<L: Listener + 'static, H: Handler> IpcServer<L, H> {
+    pub fn new(listener: L, handler: H) -> Self {
+        Self {
+            listener,
+            handler: Arc::new(handler),
+            config: ServerConfig::default(),
+            events: None,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    pub fn with_config(mut self, config: ServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Lets clients [`Command::Subscribe`] to what is sent on `events`. Subscribing to kinds not in
+    /// `kinds` fails with [`CommandError::InvalidArgument`], nothing would ever arrive.
+    pub fn with_events(mut self, events: broadcast::Sender<Event>, kinds: &[EventKind]) -> Self {
+        self.events = Some(Events { sender: events, kinds: kinds.iter().copied().collect() });
+        self
+    }
+
+    /// Stops the server when `cancel` is cancelled, e.g. by a handler serving [`Command::Shutdown`].
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Serves clients until cancelled, then waits for the open connections to close. Only fails when
+    /// the listener does, rejected peers and broken connections are logged.
+    pub async fn run(self) -> Result<()> {
+        let permits = Arc::new(Semaphore::new(self.config.max_connections));
+        let mut connections = JoinSet::new();
+        let result = loop {
+            let permit = tokio::select! {
+                _ = self.cancel.cancelled() => break Ok(()),
+                permit = permits.clone().acquire_owned() => permit.expect("the semaphore is never closed"),
+            };
+            let connection = tokio::select! {
+                _ = self.cancel.cancelled() => break Ok(()),
+                connection = self.listener.accept() => connection,
+            };
+            match connection {
+                Ok(connection) => {
+                    let (handler, config, events, cancel) = (self.handler.clone(), self.config, self.events.clone(), self.cancel.clone());
+                    connections.spawn(async move {
+                        if let Err(e) = serve(connection, handler, config, events, cancel).await {
+                            warn!("IPC connection failed: {e}");
+                        }
+                        drop(permit);
+                    });
+                }
+                Err(Error::PeerRejected { uid }) => warn!("Rejected IPC client of user {uid}"),
+                Err(Error::Handshake(e)) => warn!("IPC client failed the handshake: {e}"),
+                Err(e) => break Err(e),
+            }
+            // reap finished connections
+            while connections.try_join_next().is_some() {}
+        };
+        self.cancel.cancel();
+        while connections.join_next().await.is_some() {}
+        result
+    }
+}
+
+async fn serve<C: Connection, H: Handler>(
+    connection: C,
+    handler: Arc<H>,
+    config: ServerConfig,
+    events: Option<Events>,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let (mut reader, mut writer) = connection.into_split();
+    // reading in its own task, a frame half read when select! picks another branch would be lost
+    let (incoming_tx, mut incoming) = mpsc::channel(16);
+    let reading = AbortOnDrop(tokio::spawn(async move {
+        loop {
+            let message = reader.recv_message().await;
+            let failed = message.is_err();
+            if incoming_tx.send(message).await.is_err() || failed {
+                break;
+            }
+        }
+    }));
+
+    let mut subscriptions = BTreeSet::new();
+    let mut subscription: Option<broadcast::Receiver<Event>> = None;
+    // handlers run in tasks of their own, a slow one holds up neither events nor other requests
+    let mut running = JoinSet::new();
+    let mut requests = HashMap::new();
+    let idle = tokio::time::sleep(config.idle_timeout);
+    tokio::pin!(idle);
+    let result = loop {
+        let message = tokio::select! {
+            _ = cancel.cancelled() => {
+                // answers what is already being handled, e.g. the request that shut the server down
+                while let Some(done) = running.join_next_with_id().await {
+                    if writer.send_message(&answered(done, &mut requests)).await.is_err() {
+                        break;
+                    }
+                }
+                break Ok(());
+            }
+            // watching clients may stay silent for as long as they like
+            _ = &mut idle, if subscriptions.is_empty() && running.is_empty() => {
+                debug!("Closing idle IPC connection");
+                break Ok(());
+            }
+            Some(done) = running.join_next_with_id() => {
+                idle.as_mut().reset(tokio::time::Instant::now() + config.idle_timeout);
+                if let Err(e) = writer.send_message(&answered(done, &mut requests)).await {
+                    break Err(e);
+                }
+                continue;
+            }
+            event = next_event(&mut subscription), if subscription.is_some() => {
+                let event = match event {
+                    Ok(event) if event.kind().is_none_or(|kind| subscriptions.contains(&kind)) => event,
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => Event::Missed(missed),
+                    Err(broadcast::error::RecvError::Closed) => {
+                        subscription = None;
+                        continue;
+                    }
+                };
+                if let Err(e) = writer.send_message(&Message::Event(event)).await {
+                    break Err(e);
+                }
+                continue;
+            }
+            message = incoming.recv() => match message {
+                Some(Ok(message)) => message,
+                Some(Err(e)) if !e.is_disconnect() => break Err(e),
+                _ => break Ok(()),
+            },
+        };
+        idle.as_mut().reset(tokio::time::Instant::now() + config.idle_timeout);
+
+        let reply = match message {
+            Message::Request { id, command: Command::Subscribe(kinds) } => Message::response(id, match &events {
+                Some(events) => match kinds.iter().find(|kind| !events.kinds.contains(kind)) {
+                    Some(kind) => Err(CommandError::InvalidArgument(format!("no {kind:?} events are published"))),
+                    None => {
+                        subscription.get_or_insert_with(|| events.sender.subscribe());
+                        subscriptions.extend(kinds);
+                        Ok(Reply::Done)
+                    }
+                },
+                None => Err(CommandError::Unsupported),
+            }),
+            Message::Request { id, command: Command::Unsubscribe(kinds) } => {
+                for kind in &kinds {
+                    subscriptions.remove(kind);
+                }
+                if subscriptions.is_empty() {
+                    subscription = None;
+                }
+                Message::response(id, Ok(Reply::Done))
+            }
+            Message::Request { id, .. } if running.len() >= config.max_requests => Message::response(id, Err(CommandError::Busy)),
+            Message::Request { id, command } => {
+                let handler = handler.clone();
+                let task = running.spawn(async move {
+                    let result = tokio::time::timeout(config.request_timeout, handler.handle(command))
+                        .await
+                        .unwrap_or(Err(CommandError::Timeout));
+                    Message::response(id, result)
+                });
+                requests.insert(task.id(), id);
+                continue;
+            }
+            Message::Print(p) => {
+                debug!("IPC client says: {p}");
+                Message::Ack
+            }
+            other => {
+                warn!("Ignoring unexpected IPC message {other:?}");
+                continue;
+            }
+        };
+        if let Err(e) = writer.send_message(&reply).await {
+            break Err(e);
+        }
+    };
+    drop(reading);
+    result
+}
+
+/// Response of a finished handler task, an error response if it panicked.
+fn answered(done: std::result::Result<(Id, Message), JoinError>, requests: &mut HashMap<Id, RequestId>) -> Message {
+    match done {
+        Ok((task, response)) => {
+            requests.remove(&task);
+            response
+        }
+        Err(e) => {
+            let id = requests.remove(&e.id()).unwrap_or_default();
+            warn!("IPC handler of request {id} failed: {e}");
+            Message::response(id, Err(CommandError::Internal("the handler panicked".to_string())))
+        }
+    }
+}
+
+async fn next_event(subscription: &mut Option<broadcast::Receiver<Event>>) -> std::result::Result<Event, broadcast::error::RecvError> {
+    match subscription {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Stops a task once its handle goes away.
+pub(crate) struct AbortOnDrop<T>(pub(crate) JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::LogLevel;
+    use crate::transport::Transport;
+    use crate::unix::UnixTransport;
+
+    #[tokio::test]
+    async fn handles_until_cancelled() {
+        let path = std::env::temp_dir().join(format!("iam-server-test.{}", std::process::id()));
+        let transport = UnixTransport::default();
+        let cancel = CancellationToken::new();
+        let shutdown = cancel.clone();
+        let handler = move |command| {
+            let shutdown = shutdown.clone();
+            async move {
+                match command {
+                    Command::SetLogLevel(_) => Ok(Reply::Done),
+                    Command::ListDatasets => {
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                        Ok(Reply::Datasets(Vec::new()))
+                    }
+                    Command::Shutdown => {
+                        shutdown.cancel();
+                        Ok(Reply::Done)
+                    }
+                    _ => Err(CommandError::Unsupported),
+                }
+            }
+        };
+        let config = ServerConfig {
+            max_connections: 1,
+            idle_timeout: Duration::from_millis(200),
+            request_timeout: Duration::from_millis(50),
+            ..ServerConfig::default()
+        };
+        let server = IpcServer::new(transport.bind(&path).unwrap(), handler).with_config(config).with_cancellation(cancel);
+        let server = tokio::spawn(server.run());
+
+        let mut client = transport.connect(&path).await.unwrap();
+        assert_eq!(client.request(1, Command::SetLogLevel(LogLevel::Info)).await.unwrap(), Ok(Reply::Done));
+        assert_eq!(client.request(2, Command::Pnl { strategy: None }).await.unwrap(), Err(CommandError::Unsupported));
+        assert_eq!(client.request(3, Command::ListDatasets).await.unwrap(), Err(CommandError::Timeout));
+
+        // a single worker: the second client is not even greeted until the first one idles out
+        let connecting = transport.connect(&path);
+        tokio::pin!(connecting);
+        assert!(tokio::time::timeout(Duration::from_millis(100), &mut connecting).await.is_err());
+        assert!(client.recv_message().await.unwrap_err().is_disconnect());
+        let mut second = connecting.await.unwrap();
+        assert_eq!(second.request(4, Command::Shutdown).await.unwrap(), Ok(Reply::Done));
+
+        server.await.unwrap().unwrap();
+        assert!(second.recv_message().await.unwrap_err().is_disconnect());
+    }
+
+    #[tokio::test]
+    async fn bounds_requests_and_event_kinds() {
+        let path = std::env::temp_dir().join(format!("iam-server-bounds.{}", std::process::id()));
+        let transport = UnixTransport::default();
+        let (events, _) = broadcast::channel(16);
+        let handler = |_| async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(Reply::Done)
+        };
+        let config = ServerConfig { max_requests: 1, ..ServerConfig::default() };
+        let server = IpcServer::new(transport.bind(&path).unwrap(), handler)
+            .with_config(config)
+            .with_events(events, &[EventKind::Candles]);
+        let _server = AbortOnDrop(tokio::spawn(server.run()));
+
+        let mut client = transport.connect(&path).await.unwrap();
+        client.send_message(&Message::request(1, Command::ListDatasets)).await.unwrap();
+        client.send_message(&Message::request(2, Command::ListDatasets)).await.unwrap();
+        assert_eq!(client.recv_message().await.unwrap(), Message::response(2, Err(CommandError::Busy)));
+        assert_eq!(client.recv_message().await.unwrap(), Message::response(1, Ok(Reply::Done)));
+
+        let refused = client.request(3, Command::Subscribe(vec![EventKind::Candles, EventKind::Jobs])).await.unwrap();
+        assert!(matches!(refused, Err(CommandError::InvalidArgument(_))));
+        assert_eq!(client.request(4, Command::Subscribe(vec![EventKind::Candles])).await.unwrap(), Ok(Reply::Done));
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tracing::debug;
+
+use crate::error::{Error, Result};
+use crate::message::{Command, CommandError, Event, EventKind, Message, Reply, RequestId};
+use crate::server::AbortOnDrop;
+use crate::transport::{Connection, MessageReader, MessageWriter};
+
+/// Events kept until read with [`Session::next_event`].
+const EVENT_BUFFER: usize = 256;
+
+type Pending = Arc<StdMutex<Option<HashMap<RequestId, oneshot::Sender<std::result::Result<Reply, CommandError>>>>>>;
+
+/// Long-lived client connection: any number of requests, also concurrent ones, matched to their
+/// responses by id, and the [`Event`]s the server pushes after [`Session::subscribe`].
+///
+/// Events are buffered until read with [`Session::next_event`]. Once 256 are waiting,
+/// further ones are dropped and reported as [`Event::Missed`] when there is room again, so responses
+/// never wait for events to be read.
+pub struct Session<W> {
+    writer: Mutex<W>,
+    pending: Pending,
+    next_id: AtomicU64,
+    events: mpsc::Receiver<Event>,
+    _reading: AbortOnDrop<()>,
+}
+
+impl<W: MessageWriter> Session<W> {
+    pub fn new<C: Connection<Writer = W>>(connection: C) -> Self {
+        let (mut reader, writer) = connection.into_split();
+        let pending: Pending = Arc::new(StdMutex::new(Some(HashMap::new())));
+        let (events_tx, events) = mpsc::channel(EVENT_BUFFER);
+        let responses = pending.clone();
+        let reading = tokio::spawn(async move {
+            let mut missed = 0;
+            loop {
+                match reader.recv_message().await {
+                    Ok(Message::Response { id, result }) => {
+                        let sender = responses.lock().expect("pending lock").as_mut().and_then(|p| p.remove(&id));
+                        match sender {
+                            Some(sender) => {
+                                let _ = sender.send(result);
+                            }
+                            None => debug!("Response to unknown request {id}"),
+                        }
+                    }
+                    Ok(Message::Event(event)) => deliver(&events_tx, &mut missed, event),
+                    Ok(other) => debug!("Ignoring {other:?}"),
+                    Err(e) => {
+                        if !e.is_disconnect() {
+                            debug!("Session closed: {e}");
+                        }
+                        break;
+                    }
+                }
+            }
+            // fails the waiting and future requests
+            responses.lock().expect("pending lock").take();
+        });
+        Self {
+            writer: Mutex::new(writer),
+            pending,
+            next_id: AtomicU64::new(1),
+            events,
+            _reading: AbortOnDrop(reading),
+        }
+    }
+
+    /// Sends `command` and waits for its response, other requests may be in flight meanwhile.
+    pub async fn request(&self, command: Command) -> Result<std::result::Result<Reply, CommandError>> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        match self.pending.lock().expect("pending lock").as_mut() {
+            Some(pending) => pending.insert(id, sender),
+            None => return Err(closed()),
+        };
+        if let Err(e) = self.writer.lock().await.send_message(&Message::request(id, command)).await {
+            if let Some(pending) = self.pending.lock().expect("pending lock").as_mut() {
+                pending.remove(&id);
+            }
+            return Err(e);
+        }
+        receiver.await.map_err(|_| closed())
+    }
+
+    pub async fn subscribe(&self, kinds: &[EventKind]) -> Result<std::result::Result<Reply, CommandError>> {
+        self.request(Command::Subscribe(kinds.to_vec())).await
+    }
+
+    pub async fn unsubscribe(&self, kinds: &[EventKind]) -> Result<std::result::Result<Reply, CommandError>> {
+        self.request(Command::Unsubscribe(kinds.to_vec())).await
+    }
+
+    /// Next pushed event, `None` once the connection is closed and every event was read.
+    pub async fn next_event(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+}
+
+/// Queues `event` without waiting. What does not fit is added to `missed`, which is queued as one
+/// [`Event::Missed`] ahead of the next event that finds room.
+fn deliver(events: &mpsc::Sender<Event>, missed: &mut u64, event: Event) {
+    let event = match event {
+        Event::Missed(n) => {
+            *missed += n;
+            None
+        }
+        event => Some(event),
+    };
+    if *missed > 0 {
+        match events.try_send(Event::Missed(*missed)) {
+            Ok(()) => *missed = 0,
+            Err(TrySendError::Full(_)) => {
+                *missed += event.is_some() as u64;
+                return;
+            }
+            Err(TrySendError::Closed(_)) => return,
+        }
+    }
+    if let Some(Err(TrySendError::Full(_))) = event.map(|event| events.try_send(event)) {
+        *missed += 1;
+    }
+}
+
+fn closed() -> Error {
+    Error::Io(std::io::ErrorKind::UnexpectedEof.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::broadcast;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::message::{CandleEvent, FillEvent};
+    use crate::server::IpcServer;
+    use crate::transport::Transport;
+    use crate::unix::UnixTransport;
+
+    fn candle(open_time: u64) -> Event {
+        Event::Candle(CandleEvent {
+            symbol: "BTCUSDT".to_string(),
+            interval: "1m".to_string(),
+            open_time,
+            open: 1.0,
+            high: 1.0,
+            low: 1.0,
+            close: 1.0,
+            volume: 1.0,
+        })
+    }
+
+    #[tokio::test]
+    async fn requests_and_events_on_one_connection() {
+        let path = std::env::temp_dir().join(format!("iam-session-test.{}", std::process::id()));
+        let transport = UnixTransport::default();
+        let (events, _) = broadcast::channel(16);
+        let cancel = CancellationToken::new();
+        let handler = |command| async move {
+            match command {
+                Command::ListDatasets => {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok(Reply::Datasets(Vec::new()))
+                }
+                _ => Err(CommandError::Unsupported),
+            }
+        };
+        let server = IpcServer::new(transport.bind(&path).unwrap(), handler)
+            .with_events(events.clone(), &[EventKind::Candles, EventKind::Fills])
+            .with_cancellation(cancel.clone());
+        let server = tokio::spawn(server.run());
+
+        let mut session = Session::new(transport.connect(&path).await.unwrap());
+        assert_eq!(session.subscribe(&[EventKind::Candles]).await.unwrap(), Ok(Reply::Done));
+        let (datasets, shutdown) = tokio::join!(session.request(Command::ListDatasets), session.request(Command::Shutdown));
+        assert_eq!(datasets.unwrap(), Ok(Reply::Datasets(Vec::new())));
+        assert_eq!(shutdown.unwrap(), Err(CommandError::Unsupported));
+
+        let fill = Event::Fill(FillEvent {
+            symbol: "BTCUSDT".to_string(),
+            order_id: 1,
+            side: "BUY".to_string(),
+            quantity: 0.1,
+            price: 1.0,
+            time: 0,
+        });
+        events.send(fill.clone()).unwrap();
+        events.send(candle(0)).unwrap();
+        assert_eq!(session.next_event().await, Some(candle(0)));
+
+        session.subscribe(&[EventKind::Fills]).await.unwrap().unwrap();
+        session.unsubscribe(&[EventKind::Candles]).await.unwrap().unwrap();
+        events.send(candle(60_000)).unwrap();
+        events.send(fill.clone()).unwrap();
+        assert_eq!(session.next_event().await, Some(fill));
+
+        cancel.cancel();
+        server.await.unwrap().unwrap();
+        assert_eq!(session.next_event().await, None);
+        assert!(session.request(Command::ListDatasets).await.unwrap_err().is_disconnect());
+    }
+
+    #[tokio::test]
+    async fn unread_events_do_not_block_responses() {
+        let path = std::env::temp_dir().join(format!("iam-session-unread.{}", std::process::id()));
+        let transport = UnixTransport::default();
+        let (events, _) = broadcast::channel(1024);
+        let handler = |_| async { Ok(Reply::Done) };
+        let server = IpcServer::new(transport.bind(&path).unwrap(), handler).with_events(events.clone(), &[EventKind::Candles, EventKind::Fills]);
+        let _server = AbortOnDrop(tokio::spawn(server.run()));
+
+        let mut session = Session::new(transport.connect(&path).await.unwrap());
+        session.subscribe(&[EventKind::Candles]).await.unwrap().unwrap();
+        let sent = EVENT_BUFFER as u64 + 50;
+        for i in 0..sent {
+            events.send(candle(i)).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let reply = tokio::time::timeout(Duration::from_secs(1), session.request(Command::ListDatasets)).await;
+        assert_eq!(reply.expect("the response waited for events to be read").unwrap(), Ok(Reply::Done));
+
+        // every event is either read or counted as missed
+        events.send(candle(sent)).unwrap();
+        let (mut read, mut missed) = (0, 0);
+        loop {
+            match session.next_event().await.unwrap() {
+                Event::Missed(n) => missed += n,
+                Event::Candle(c) if c.open_time == sent => break,
+                _ => read += 1,
+            }
+        }
+        assert!(missed > 0);
+        assert_eq!(read + missed, sent);
+    }
+}
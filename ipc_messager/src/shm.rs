@@ -0,0 +1,373 @@
+//! Single producer, many consumer ring buffer in shared memory, for strategy processes that cannot
+//! afford a trip through the kernel per kline.
+//!
+//! The file, best placed on a tmpfs like `/dev/shm` or the [`crate::runtime::RuntimeDir`], holds a
+//! [`Header`] followed by `capacity` slots of one [`Plain`] record each. The producer never waits:
+//! every slot is guarded by a sequence number written before and after the record (a seqlock), so
+//! consumers notice both records not written yet and records overwritten while they read. Consumers
+//! that fall more than `capacity` records behind skip ahead and count what they missed.
+
+use std::fs::OpenOptions;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+use memmap2::{Mmap, MmapMut, MmapOptions};
+
+use crate::error::{Error, Result};
+use crate::message::KlineRecord;
+
+pub const SHM_MAGIC: u64 = u64::from_le_bytes(*b"IAMSHMRB");
+pub const SHM_VERSION: u32 = 1;
+
+/// Fixed-layout record that may be copied into shared memory as raw bytes.
+///
+/// # Safety
+///
+/// Implementors have to be `#[repr(C)]`, without padding, pointers or anything invalid for some
+/// bit pattern, so a copy made by another process is a valid value.
+pub unsafe trait Plain: Copy + Send + 'static {}
+
+// SAFETY: repr(C), eleven 8 byte fields
+unsafe impl Plain for KlineRecord {}
+
+/// One trade, `symbol` padded with zeros.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickRecord {
+    pub symbol: [u8; 16],
+    pub trade_id: u64,
+    pub price: f64,
+    pub quantity: f64,
+    pub time: u64,
+    /// `1` when the buyer placed the resting order.
+    pub buyer_maker: u64,
+}
+
+// SAFETY: repr(C), every field 8 byte aligned and valid for any bits
+unsafe impl Plain for TickRecord {}
+
+impl TickRecord {
+    /// `symbol` is cut to 16 bytes.
+    pub fn symbol_bytes(symbol: &str) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        let len = symbol.len().min(16);
+        bytes[..len].copy_from_slice(&symbol.as_bytes()[..len]);
+        bytes
+    }
+
+    pub fn symbol(&self) -> &str {
+        let len = self.symbol.iter().position(|&b| b == 0).unwrap_or(16);
+        std::str::from_utf8(&self.symbol[..len]).unwrap_or_default()
+    }
+}
+
+#[repr(C)]
+struct Header {
+    magic: u64,
+    version: u32,
+    record_size: u32,
+    capacity: u64,
+    /// Sequence number of the next record, records are numbered from 1.
+    next: AtomicU64,
+}
+
+/// Sequence number followed by the record, the sequence is odd while the producer writes.
+#[repr(C)]
+struct Slot<T> {
+    seq: AtomicU64,
+    record: T,
+}
+
+/// Shared mapping of a ring of `T`.
+struct Ring<T> {
+    map: Map,
+    capacity: u64,
+    _record: PhantomData<T>,
+}
+
+/// The producer's mapping is writable, the consumers only ever read theirs.
+enum Map {
+    Writable(MmapMut),
+    ReadOnly(Mmap),
+}
+
+impl Map {
+    fn as_ptr(&self) -> *const u8 {
+        match self {
+            Map::Writable(map) => map.as_ptr(),
+            Map::ReadOnly(map) => map.as_ptr(),
+        }
+    }
+}
+
+impl<T: Plain> Ring<T> {
+    fn slots_offset() -> usize {
+        size_of::<Header>().next_multiple_of(align_of::<Slot<T>>())
+    }
+
+    /// Bytes of a ring of `capacity` records, an error if that does not fit in the address space.
+    fn len(capacity: u64) -> Result<usize> {
+        usize::try_from(capacity)
+            .ok()
+            .and_then(|capacity| size_of::<Slot<T>>().checked_mul(capacity))
+            .and_then(|slots| slots.checked_add(Self::slots_offset()))
+            .ok_or_else(|| Error::ShmLayout(format!("{capacity} records do not fit in memory")))
+    }
+
+    fn header(&self) -> &Header {
+        // SAFETY: the mapping is page aligned and at least `len(capacity)` bytes
+        unsafe { &*(self.map.as_ptr() as *const Header) }
+    }
+
+    fn slot(&self, seq: u64) -> *mut Slot<T> {
+        let index = ((seq - 1) % self.capacity) as usize;
+        // SAFETY: index < capacity and the mapping is at least `len(capacity)` bytes
+        unsafe { (self.map.as_ptr().add(Self::slots_offset()) as *mut Slot<T>).add(index) }
+    }
+}
+
+/// Writing end, there must only be one per file.
+pub struct ShmProducer<T> {
+    ring: Ring<T>,
+    path: PathBuf,
+}
+
+impl<T: Plain> ShmProducer<T> {
+    /// Creates or replaces the ring at `path`, only readable by the current user.
+    pub fn create<P: AsRef<Path>>(path: P, capacity: u64) -> Result<Self> {
+        if capacity == 0 {
+            return Err(Error::ShmLayout("capacity must not be zero".to_string()));
+        }
+        let len = Ring::<T>::len(capacity)?;
+        let path = path.as_ref().to_path_buf();
+        let _ = std::fs::remove_file(&path);
+        let file = OpenOptions::new().read(true).write(true).create_new(true).mode(0o600).open(&path)?;
+        file.set_len(len as u64)?;
+        // SAFETY: the file was just created by us, nobody truncates it while mapped
+        let map = unsafe { MmapOptions::new().map_mut(&file)? };
+        let ring = Ring { map: Map::Writable(map), capacity, _record: PhantomData };
+        // SAFETY: the mapping is zeroed and exclusively ours until the magic is written
+        unsafe {
+            let header = ring.map.as_ptr() as *mut Header;
+            (*header).version = SHM_VERSION;
+            (*header).record_size = size_of::<T>() as u32;
+            (*header).capacity = capacity;
+            (*header).next.store(1, Ordering::Relaxed);
+            fence(Ordering::Release);
+            (*header).magic = SHM_MAGIC;
+        }
+        Ok(Self { ring, path })
+    }
+
+    /// Appends `record`, overwriting the oldest one once the ring is full.
+    pub fn push(&mut self, record: &T) {
+        let header = self.ring.header();
+        let seq = header.next.load(Ordering::Relaxed);
+        let slot = self.ring.slot(seq);
+        // SAFETY: slot is inside the mapping, readers only copy the record and check the sequence
+        unsafe {
+            (*slot).seq.store(2 * seq - 1, Ordering::Relaxed);
+            fence(Ordering::Release);
+            std::ptr::write_volatile(std::ptr::addr_of_mut!((*slot).record), *record);
+            (*slot).seq.store(2 * seq, Ordering::Release);
+        }
+        header.next.store(seq + 1, Ordering::Release);
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl<T> Drop for ShmProducer<T> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Reading end, each consumer reads every record on its own.
+pub struct ShmConsumer<T> {
+    ring: Ring<T>,
+    /// Sequence number of the next record to read.
+    next: u64,
+    dropped: u64,
+}
+
+impl<T: Plain> ShmConsumer<T> {
+    /// Opens the ring at `path`, starting with the records pushed from now on.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len < size_of::<Header>() {
+            return Err(Error::ShmLayout(format!("{len} bytes are too short for a header")));
+        }
+        // SAFETY: the producer never resizes the file after creating it
+        let map = unsafe { MmapOptions::new().map(&file)? };
+        // SAFETY: at least a header long
+        let header = unsafe { &*(map.as_ptr() as *const Header) };
+        if header.magic != SHM_MAGIC {
+            return Err(Error::ShmLayout("not a ring buffer".to_string()));
+        }
+        fence(Ordering::Acquire);
+        if header.version != SHM_VERSION {
+            return Err(Error::ShmLayout(format!("version {}, expected {SHM_VERSION}", header.version)));
+        }
+        if header.record_size as usize != size_of::<T>() {
+            return Err(Error::ShmLayout(format!("records of {} bytes, expected {}", header.record_size, size_of::<T>())));
+        }
+        let capacity = header.capacity;
+        if capacity == 0 || len < Ring::<T>::len(capacity)? {
+            return Err(Error::ShmLayout(format!("{len} bytes are too short for {capacity} records")));
+        }
+        let next = header.next.load(Ordering::Acquire);
+        Ok(Self { ring: Ring { map: Map::ReadOnly(map), capacity, _record: PhantomData }, next, dropped: 0 })
+    }
+
+    /// Next record if one was pushed, without waiting.
+    pub fn try_next(&mut self) -> Option<T> {
+        loop {
+            let written = self.ring.header().next.load(Ordering::Acquire);
+            if self.next >= written {
+                return None;
+            }
+            if written - self.next > self.ring.capacity {
+                // overwritten before we got to it
+                let oldest = written - self.ring.capacity;
+                self.dropped += oldest - self.next;
+                self.next = oldest;
+            }
+            let slot = self.ring.slot(self.next);
+            // SAFETY: slot is inside the mapping, a torn copy is detected and discarded below
+            let (before, record, after) = unsafe {
+                let before = (*slot).seq.load(Ordering::Acquire);
+                let record = std::ptr::read_volatile(std::ptr::addr_of!((*slot).record));
+                fence(Ordering::Acquire);
+                (before, record, (*slot).seq.load(Ordering::Relaxed))
+            };
+            if before == 2 * self.next && after == before {
+                self.next += 1;
+                return Some(record);
+            }
+            // overwritten while reading, start over from the oldest record left
+        }
+    }
+
+    /// Spins until the next record arrives, yielding the thread now and then.
+    pub fn next_blocking(&mut self) -> T {
+        let mut spins = 0u32;
+        loop {
+            if let Some(record) = self.try_next() {
+                return record;
+            }
+            spins += 1;
+            if spins.is_multiple_of(128) {
+                std::thread::yield_now();
+            } else {
+                std::hint::spin_loop();
+            }
+        }
+    }
+
+    /// Records skipped because the producer overwrote them first.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(open_time: u64) -> KlineRecord {
+        KlineRecord {
+            open_time,
+            open: 1.0,
+            high: 2.0,
+            low: 0.5,
+            close: 1.5,
+            volume: 10.0,
+            close_time: open_time + 59_999,
+            quote_asset_volume: 15.0,
+            trade_number: 3,
+            buy_base: 4.0,
+            buy_quote: 6.0,
+        }
+    }
+
+    #[test]
+    fn consumers_follow_and_skip_overwritten() {
+        let path = std::env::temp_dir().join(format!("iam-shm-test.{}", std::process::id()));
+        let mut producer = ShmProducer::<KlineRecord>::create(&path, 4).unwrap();
+        producer.push(&record(0));
+        let mut first = ShmConsumer::<KlineRecord>::open(&path).unwrap();
+        let mut second = ShmConsumer::<KlineRecord>::open(&path).unwrap();
+        assert_eq!(first.try_next(), None);
+
+        producer.push(&record(1));
+        producer.push(&record(2));
+        assert_eq!(first.try_next(), Some(record(1)));
+        assert_eq!(first.try_next(), Some(record(2)));
+        assert_eq!(second.try_next(), Some(record(1)));
+
+        for t in 3..10 {
+            producer.push(&record(t));
+        }
+        assert_eq!(second.try_next(), Some(record(6)));
+        assert_eq!(second.dropped(), 4);
+        assert_eq!((6..10).map(|_| first.try_next().unwrap().open_time).last(), Some(9));
+        assert_eq!(first.try_next(), None);
+
+        assert!(matches!(ShmConsumer::<TickRecord>::open(&path), Err(Error::ShmLayout(_))));
+        drop(producer);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn refuses_sizes_beyond_the_address_space() {
+        let path = std::env::temp_dir().join(format!("iam-shm-overflow.{}", std::process::id()));
+        assert!(matches!(ShmProducer::<KlineRecord>::create(&path, u64::MAX / 8), Err(Error::ShmLayout(_))));
+
+        // a header claiming more records than fit, the slots would wrap to a tiny mapping
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&SHM_MAGIC.to_ne_bytes());
+        bytes.extend_from_slice(&SHM_VERSION.to_ne_bytes());
+        bytes.extend_from_slice(&(size_of::<KlineRecord>() as u32).to_ne_bytes());
+        bytes.extend_from_slice(&(1u64 << 60).to_ne_bytes());
+        bytes.extend_from_slice(&1u64.to_ne_bytes());
+        bytes.resize(4096, 0);
+        std::fs::write(&path, bytes).unwrap();
+        assert!(matches!(ShmConsumer::<KlineRecord>::open(&path), Err(Error::ShmLayout(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn across_threads() {
+        let path = std::env::temp_dir().join(format!("iam-shm-threads.{}", std::process::id()));
+        let mut producer = ShmProducer::<TickRecord>::create(&path, 1024).unwrap();
+        let mut consumer = ShmConsumer::<TickRecord>::open(&path).unwrap();
+        let reader = std::thread::spawn(move || {
+            let mut last = 0;
+            while last < 10_000 {
+                let tick = consumer.next_blocking();
+                assert_eq!(tick.symbol(), "BTCUSDT");
+                assert_eq!(tick.price, tick.trade_id as f64 * 0.5);
+                assert!(tick.trade_id > last);
+                last = tick.trade_id;
+            }
+        });
+        for trade_id in 1..=10_000 {
+            producer.push(&TickRecord {
+                symbol: TickRecord::symbol_bytes("BTCUSDT"),
+                trade_id,
+                price: trade_id as f64 * 0.5,
+                quantity: 1.0,
+                time: trade_id,
+                buyer_maker: trade_id % 2,
+            });
+        }
+        reader.join().unwrap();
+    }
+}
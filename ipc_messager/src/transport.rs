@@ -0,0 +1,127 @@
+use std::future::Future;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::message::{Command, CommandError, Message, Reply, RequestId};
+
+/// A connection exchanging [`Message`]s with one peer.
+pub trait Connection: Send {
+    type Reader: MessageReader;
+    type Writer: MessageWriter;
+
+    fn send_message(&mut self, msg: &Message) -> impl Future<Output = Result<()>> + Send;
+
+    fn recv_message(&mut self) -> impl Future<Output = Result<Message>> + Send;
+
+    /// Separates both directions, so one task can wait for messages while another sends.
+    fn into_split(self) -> (Self::Reader, Self::Writer);
+
+    /// Sends `command` and waits for the response carrying the same `id`.
+    fn request(&mut self, id: RequestId, command: Command) -> impl Future<Output = Result<std::result::Result<Reply, CommandError>>> + Send {
+        async move {
+            self.send_message(&Message::request(id, command)).await?;
+            response(id, self.recv_message().await?)
+        }
+    }
+}
+
+/// Receiving half of a [`Connection`].
+pub trait MessageReader: Send + 'static {
+    /// Not cancel safe: a message partially read when the future is dropped is lost.
+    fn recv_message(&mut self) -> impl Future<Output = Result<Message>> + Send;
+}
+
+/// Sending half of a [`Connection`].
+pub trait MessageWriter: Send + 'static {
+    fn send_message(&mut self, msg: &Message) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Server side of a [`Transport`], handing out one [`Connection`] per client.
+pub trait Listener: Send + Sync {
+    type Connection: Connection;
+
+    fn accept(&self) -> impl Future<Output = Result<Self::Connection>> + Send;
+}
+
+/// How clients reach a server at a path, e.g. [`crate::fifo::FifoTransport`] or [`crate::unix::UnixTransport`].
+pub trait Transport: Send + Sync {
+    type Connection: Connection;
+    type Listener: Listener<Connection = Self::Connection>;
+
+    /// Starts listening on `path`, replacing whatever stale file is left there.
+    fn bind(&self, path: &Path) -> Result<Self::Listener>;
+
+    fn connect(&self, path: &Path) -> impl Future<Output = Result<Self::Connection>> + Send;
+}
+
+/// The reply of the request `id`, or what the peer sent instead.
+pub(crate) fn response(id: RequestId, message: Message) -> Result<std::result::Result<Reply, CommandError>> {
+    match message {
+        Message::Response { id: got, result } if got == id => Ok(result),
+        other => Err(Error::UnexpectedResponse { id, got: Box::new(other) }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fifo::FifoTransport;
+    use crate::message::LogLevel;
+    use crate::unix::UnixTransport;
+
+    /// What every transport has to get right.
+    async fn suite<T: Transport + 'static>(transport: T, name: &str) {
+        let path = std::env::temp_dir().join(format!("iam-{name}-suite.{}", std::process::id()));
+        let listener = transport.bind(&path).unwrap();
+
+        // two clients of the same process at once
+        let (first, second, a, b) = tokio::join!(transport.connect(&path), transport.connect(&path), listener.accept(), listener.accept());
+        let (mut first, mut second) = (first.unwrap(), second.unwrap());
+        let mut servers = vec![a.unwrap(), b.unwrap()];
+
+        first.send_message(&Message::request(1, Command::ListDatasets)).await.unwrap();
+        second.send_message(&Message::request(2, Command::SetLogLevel(LogLevel::Warn))).await.unwrap();
+        for server in &mut servers {
+            let Message::Request { id, .. } = server.recv_message().await.unwrap() else {
+                panic!("expected a request");
+            };
+            let reply = if id == 1 { Ok(Reply::Datasets(Vec::new())) } else { Err(CommandError::Unsupported) };
+            server.send_message(&Message::response(id, reply)).await.unwrap();
+        }
+        assert_eq!(response(1, first.recv_message().await.unwrap()).unwrap(), Ok(Reply::Datasets(Vec::new())));
+        assert_eq!(response(2, second.recv_message().await.unwrap()).unwrap(), Err(CommandError::Unsupported));
+
+        // correlated requests, and a mismatched id is reported
+        let client = tokio::spawn(async move {
+            let done = first.request(3, Command::Shutdown).await.unwrap();
+            let mismatch = first.request(4, Command::Shutdown).await;
+            (first, done, mismatch)
+        });
+        let server = &mut servers[0];
+        assert_eq!(server.recv_message().await.unwrap(), Message::request(3, Command::Shutdown));
+        server.send_message(&Message::response(3, Ok(Reply::Done))).await.unwrap();
+        server.recv_message().await.unwrap();
+        server.send_message(&Message::response(9, Ok(Reply::Done))).await.unwrap();
+        let (first, done, mismatch) = client.await.unwrap();
+        assert_eq!(done, Ok(Reply::Done));
+        assert!(matches!(mismatch, Err(Error::UnexpectedResponse { id: 4, .. })));
+
+        drop(first);
+        assert!(servers[0].recv_message().await.unwrap_err().is_disconnect());
+        drop(servers);
+        assert!(second.recv_message().await.unwrap_err().is_disconnect());
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn fifo() {
+        suite(FifoTransport::default(), "fifo").await;
+    }
+
+    #[tokio::test]
+    async fn unix_socket() {
+        suite(UnixTransport::default(), "unix").await;
+    }
+}
@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::codec::{CodecKind, Wire};
+use crate::error::{Error, Result};
+use crate::frame::DEFAULT_MAX_FRAME_LEN;
+use crate::message::Message;
+use crate::transport::{Connection, Listener, MessageReader, MessageWriter, Transport};
+
+/// Unix domain socket transport. The server checks who connects with `SO_PEERCRED` and only lets
+/// the allowed users in, by default the user running the server.
+#[derive(Debug, Clone)]
+pub struct UnixTransport {
+    allowed_uids: Vec<u32>,
+    max_frame_len: usize,
+    codecs: Vec<CodecKind>,
+}
+
+impl Default for UnixTransport {
+    fn default() -> Self {
+        Self {
+            allowed_uids: vec![unsafe { libc::geteuid() }],
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            codecs: CodecKind::ALL.to_vec(),
+        }
+    }
+}
+
+impl UnixTransport {
+    /// Replaces the users allowed to connect.
+    pub fn with_allowed_uids(mut self, uids: &[u32]) -> Self {
+        self.allowed_uids = uids.to_vec();
+        self
+    }
+
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// Codecs servers accept and clients offer, most preferred first, see [`crate::codec`].
+    pub fn with_codecs(mut self, codecs: &[CodecKind]) -> Self {
+        self.codecs = codecs.to_vec();
+        self
+    }
+}
+
+impl Transport for UnixTransport {
+    type Connection = UnixConnection;
+    type Listener = UnixSocketListener;
+
+    fn bind(&self, path: &Path) -> Result<UnixSocketListener> {
+        let _ = std::fs::remove_file(path);
+        Ok(UnixSocketListener {
+            listener: UnixListener::bind(path)?,
+            path: path.to_path_buf(),
+            allowed_uids: self.allowed_uids.clone(),
+            max_frame_len: self.max_frame_len,
+            codecs: self.codecs.clone(),
+        })
+    }
+
+    async fn connect(&self, path: &Path) -> Result<UnixConnection> {
+        let mut stream = UnixStream::connect(path).await?;
+        let (mut read, mut write) = stream.split();
+        let wire = Wire::offer(&mut read, &mut write, &self.codecs, self.max_frame_len).await?;
+        Ok(UnixConnection { stream, wire })
+    }
+}
+
+/// Listening socket, removed from the file system when dropped.
+pub struct UnixSocketListener {
+    listener: UnixListener,
+    path: PathBuf,
+    allowed_uids: Vec<u32>,
+    max_frame_len: usize,
+    codecs: Vec<CodecKind>,
+}
+
+impl Listener for UnixSocketListener {
+    type Connection = UnixConnection;
+
+    /// Fails with [`Error::PeerRejected`] for users not allowed in, the listener stays usable.
+    async fn accept(&self) -> Result<UnixConnection> {
+        let (mut stream, _) = self.listener.accept().await?;
+        let uid = stream.peer_cred()?.uid();
+        if !self.allowed_uids.contains(&uid) {
+            return Err(Error::PeerRejected { uid });
+        }
+        let (mut read, mut write) = stream.split();
+        let wire = Wire::answer(&mut read, &mut write, &self.codecs, self.max_frame_len).await?;
+        Ok(UnixConnection { stream, wire })
+    }
+}
+
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+pub struct UnixConnection {
+    stream: UnixStream,
+    wire: Wire,
+}
+
+impl UnixConnection {
+    /// Codec agreed on during the handshake.
+    pub fn codec(&self) -> CodecKind {
+        self.wire.codec
+    }
+}
+
+impl Connection for UnixConnection {
+    type Reader = UnixReader;
+    type Writer = UnixWriter;
+
+    async fn send_message(&mut self, msg: &Message) -> Result<()> {
+        self.wire.send(&mut self.stream, msg).await
+    }
+
+    async fn recv_message(&mut self) -> Result<Message> {
+        self.wire.recv(&mut self.stream).await
+    }
+
+    fn into_split(self) -> (UnixReader, UnixWriter) {
+        let (read, write) = self.stream.into_split();
+        (UnixReader { read, wire: self.wire }, UnixWriter { write, wire: self.wire })
+    }
+}
+
+pub struct UnixReader {
+    read: OwnedReadHalf,
+    wire: Wire,
+}
+
+impl MessageReader for UnixReader {
+    async fn recv_message(&mut self) -> Result<Message> {
+        self.wire.recv(&mut self.read).await
+    }
+}
+
+pub struct UnixWriter {
+    write: OwnedWriteHalf,
+    wire: Wire,
+}
+
+impl MessageWriter for UnixWriter {
+    async fn send_message(&mut self, msg: &Message) -> Result<()> {
+        self.wire.send(&mut self.write, msg).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_other_users() {
+        let path = std::env::temp_dir().join(format!("iam-unix-peercred.{}", std::process::id()));
+        let uid = unsafe { libc::geteuid() };
+        let transport = UnixTransport::default().with_allowed_uids(&[uid + 1]);
+        let listener = transport.bind(&path).unwrap();
+        let (client, server) = tokio::join!(transport.connect(&path), listener.accept());
+        assert!(matches!(server, Err(Error::PeerRejected { uid: u }) if u == uid));
+        assert!(matches!(client, Err(e) if e.is_disconnect()));
+    }
+
+    #[tokio::test]
+    async fn json_client() {
+        use crate::frame::{read_frame_async, write_frame_async};
+        use crate::message::{Command, CommandError};
+
+        let path = std::env::temp_dir().join(format!("iam-unix-json.{}", std::process::id()));
+        let listener = UnixTransport::default().with_codecs(&[CodecKind::Bincode, CodecKind::Json]).bind(&path).unwrap();
+
+        // what a script without any Rust types sends
+        let script = async {
+            let mut stream = UnixStream::connect(&path).await.unwrap();
+            write_frame_async(&mut stream, b"cbor,json", DEFAULT_MAX_FRAME_LEN).await.unwrap();
+            assert_eq!(read_frame_async(&mut stream, DEFAULT_MAX_FRAME_LEN).await.unwrap(), b"json");
+            write_frame_async(&mut stream, br#"{"Request":{"id":3,"command":"ListDatasets"}}"#, DEFAULT_MAX_FRAME_LEN).await.unwrap();
+            read_frame_async(&mut stream, DEFAULT_MAX_FRAME_LEN).await.unwrap()
+        };
+        let server = async {
+            let mut conn = listener.accept().await.unwrap();
+            assert_eq!(conn.codec(), CodecKind::Json);
+            assert_eq!(conn.recv_message().await.unwrap(), Message::request(3, Command::ListDatasets));
+            conn.send_message(&Message::response(3, Err(CommandError::Unsupported))).await.unwrap();
+        };
+        let (reply, ()) = tokio::join!(script, server);
+        assert_eq!(reply, br#"{"Response":{"id":3,"result":{"Err":"Unsupported"}}}"#);
+
+        let msgpack_only = UnixTransport::default().with_codecs(&[CodecKind::MessagePack]);
+        let (client, server) = tokio::join!(msgpack_only.connect(&path), listener.accept());
+        assert!(matches!(client, Err(Error::NoCommonCodec(offered)) if offered == "msgpack"));
+        assert!(matches!(server, Err(Error::Handshake(e)) if matches!(*e, Error::NoCommonCodec(ref offered) if offered == "msgpack")));
+        // the listener still serves the next client
+        let rust = UnixTransport::default();
+        let (client, server) = tokio::join!(rust.connect(&path), listener.accept());
+        assert_eq!((client.unwrap().codec(), server.unwrap().codec()), (CodecKind::Bincode, CodecKind::Bincode));
+    }
+}
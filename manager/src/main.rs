@@ -1,13 +1,365 @@
-use data_downloader::Requester;
-use tracing_subscriber::{fmt, EnvFilter, prelude::*};
+use std::time::Duration;
+
+use backtester::optimize::{self, Objective, Optimizer, ParamGrid};
+use backtester::strategies::{RsiThreshold, RsiThresholdConfig};
+use backtester::{Backtester, BrokerConfig, Metrics, Report};
+use data_downloader::{FeatureSpec, Kline, Requester};
+use ipc_messager::bus::Bus;
+use ipc_messager::message::{CandleEvent, Command, CommandError, Event, EventKind, FillEvent, LogLevel, LogLine, PnlReport, PositionInfo, Reply};
+use ipc_messager::runtime::RuntimeDir;
+use ipc_messager::server::{Handler, IpcServer};
+use ipc_messager::transport::Transport;
+use ipc_messager::unix::UnixTransport;
+use tokio::sync::{broadcast, watch};
+use tokio::sync::mpsc::Receiver;
+use tokio_util::sync::CancellationToken;
+use trader::{split_symbol, Exchange, ExecutionType, Ledger, Mode, OrderManager, PaperExchange, RiskManager, Side, Trader, TraderConfig, TradingClient, UserEvent, UserStream};
+use tracing::field::{Field, Visit};
+use tracing::{error, info, warn, Level};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry, prelude::*};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
 
 #[tokio::main]
 async fn main() {
+    // the filter can be swapped at runtime over IPC
+    let (filter, log_filter) = reload::Layer::new(EnvFilter::from_default_env());
+    // candles, fills and log lines pushed to the IPC clients watching the bot
+    let (published, _) = broadcast::channel(256);
     tracing_subscriber::registry()
+        .with(filter)
         .with(fmt::layer())
-        .with(EnvFilter::from_default_env())
+        .with(LogEvents(published.clone()))
         .init();
 
-    let requester = Requester::default();
-    requester.get_historical_data("BTCUSDT", "1h", 1704110400000, 1715177886000, "BTCUSDT20240101.parquet").await;
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        // manager backtest <klines.parquet> [report.html]
+        Some("backtest") => {
+            let input = args.get(2).map_or("BTCUSDT20240101.parquet", String::as_str);
+            let output = args.get(3).map_or("report.html", String::as_str);
+            let strategy = RsiThreshold::new(RsiThresholdConfig::default());
+            let result = Backtester::new(strategy, BrokerConfig::default())
+                .run_file(input)
+                .expect("Could not run backtest");
+            Report::new(input, &result).write_html(output).expect("Could not write report");
+            println!("{}", Metrics::from_result(&result).to_json());
+        }
+        // manager optimize <klines.parquet> [results.parquet]
+        Some("optimize") => {
+            let input = args.get(2).map_or("BTCUSDT20240101.parquet", String::as_str);
+            let output = args.get(3).map_or("optimization.parquet", String::as_str);
+            let data = data_downloader::read_klines(input).expect("Could not read klines");
+            let grid = ParamGrid::new()
+                .param("period", &[7.0, 14.0, 21.0])
+                .param("buy_below", &[20.0, 25.0, 30.0, 35.0])
+                .param("sell_above", &[65.0, 70.0, 75.0, 80.0]);
+            let evaluations = Optimizer::new(BrokerConfig::default(), Objective::Sharpe)
+                .grid_search(&data, &grid, &|params| RsiThreshold::new(RsiThresholdConfig::from_params(params)))
+                .expect("Could not search the parameter grid");
+            let mut df = optimize::evaluations_df(&evaluations).expect("Could not build results table");
+            optimize::write_df(output, &mut df).expect("Could not write results");
+            println!("{}", df.head(Some(10)));
+        }
+        // manager live <symbol> <interval>
+        Some("live") => {
+            let symbol = args.get(2).map_or("BTCUSDT", String::as_str).to_string();
+            let interval = args.get(3).map_or("1m", String::as_str).to_string();
+            let receiver = stream_klines(symbol, interval);
+            let mut backtester = Backtester::new(RsiThreshold::new(RsiThresholdConfig::default()), BrokerConfig::default());
+            backtester::run_live(&mut backtester, receiver).await;
+            println!("{}", Metrics::from_result(&backtester.into_result()).to_json());
+        }
+        // manager trade [trader.toml]
+        Some("trade") => {
+            let config = match args.get(2) {
+                Some(path) => TraderConfig::from_path(path).expect("Could not load trader config"),
+                None => TraderConfig::default(),
+            };
+            match config.mode {
+                Mode::Paper => {
+                    let exchange = PaperExchange::new(config.paper.clone()).expect("Could not open paper account");
+                    trade(exchange, None, &config, log_filter, published).await;
+                }
+                Mode::Live => {
+                    let api_key = std::env::var("BINANCE_API_KEY").expect("BINANCE_API_KEY is not set");
+                    let secret_key = std::env::var("BINANCE_SECRET_KEY").expect("BINANCE_SECRET_KEY is not set");
+                    let client = TradingClient::new(&api_key, &secret_key);
+                    client.sync_time().await.expect("Could not sync with the server time");
+                    let (sender, events) = tokio::sync::mpsc::channel(256);
+                    let stream = UserStream::new(TradingClient::new(&api_key, &secret_key));
+                    tokio::spawn(async move { stream.run(sender).await });
+                    trade(client, Some(events), &config, log_filter, published).await;
+                }
+            }
+        }
+        _ => {
+            let requester = Requester::default();
+            requester.get_historical_data("BTCUSDT", "1h", 1704110400000, 1715177886000, "BTCUSDT20240101.parquet")
+                .await
+                .expect("Could not download historical data");
+
+            // the feature spec can be swapped without recompiling, falls back to RSI[14]
+            let spec = match std::env::var("IAM_FEATURE_SPEC") {
+                Ok(path) => FeatureSpec::from_path(path).expect("Could not load feature spec"),
+                Err(_) => FeatureSpec::default(),
+            };
+            spec.run("BTCUSDT20240101.parquet").expect("Could not build feature file");
+        }
+    }
+}
+
+/// Runs the RSI strategy on `exchange` with the closed candles of the configured stream,
+/// following the account through `events` when given. Controlled over the socket of the mode's
+/// runtime dir until told to shut down, candles, fills and log lines are published there.
+async fn trade<E: Exchange>(exchange: E, events: Option<Receiver<UserEvent>>, config: &TraderConfig, log_filter: LogFilter, published: broadcast::Sender<Event>) {
+    let shutdown = CancellationToken::new();
+    // fills only come with the user data stream, no download jobs run here
+    let mut kinds = vec![EventKind::Candles, EventKind::Logs];
+    if events.is_some() {
+        kinds.push(EventKind::Fills);
+    }
+    let instance = match config.mode {
+        Mode::Paper => "paper",
+        Mode::Live => "live",
+    };
+    // what the trader booked, for the positions and PnL asked for over IPC
+    let (ledger_updates, ledger) = watch::channel(Ledger::new(split_symbol(&config.symbol).map_or("", |(_, quote)| quote)));
+    let dir = RuntimeDir::for_instance(instance).expect("paper and live are valid instance names");
+    match dir.create().and_then(|dir| UnixTransport::default().bind(&dir.socket_path())) {
+        Ok(listener) => {
+            let server = IpcServer::new(listener, control_handler(log_filter, ledger, shutdown.clone()))
+                .with_events(published.clone(), &kinds)
+                .with_cancellation(shutdown.clone());
+            tokio::spawn(async move {
+                if let Err(e) = server.run().await {
+                    error!("Control server failed: {e}");
+                }
+            });
+        }
+        Err(e) => warn!("Could not listen on {}: {e}", dir.socket_path().display()),
+    }
+    // the klines fanned out to other local processes
+    let bus = Bus::new();
+    match UnixTransport::default().bind(&dir.bus_path()) {
+        Ok(listener) => {
+            let (bus, shutdown) = (bus.clone(), shutdown.clone());
+            tokio::spawn(async move {
+                if let Err(e) = bus.serve(listener, shutdown).await {
+                    error!("Kline bus failed: {e}");
+                }
+            });
+        }
+        Err(e) => warn!("Could not listen on {}: {e}", dir.bus_path().display()),
+    }
+
+    let receiver = stream_klines(config.symbol.clone(), config.interval.clone());
+    let receiver = fan_out(receiver, bus, ipc_messager::bus::topic(&config.symbol, &config.interval));
+    let (symbol, interval) = (config.symbol.clone(), config.interval.clone());
+    let receiver = publish(receiver, published.clone(), move |kline| {
+        Some(Event::Candle(CandleEvent {
+            symbol: symbol.clone(),
+            interval: interval.clone(),
+            open_time: kline.open_time,
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+        }))
+    });
+    let orders = match &config.orders_db {
+        Some(path) => OrderManager::open(path),
+        None => OrderManager::in_memory(),
+    }
+    .expect("Could not open the order database");
+    let mut risk = RiskManager::new(config.risk.clone());
+    if config.mode == Mode::Paper {
+        // paper orders follow the rules of the real exchange, exchangeInfo needs no keys
+        match TradingClient::new("", "").symbol_filters(&config.symbol).await {
+            Ok(filters) => risk.set_filters(&config.symbol, filters),
+            Err(e) => warn!("Could not load the filters of {}: {e}", config.symbol),
+        }
+    }
+    let strategy = trader::strategies::RsiThreshold::new(&config.symbol, RsiThresholdConfig::default());
+    let mut trader = Trader::new(exchange, strategy, orders, &config.symbol)
+        .with_risk(risk)
+        .with_ledger_updates(ledger_updates);
+    if let Some(events) = events {
+        trader = trader.with_user_events(publish(events, published, |event| match event {
+            UserEvent::ExecutionReport(report) if report.execution_type == ExecutionType::Trade => Some(Event::Fill(FillEvent {
+                symbol: report.symbol.clone(),
+                order_id: report.order_id,
+                side: match report.side {
+                    Side::Buy => "BUY",
+                    Side::Sell => "SELL",
+                }
+                .to_string(),
+                quantity: report.last_quantity,
+                price: report.last_price,
+                time: report.transaction_time,
+            })),
+            _ => None,
+        }));
+    }
+    tokio::select! {
+        result = trader.run(receiver) => if let Err(e) = result {
+            error!("Trading stopped: {e}");
+        },
+        _ = shutdown.cancelled() => info!("Shutting down"),
+    }
+    shutdown.cancel();
+}
+
+/// Closed candles of `symbol` until the receiver is dropped. Binance closes market streams at least
+/// once a day, dropped connections are reopened with a delay doubling from 1s up to a minute.
+fn stream_klines(symbol: String, interval: String) -> Receiver<Kline> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(64);
+    tokio::spawn(async move {
+        let requester = Requester::default();
+        let mut delay = RECONNECT_DELAY;
+        loop {
+            match requester.connect_to_ws(&symbol, &interval, sender.clone()).await {
+                Ok(()) => delay = RECONNECT_DELAY,
+                Err(e) => warn!("Kline stream failed: {e}"),
+            }
+            if sender.is_closed() {
+                break;
+            }
+            info!("Kline stream ended, reconnecting in {delay:?}");
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    });
+    receiver
+}
+
+/// Passes everything from `input` on, publishing what `to_event` turns into an event.
+fn publish<T, F>(mut input: Receiver<T>, events: broadcast::Sender<Event>, to_event: F) -> Receiver<T>
+where
+    T: Send + 'static,
+    F: Fn(&T) -> Option<Event> + Send + 'static,
+{
+    let (sender, output) = tokio::sync::mpsc::channel(64);
+    tokio::spawn(async move {
+        while let Some(item) = input.recv().await {
+            if let Some(event) = to_event(&item) {
+                // nobody watching is fine
+                let _ = events.send(event);
+            }
+            if sender.send(item).await.is_err() {
+                break;
+            }
+        }
+    });
+    output
+}
+
+/// Passes every kline from `input` on, and publishes it on `bus` under `topic` without waiting for
+/// the subscribers.
+fn fan_out(mut input: Receiver<Kline>, bus: Bus, topic: String) -> Receiver<Kline> {
+    let (sender, output) = tokio::sync::mpsc::channel(64);
+    // publishing in its own task, a subscriber that blocks the bus must not hold up trading
+    let (publish_tx, mut publish_rx) = tokio::sync::mpsc::channel::<Kline>(64);
+    tokio::spawn(async move {
+        while let Some(kline) = publish_rx.recv().await {
+            bus.publish(&topic, &kline).await;
+        }
+    });
+    tokio::spawn(async move {
+        while let Some(kline) = input.recv().await {
+            if publish_tx.try_send(kline.clone()).is_err() {
+                warn!("Kline bus is behind, dropping a kline");
+            }
+            if sender.send(kline).await.is_err() {
+                break;
+            }
+        }
+    });
+    output
+}
+
+type LogFilter = reload::Handle<EnvFilter, Registry>;
+
+/// Publishes the log lines that pass the filter as [`Event::Log`].
+struct LogEvents(broadcast::Sender<Event>);
+
+impl<S: tracing::Subscriber> Layer<S> for LogEvents {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        // the lines of the IPC server sending them would feed themselves back
+        if self.0.receiver_count() == 0 || metadata.target().starts_with("ipc_messager") {
+            return;
+        }
+        let level = match *metadata.level() {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warn,
+            Level::INFO => LogLevel::Info,
+            Level::DEBUG => LogLevel::Debug,
+            Level::TRACE => LogLevel::Trace,
+        };
+        let mut message = LogMessage(String::new());
+        event.record(&mut message);
+        let _ = self.0.send(Event::Log(LogLine { level, target: metadata.target().to_string(), message: message.0 }));
+    }
+}
+
+/// The message of a log line followed by its other fields as `name=value`.
+struct LogMessage(String);
+
+impl Visit for LogMessage {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{value:?}"));
+        } else {
+            self.0.push_str(&format!(" {}={value:?}", field.name()));
+        }
+    }
+}
+
+/// Commands the trading manager serves, the others are reported as unsupported. Positions and PnL
+/// come from the latest copy of the trader's `ledger`, it runs a single unnamed strategy.
+fn control_handler(log_filter: LogFilter, ledger: watch::Receiver<Ledger>, shutdown: CancellationToken) -> impl Handler {
+    move |command| {
+        let result = match command {
+            Command::Positions { strategy: Some(name) } | Command::Pnl { strategy: Some(name) } => Err(CommandError::UnknownStrategy(name)),
+            Command::Positions { strategy: None } => {
+                let ledger = ledger.borrow();
+                Ok(Reply::Positions(ledger
+                    .positions()
+                    .iter()
+                    .map(|(asset, position)| PositionInfo {
+                        asset: asset.clone(),
+                        quantity: position.quantity,
+                        avg_entry: position.avg_entry,
+                        last_price: position.last_price,
+                        realized_pnl: position.realized_pnl,
+                        unrealized_pnl: position.unrealized_pnl(),
+                    })
+                    .collect()))
+            }
+            Command::Pnl { strategy: None } => {
+                let ledger = ledger.borrow();
+                Ok(Reply::Pnl(PnlReport {
+                    quote: ledger.quote().to_string(),
+                    realized: ledger.realized_pnl(),
+                    unrealized: ledger.unrealized_pnl(),
+                    fees: ledger.fees(),
+                    equity: ledger.equity(),
+                }))
+            }
+            Command::SetLogLevel(level) => log_filter
+                .modify(|filter| *filter = EnvFilter::new(level.as_str()))
+                .map(|()| Reply::Done)
+                .map_err(|e| CommandError::Internal(e.to_string())),
+            Command::Shutdown => {
+                shutdown.cancel();
+                Ok(Reply::Done)
+            }
+            _ => Err(CommandError::Unsupported),
+        };
+        std::future::ready(result)
+    }
 }
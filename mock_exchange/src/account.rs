@@ -0,0 +1,518 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use axum::extract::{RawQuery, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use crate::market::{last_price, now_ms, split_symbol};
+use crate::server::Shared;
+
+type ApiError = (StatusCode, i64, &'static str);
+
+pub(crate) fn api_error(status: StatusCode, code: i64, msg: &str) -> Response {
+    (status, Json(json!({ "code": code, "msg": msg }))).into_response()
+}
+
+fn reject((status, code, msg): ApiError) -> Response {
+    api_error(status, code, msg)
+}
+
+fn fmt(value: f64) -> String {
+    format!("{value:.8}")
+}
+
+#[derive(Debug, Clone)]
+struct MockOrder {
+    symbol: String,
+    order_id: u64,
+    client_order_id: String,
+    side: String,
+    order_type: String,
+    time_in_force: String,
+    price: f64,
+    stop_price: f64,
+    orig_qty: f64,
+    executed_qty: f64,
+    cumulative_quote_qty: f64,
+    status: &'static str,
+    time: u64,
+    update_time: u64,
+}
+
+impl MockOrder {
+    fn is_open(&self) -> bool {
+        matches!(self.status, "NEW" | "PARTIALLY_FILLED")
+    }
+
+    fn is_buy(&self) -> bool {
+        self.side == "BUY"
+    }
+
+    /// Price the funds of a resting order are reserved at.
+    fn reserve_price(&self) -> f64 {
+        self.price.max(self.stop_price)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "symbol": self.symbol,
+            "orderId": self.order_id,
+            "orderListId": -1,
+            "clientOrderId": self.client_order_id,
+            "transactTime": self.update_time,
+            "time": self.time,
+            "updateTime": self.update_time,
+            "price": fmt(self.price),
+            "origQty": fmt(self.orig_qty),
+            "executedQty": fmt(self.executed_qty),
+            "cummulativeQuoteQty": fmt(self.cumulative_quote_qty),
+            "status": self.status,
+            "timeInForce": self.time_in_force,
+            "type": self.order_type,
+            "side": self.side,
+            "stopPrice": fmt(self.stop_price),
+            "isWorking": self.is_open(),
+        })
+    }
+
+    fn execution_report(&self, execution_type: &str, last_qty: f64, last_price: f64, trade_id: i64) -> Value {
+        json!({
+            "e": "executionReport",
+            "E": self.update_time,
+            "s": self.symbol,
+            "c": self.client_order_id,
+            "S": self.side,
+            "o": self.order_type,
+            "f": self.time_in_force,
+            "q": fmt(self.orig_qty),
+            "p": fmt(self.price),
+            "P": fmt(self.stop_price),
+            "x": execution_type,
+            "X": self.status,
+            "r": "NONE",
+            "i": self.order_id,
+            "l": fmt(last_qty),
+            "z": fmt(self.executed_qty),
+            "L": fmt(last_price),
+            "n": "0.00000000",
+            "N": null,
+            "T": self.update_time,
+            "t": trade_id,
+            "O": self.time,
+            "Z": fmt(self.cumulative_quote_qty),
+        })
+    }
+}
+
+/// Orders, trades and balances of the single mock account.
+pub(crate) struct Book {
+    orders: Vec<MockOrder>,
+    trades: Vec<Value>,
+    /// `(free, locked)` per asset.
+    balances: BTreeMap<String, (f64, f64)>,
+}
+
+impl Book {
+    pub fn new(balances: &[(String, f64)]) -> Self {
+        Self {
+            orders: Vec::new(),
+            trades: Vec::new(),
+            balances: balances.iter().map(|(asset, free)| (asset.clone(), (*free, 0.0))).collect(),
+        }
+    }
+
+    pub fn orders_json(&self) -> Vec<Value> {
+        self.orders.iter().map(MockOrder::to_json).collect()
+    }
+
+    pub fn free(&self, asset: &str) -> f64 {
+        self.balances.get(asset).map_or(0.0, |b| b.0)
+    }
+
+    /// Credits `amount` of `asset`, negative amounts withdraw. Returns the `balanceUpdate` event.
+    pub fn deposit(&mut self, asset: &str, amount: f64) -> Value {
+        self.balance(asset).0 += amount;
+        let now = now_ms();
+        json!({ "e": "balanceUpdate", "E": now, "a": asset, "d": fmt(amount), "T": now })
+    }
+
+    fn balance(&mut self, asset: &str) -> &mut (f64, f64) {
+        self.balances.entry(asset.to_string()).or_default()
+    }
+
+    fn find(&self, params: &HashMap<String, String>) -> Option<usize> {
+        let order_id = params.get("orderId").and_then(|id| id.parse::<u64>().ok());
+        let client_id = params.get("origClientOrderId");
+        self.orders.iter().position(|o| {
+            Some(o.symbol.as_str()) == params.get("symbol").map(String::as_str)
+                && (Some(o.order_id) == order_id || Some(&o.client_order_id) == client_id)
+        })
+    }
+
+    /// Asset and amount an order reserves while it rests on the book.
+    fn reservation(order: &MockOrder, price: f64) -> (String, f64) {
+        let (base, quote) = split_symbol(&order.symbol).unwrap_or((&order.symbol, ""));
+        let open_qty = order.orig_qty - order.executed_qty;
+        if order.is_buy() {
+            (quote.to_string(), open_qty * price)
+        } else {
+            (base.to_string(), open_qty)
+        }
+    }
+
+    /// Executes `quantity` of order `index` at `price` and returns the user data stream events.
+    fn execute(&mut self, index: usize, quantity: f64, price: f64, from_locked: bool) -> Vec<Value> {
+        let order = self.orders[index].clone();
+        let (base, quote) = split_symbol(&order.symbol).unwrap_or((&order.symbol, ""));
+        let (base, quote) = (base.to_string(), quote.to_string());
+        let quantity = quantity.min(order.orig_qty - order.executed_qty);
+        let notional = quantity * price;
+
+        if order.is_buy() {
+            let reserved = if from_locked { quantity * order.reserve_price() } else { 0.0 };
+            let balance = self.balance(&quote);
+            balance.1 -= reserved;
+            balance.0 += reserved - notional;
+            self.balance(&base).0 += quantity;
+        } else {
+            let balance = self.balance(&base);
+            if from_locked {
+                balance.1 -= quantity;
+            } else {
+                balance.0 -= quantity;
+            }
+            self.balance(&quote).0 += notional;
+        }
+
+        let trade_id = self.trades.len() as i64 + 1;
+        let now = now_ms();
+        let order = &mut self.orders[index];
+        order.executed_qty += quantity;
+        order.cumulative_quote_qty += notional;
+        order.update_time = now;
+        order.status = if order.orig_qty - order.executed_qty > 1e-12 { "PARTIALLY_FILLED" } else { "FILLED" };
+        let order = order.clone();
+        self.trades.push(json!({
+            "symbol": order.symbol,
+            "id": trade_id,
+            "orderId": order.order_id,
+            "orderListId": -1,
+            "price": fmt(price),
+            "qty": fmt(quantity),
+            "quoteQty": fmt(notional),
+            "commission": "0.00000000",
+            "commissionAsset": base,
+            "time": now,
+            "isBuyer": order.is_buy(),
+            "isMaker": from_locked,
+            "isBestMatch": true,
+        }));
+        vec![order.execution_report("TRADE", quantity, price, trade_id), self.account_position(&[&base, &quote], now)]
+    }
+
+    /// Fills a resting order, see [`crate::MockExchange::fill_order`].
+    pub fn fill(&mut self, order_id: u64, quantity: f64, price: f64) -> Vec<Value> {
+        match self.orders.iter().position(|o| o.order_id == order_id && o.is_open()) {
+            Some(index) => self.execute(index, quantity, price, true),
+            None => Vec::new(),
+        }
+    }
+
+    fn account_position(&self, assets: &[&str], time: u64) -> Value {
+        let balances: Vec<Value> = assets
+            .iter()
+            .map(|asset| {
+                let (free, locked) = self.balances.get(*asset).copied().unwrap_or_default();
+                json!({ "a": asset, "f": fmt(free), "l": fmt(locked) })
+            })
+            .collect();
+        json!({ "e": "outboundAccountPosition", "E": time, "u": time, "B": balances })
+    }
+}
+
+fn signature(secret_key: &str, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    form_urlencoded::parse(query.as_bytes()).into_owned().collect()
+}
+
+fn check_api_key(shared: &Shared, headers: &HeaderMap) -> Result<(), ApiError> {
+    if headers.get("X-MBX-APIKEY").and_then(|v| v.to_str().ok()) != Some(shared.config.api_key.as_str()) {
+        return Err((StatusCode::UNAUTHORIZED, -2015, "Invalid API-key, IP, or permissions for action."));
+    }
+    Ok(())
+}
+
+/// Checks the API key, the signature and the timestamp like the exchange does and returns the parameters.
+fn verify(shared: &Shared, headers: &HeaderMap, query: Option<String>) -> Result<HashMap<String, String>, ApiError> {
+    check_api_key(shared, headers)?;
+    let query = query.unwrap_or_default();
+    let Some((payload, sig)) = query.rsplit_once("&signature=") else {
+        return Err((StatusCode::BAD_REQUEST, -1102, "Mandatory parameter 'signature' was not sent, was empty/null, or malformed."));
+    };
+    if signature(&shared.config.secret_key, payload) != sig {
+        return Err((StatusCode::BAD_REQUEST, -1022, "Signature for this request is not valid."));
+    }
+    let params = parse_query(payload);
+    let timestamp = params.get("timestamp").and_then(|t| t.parse::<i64>().ok());
+    let recv_window = params.get("recvWindow").and_then(|t| t.parse::<i64>().ok()).unwrap_or(5_000);
+    match timestamp {
+        None => Err((StatusCode::BAD_REQUEST, -1102, "Mandatory parameter 'timestamp' was not sent, was empty/null, or malformed.")),
+        Some(t) if (now_ms() as i64 - t).abs() > recv_window => {
+            Err((StatusCode::BAD_REQUEST, -1021, "Timestamp for this request is outside of the recvWindow."))
+        }
+        Some(_) => Ok(params),
+    }
+}
+
+fn parse_f64(params: &HashMap<String, String>, key: &str) -> Option<f64> {
+    params.get(key).and_then(|v| v.parse().ok())
+}
+
+pub(crate) async fn new_order(State(shared): State<Arc<Shared>>, headers: HeaderMap, RawQuery(query): RawQuery) -> Response {
+    let params = match verify(&shared, &headers, query) {
+        Ok(params) => params,
+        Err(err) => return reject(err),
+    };
+    let (Some(symbol), Some(side), Some(order_type)) = (params.get("symbol"), params.get("side"), params.get("type")) else {
+        return api_error(StatusCode::BAD_REQUEST, -1102, "Mandatory parameter was not sent, was empty/null, or malformed.");
+    };
+    let Some(market_price) = last_price(&shared, symbol) else {
+        return api_error(StatusCode::BAD_REQUEST, -1121, "Invalid symbol.");
+    };
+    let market = order_type == "MARKET";
+    let price = parse_f64(&params, "price").unwrap_or(0.0);
+    if !market && price <= 0.0 && order_type != "STOP_LOSS" && order_type != "TAKE_PROFIT" {
+        return api_error(StatusCode::BAD_REQUEST, -1102, "Mandatory parameter 'price' was not sent, was empty/null, or malformed.");
+    }
+    let quantity = match (parse_f64(&params, "quantity"), parse_f64(&params, "quoteOrderQty")) {
+        (Some(quantity), _) => quantity,
+        (None, Some(quote)) if market => quote / market_price,
+        _ => return api_error(StatusCode::BAD_REQUEST, -1102, "Mandatory parameter 'quantity' was not sent, was empty/null, or malformed."),
+    };
+
+    let mut book = shared.book.lock().unwrap();
+    if let Some(client_id) = params.get("newClientOrderId") {
+        if book.orders.iter().any(|o| &o.client_order_id == client_id && o.is_open()) {
+            return api_error(StatusCode::BAD_REQUEST, -2010, "Duplicate order sent.");
+        }
+    }
+    let now = now_ms();
+    let order_id = book.orders.len() as u64 + 1;
+    let order = MockOrder {
+        symbol: symbol.clone(),
+        order_id,
+        client_order_id: params.get("newClientOrderId").cloned().unwrap_or_else(|| format!("mock{order_id}")),
+        side: side.clone(),
+        order_type: order_type.clone(),
+        time_in_force: params.get("timeInForce").cloned().unwrap_or_else(|| "GTC".to_string()),
+        price,
+        stop_price: parse_f64(&params, "stopPrice").unwrap_or(0.0),
+        orig_qty: quantity,
+        executed_qty: 0.0,
+        cumulative_quote_qty: 0.0,
+        status: "NEW",
+        time: now,
+        update_time: now,
+    };
+
+    let fill_price = if market { market_price } else { order.reserve_price() };
+    let (asset, needed) = Book::reservation(&order, fill_price);
+    if book.free(&asset) + 1e-9 < needed {
+        return api_error(StatusCode::BAD_REQUEST, -2010, "Account has insufficient balance for requested action.");
+    }
+
+    book.orders.push(order);
+    let index = book.orders.len() - 1;
+    let mut events = vec![book.orders[index].execution_report("NEW", 0.0, 0.0, -1)];
+    if market {
+        events.extend(book.execute(index, quantity, market_price, false));
+    } else {
+        let balance = book.balance(&asset);
+        balance.0 -= needed;
+        balance.1 += needed;
+    }
+    let response = book.orders[index].to_json();
+    drop(book);
+
+    for event in &events {
+        shared.publish(event);
+    }
+    Json(response).into_response()
+}
+
+pub(crate) async fn query_order(State(shared): State<Arc<Shared>>, headers: HeaderMap, RawQuery(query): RawQuery) -> Response {
+    let params = match verify(&shared, &headers, query) {
+        Ok(params) => params,
+        Err(err) => return reject(err),
+    };
+    let book = shared.book.lock().unwrap();
+    match book.find(&params) {
+        Some(i) => Json(book.orders[i].to_json()).into_response(),
+        None => api_error(StatusCode::BAD_REQUEST, -2013, "Order does not exist."),
+    }
+}
+
+pub(crate) async fn cancel_order(State(shared): State<Arc<Shared>>, headers: HeaderMap, RawQuery(query): RawQuery) -> Response {
+    let params = match verify(&shared, &headers, query) {
+        Ok(params) => params,
+        Err(err) => return reject(err),
+    };
+    let mut book = shared.book.lock().unwrap();
+    let Some(index) = book.find(&params).filter(|i| book.orders[*i].is_open()) else {
+        return api_error(StatusCode::BAD_REQUEST, -2011, "Unknown order sent.");
+    };
+    let order = book.orders[index].clone();
+    let (asset, reserved) = Book::reservation(&order, order.reserve_price());
+    let balance = book.balance(&asset);
+    balance.0 += reserved;
+    balance.1 -= reserved;
+
+    let order = &mut book.orders[index];
+    order.status = "CANCELED";
+    order.update_time = now_ms();
+    let order = order.clone();
+    drop(book);
+
+    shared.publish(&order.execution_report("CANCELED", 0.0, 0.0, -1));
+    Json(order.to_json()).into_response()
+}
+
+pub(crate) async fn open_orders(State(shared): State<Arc<Shared>>, headers: HeaderMap, RawQuery(query): RawQuery) -> Response {
+    let params = match verify(&shared, &headers, query) {
+        Ok(params) => params,
+        Err(err) => return reject(err),
+    };
+    let book = shared.book.lock().unwrap();
+    let open: Vec<Value> = book.orders
+        .iter()
+        .filter(|o| o.is_open() && params.get("symbol").is_none_or(|s| *s == o.symbol))
+        .map(MockOrder::to_json)
+        .collect();
+    Json(open).into_response()
+}
+
+pub(crate) async fn account(State(shared): State<Arc<Shared>>, headers: HeaderMap, RawQuery(query): RawQuery) -> Response {
+    if let Err(err) = verify(&shared, &headers, query) {
+        return reject(err);
+    }
+    let book = shared.book.lock().unwrap();
+    let balances: Vec<Value> = book.balances
+        .iter()
+        .map(|(asset, (free, locked))| json!({ "asset": asset, "free": fmt(*free), "locked": fmt(*locked) }))
+        .collect();
+    Json(json!({
+        "makerCommission": 0,
+        "takerCommission": 0,
+        "canTrade": true,
+        "canWithdraw": true,
+        "canDeposit": true,
+        "updateTime": now_ms(),
+        "accountType": "SPOT",
+        "balances": balances,
+        "permissions": ["SPOT"],
+    }))
+    .into_response()
+}
+
+pub(crate) async fn my_trades(State(shared): State<Arc<Shared>>, headers: HeaderMap, RawQuery(query): RawQuery) -> Response {
+    let params = match verify(&shared, &headers, query) {
+        Ok(params) => params,
+        Err(err) => return reject(err),
+    };
+    let from_id = params.get("fromId").and_then(|id| id.parse::<u64>().ok()).unwrap_or(0);
+    let book = shared.book.lock().unwrap();
+    let trades: Vec<&Value> = book.trades
+        .iter()
+        .filter(|t| params.get("symbol").is_some_and(|s| t["symbol"] == s.as_str()))
+        .filter(|t| t["id"].as_u64().is_some_and(|id| id >= from_id))
+        .collect();
+    Json(trades).into_response()
+}
+
+pub(crate) async fn start_user_stream(State(shared): State<Arc<Shared>>, headers: HeaderMap) -> Response {
+    if let Err(err) = check_api_key(&shared, &headers) {
+        return reject(err);
+    }
+    let mut keys = shared.listen_keys.lock().unwrap();
+    let listen_key = format!("mockListenKey{:0>52}", keys.len() + 1);
+    keys.insert(listen_key.clone());
+    Json(json!({ "listenKey": listen_key })).into_response()
+}
+
+fn listen_key_request(shared: &Shared, headers: &HeaderMap, query: Option<String>, close: bool) -> Response {
+    if let Err(err) = check_api_key(shared, headers) {
+        return reject(err);
+    }
+    let params = parse_query(&query.unwrap_or_default());
+    let mut keys = shared.listen_keys.lock().unwrap();
+    let known = match params.get("listenKey") {
+        Some(key) if close => keys.remove(key),
+        Some(key) => keys.contains(key),
+        None => false,
+    };
+    if known {
+        Json(json!({})).into_response()
+    } else {
+        api_error(StatusCode::BAD_REQUEST, -1125, "This listenKey does not exist.")
+    }
+}
+
+pub(crate) async fn keepalive_user_stream(State(shared): State<Arc<Shared>>, headers: HeaderMap, RawQuery(query): RawQuery) -> Response {
+    listen_key_request(&shared, &headers, query, false)
+}
+
+pub(crate) async fn close_user_stream(State(shared): State<Arc<Shared>>, headers: HeaderMap, RawQuery(query): RawQuery) -> Response {
+    listen_key_request(&shared, &headers, query, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(side: &str, price: f64, quantity: f64) -> MockOrder {
+        MockOrder {
+            symbol: "BTCUSDT".to_string(),
+            order_id: 1,
+            client_order_id: "a".to_string(),
+            side: side.to_string(),
+            order_type: "LIMIT".to_string(),
+            time_in_force: "GTC".to_string(),
+            price,
+            stop_price: 0.0,
+            orig_qty: quantity,
+            executed_qty: 0.0,
+            cumulative_quote_qty: 0.0,
+            status: "NEW",
+            time: 0,
+            update_time: 0,
+        }
+    }
+
+    #[test]
+    fn partial_fills_release_the_reservation() {
+        let mut book = Book::new(&[("USDT".to_string(), 1_000.0)]);
+        book.orders.push(order("BUY", 100.0, 4.0));
+        *book.balance("USDT") = (600.0, 400.0);
+
+        let events = book.fill(1, 1.0, 90.0);
+        assert_eq!(events[0]["X"], "PARTIALLY_FILLED");
+        assert_eq!(book.balances["USDT"], (610.0, 300.0));
+        assert_eq!(book.free("BTC"), 1.0);
+
+        book.fill(1, 10.0, 100.0);
+        assert_eq!(book.orders[0].status, "FILLED");
+        assert_eq!(book.balances["USDT"], (610.0, 0.0));
+        assert_eq!(book.free("BTC"), 4.0);
+        assert!(book.fill(1, 1.0, 100.0).is_empty());
+    }
+}
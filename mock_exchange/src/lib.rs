@@ -0,0 +1,11 @@
+// IAMbot Mock Exchange
+// In-process stand-in for the Binance REST and WebSocket APIs, used by the integration tests
+
+#![crate_name = "mock_exchange"]
+
+mod account;
+mod market;
+mod server;
+mod stream;
+
+pub use server::{Faults, MockConfig, MockExchange};
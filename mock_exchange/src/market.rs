@@ -0,0 +1,129 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::{json, Value};
+
+use crate::account::api_error;
+use crate::server::Shared;
+
+const QUOTE_ASSETS: [&str; 5] = ["USDT", "USDC", "FDUSD", "BTC", "ETH"];
+
+pub(crate) fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Splits `BTCUSDT` into `("BTC", "USDT")`.
+pub(crate) fn split_symbol(symbol: &str) -> Option<(&str, &str)> {
+    QUOTE_ASSETS
+        .iter()
+        .find(|quote| symbol.len() > quote.len() && symbol.ends_with(*quote))
+        .map(|quote| symbol.split_at(symbol.len() - quote.len()))
+}
+
+/// Close of the most recent fixture candle of `symbol`, over all intervals.
+pub(crate) fn last_price(shared: &Shared, symbol: &str) -> Option<f64> {
+    shared.config.klines
+        .iter()
+        .filter(|((s, _), _)| s == symbol)
+        .filter_map(|(_, rows)| rows.last())
+        .max_by_key(|row| row[0].as_u64())
+        .and_then(|row| row[4].as_str())
+        .and_then(|close| close.parse().ok())
+}
+
+pub(crate) async fn ping() -> Json<Value> {
+    Json(json!({}))
+}
+
+pub(crate) async fn time() -> Json<Value> {
+    Json(json!({ "serverTime": now_ms() }))
+}
+
+pub(crate) async fn exchange_info(State(shared): State<Arc<Shared>>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let symbols: BTreeSet<&str> = shared.config.klines.keys().map(|(symbol, _)| symbol.as_str()).collect();
+    if let Some(symbol) = params.get("symbol") {
+        if !symbols.contains(symbol.as_str()) {
+            return api_error(StatusCode::BAD_REQUEST, -1121, "Invalid symbol.");
+        }
+    }
+    let symbols: Vec<Value> = symbols
+        .into_iter()
+        .filter(|s| params.get("symbol").is_none_or(|wanted| wanted == s))
+        .filter_map(|symbol| {
+            let (base, quote) = split_symbol(symbol)?;
+            Some(json!({
+                "symbol": symbol,
+                "status": "TRADING",
+                "baseAsset": base,
+                "baseAssetPrecision": 8,
+                "quoteAsset": quote,
+                "quoteAssetPrecision": 8,
+                "orderTypes": ["LIMIT", "LIMIT_MAKER", "MARKET", "STOP_LOSS", "STOP_LOSS_LIMIT", "TAKE_PROFIT", "TAKE_PROFIT_LIMIT"],
+                "isSpotTradingAllowed": true,
+                "filters": [
+                    { "filterType": "PRICE_FILTER", "minPrice": "0.01000000", "maxPrice": "1000000.00000000", "tickSize": "0.01000000" },
+                    { "filterType": "LOT_SIZE", "minQty": "0.00001000", "maxQty": "9000.00000000", "stepSize": "0.00001000" },
+                    { "filterType": "NOTIONAL", "minNotional": "5.00000000", "maxNotional": "9000000.00000000" },
+                ],
+            }))
+        })
+        .collect();
+    Json(json!({
+        "timezone": "UTC",
+        "serverTime": now_ms(),
+        "rateLimits": [
+            { "rateLimitType": "REQUEST_WEIGHT", "interval": "MINUTE", "intervalNum": 1, "limit": 6000 },
+            { "rateLimitType": "ORDERS", "interval": "SECOND", "intervalNum": 10, "limit": 100 },
+        ],
+        "symbols": symbols,
+    }))
+    .into_response()
+}
+
+pub(crate) async fn ticker_price(State(shared): State<Arc<Shared>>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let Some(symbol) = params.get("symbol") else {
+        return api_error(StatusCode::BAD_REQUEST, -1102, "Mandatory parameter 'symbol' was not sent.");
+    };
+    match last_price(&shared, symbol) {
+        Some(price) => Json(json!({ "symbol": symbol, "price": format!("{price:.8}") })).into_response(),
+        None => api_error(StatusCode::BAD_REQUEST, -1121, "Invalid symbol."),
+    }
+}
+
+pub(crate) async fn klines(State(shared): State<Arc<Shared>>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let (Some(symbol), Some(interval)) = (params.get("symbol"), params.get("interval")) else {
+        return api_error(StatusCode::BAD_REQUEST, -1102, "Mandatory parameter 'symbol' or 'interval' was not sent.");
+    };
+    let Some(rows) = shared.config.klines.get(&(symbol.clone(), interval.clone())) else {
+        return api_error(StatusCode::BAD_REQUEST, -1121, "Invalid symbol.");
+    };
+    let bound = |key: &str| params.get(key).and_then(|v| v.parse::<u64>().ok());
+    let start = bound("startTime").unwrap_or(0);
+    let end = bound("endTime").unwrap_or(u64::MAX);
+    let limit = bound("limit").unwrap_or(500).min(1000) as usize;
+
+    let rows: Vec<&Value> = rows
+        .iter()
+        .filter(|row| row[0].as_u64().is_some_and(|open_time| open_time >= start && open_time <= end))
+        .take(limit)
+        .collect();
+    Json(rows).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_split_on_the_quote_asset() {
+        assert_eq!(split_symbol("BTCUSDT"), Some(("BTC", "USDT")));
+        assert_eq!(split_symbol("ETHBTC"), Some(("ETH", "BTC")));
+        assert_eq!(split_symbol("USDT"), None);
+    }
+}
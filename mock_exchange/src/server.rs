@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::account::{self, Book};
+use crate::market::{self, now_ms};
+use crate::stream;
+
+/// Misbehaviour injected into every response, can be changed while the server runs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Faults {
+    /// Delay before each REST response and between stream messages.
+    pub latency: Duration,
+    /// Number of requests served before every further one is answered with `429 Too Many Requests`.
+    pub rate_limit: Option<usize>,
+    /// Cut successful REST bodies and stream messages in half.
+    pub malformed: bool,
+    /// Drop stream connections without a close frame after this many messages.
+    pub disconnect_after: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    pub api_key: String,
+    pub secret_key: String,
+    /// Starting free balances per asset.
+    pub balances: Vec<(String, f64)>,
+    /// Raw REST kline rows per `(symbol, interval)`. The default holds four made-up hourly
+    /// `BTCUSDT` rows in Binance's format, not recorded market data.
+    pub klines: BTreeMap<(String, String), Vec<Value>>,
+    pub faults: Faults,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        let fixture = |json: &str| serde_json::from_str(json).expect("kline fixtures are valid JSON");
+        let klines = BTreeMap::from([
+            (("BTCUSDT".to_string(), "1h".to_string()), fixture(include_str!("../fixtures/BTCUSDT-1h-sample.json"))),
+        ]);
+        Self {
+            api_key: "mock-key".to_string(),
+            secret_key: "mock-secret".to_string(),
+            balances: vec![("BTC".to_string(), 1.0), ("USDT".to_string(), 10_000.0)],
+            klines,
+            faults: Faults::default(),
+        }
+    }
+}
+
+pub(crate) struct Shared {
+    pub config: MockConfig,
+    pub faults: Mutex<Faults>,
+    pub requests: AtomicUsize,
+    pub book: Mutex<Book>,
+    pub listen_keys: Mutex<HashSet<String>>,
+    /// User data stream events, already serialized.
+    pub events: broadcast::Sender<String>,
+}
+
+impl Shared {
+    pub fn faults(&self) -> Faults {
+        self.faults.lock().unwrap().clone()
+    }
+
+    /// Sends a user data stream event to every connected listener.
+    pub fn publish(&self, event: &Value) {
+        // no receivers is fine, nobody listens
+        let _ = self.events.send(event.to_string());
+    }
+}
+
+/// Binance look-alike serving the REST and stream endpoints on a random local port.
+/// The server stops when this handle is dropped.
+pub struct MockExchange {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    server: JoinHandle<()>,
+}
+
+impl MockExchange {
+    pub async fn start(config: MockConfig) -> std::io::Result<Self> {
+        let shared = Arc::new(Shared {
+            faults: Mutex::new(config.faults.clone()),
+            requests: AtomicUsize::new(0),
+            book: Mutex::new(Book::new(&config.balances)),
+            listen_keys: Mutex::new(HashSet::new()),
+            events: broadcast::channel(256).0,
+            config,
+        });
+
+        let app = Router::new()
+            .route("/api/v3/ping", get(market::ping))
+            .route("/api/v3/time", get(market::time))
+            .route("/api/v3/exchangeInfo", get(market::exchange_info))
+            .route("/api/v3/klines", get(market::klines))
+            .route("/api/v3/ticker/price", get(market::ticker_price))
+            .route("/api/v3/order", get(account::query_order).post(account::new_order).delete(account::cancel_order))
+            .route("/api/v3/openOrders", get(account::open_orders))
+            .route("/api/v3/account", get(account::account))
+            .route("/api/v3/myTrades", get(account::my_trades))
+            .route(
+                "/api/v3/userDataStream",
+                post(account::start_user_stream).put(account::keepalive_user_stream).delete(account::close_user_stream),
+            )
+            .route("/ws/{stream}", get(stream::connect))
+            .layer(middleware::from_fn_with_state(shared.clone(), inject_faults))
+            .with_state(shared.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        debug!("Mock exchange listening on {addr}");
+        let server = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("Mock exchange stopped: {e}");
+            }
+        });
+        Ok(Self { addr, shared, server })
+    }
+
+    /// Base URL of the REST API.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Base URL of the raw streams, `<ws_url>/<stream name>` subscribes.
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}/ws", self.addr)
+    }
+
+    pub fn config(&self) -> &MockConfig {
+        &self.shared.config
+    }
+
+    pub fn set_faults(&self, faults: Faults) {
+        *self.shared.faults.lock().unwrap() = faults;
+    }
+
+    /// Requests received so far, including rate limited ones.
+    pub fn request_count(&self) -> usize {
+        self.shared.requests.load(Ordering::SeqCst)
+    }
+
+    /// Snapshot of every order in the REST representation.
+    pub fn orders(&self) -> Vec<Value> {
+        self.shared.book.lock().unwrap().orders_json()
+    }
+
+    /// Free balance of `asset`.
+    pub fn balance(&self, asset: &str) -> f64 {
+        self.shared.book.lock().unwrap().free(asset)
+    }
+
+    /// Fills `quantity` of a resting order at `price` as if a counterparty traded against it.
+    /// Returns `false` if the order does not exist or is no longer open.
+    pub fn fill_order(&self, order_id: u64, quantity: f64, price: f64) -> bool {
+        let events = self.shared.book.lock().unwrap().fill(order_id, quantity, price);
+        let filled = !events.is_empty();
+        for event in events {
+            self.shared.publish(&event);
+        }
+        filled
+    }
+
+    /// Credits `amount` of `asset` as if it was deposited, negative amounts withdraw.
+    pub fn deposit(&self, asset: &str, amount: f64) {
+        let event = self.shared.book.lock().unwrap().deposit(asset, amount);
+        self.shared.publish(&event);
+    }
+
+    /// Invalidates every listen key like the exchange does after 60 minutes without a keepalive.
+    pub fn expire_listen_keys(&self) {
+        for listen_key in self.shared.listen_keys.lock().unwrap().drain() {
+            self.shared.publish(&json!({ "e": "listenKeyExpired", "E": now_ms(), "listenKey": listen_key }));
+        }
+    }
+
+    /// Listen keys currently valid.
+    pub fn listen_keys(&self) -> usize {
+        self.shared.listen_keys.lock().unwrap().len()
+    }
+}
+
+impl Drop for MockExchange {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn inject_faults(State(shared): State<Arc<Shared>>, request: Request, next: Next) -> Response {
+    let faults = shared.faults();
+    let served = shared.requests.fetch_add(1, Ordering::SeqCst);
+    if !faults.latency.is_zero() {
+        tokio::time::sleep(faults.latency).await;
+    }
+    if faults.rate_limit.is_some_and(|limit| served >= limit) {
+        let mut response = account::api_error(StatusCode::TOO_MANY_REQUESTS, -1003, "Too many requests; current limit is exceeded.");
+        response.headers_mut().insert(header::RETRY_AFTER, 1.into());
+        return response;
+    }
+
+    let response = next.run(request).await;
+    if !faults.malformed || response.status() != StatusCode::OK {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => Response::from_parts(parts, Body::from(bytes.slice(..bytes.len() / 2))),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
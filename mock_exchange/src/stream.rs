@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use tracing::debug;
+
+use crate::server::{Faults, Shared};
+
+/// Handles `/ws/<listenKey>` and `/ws/<symbol>@kline_<interval>`.
+pub(crate) async fn connect(State(shared): State<Arc<Shared>>, Path(stream): Path<String>, ws: WebSocketUpgrade) -> Response {
+    if shared.listen_keys.lock().unwrap().contains(&stream) {
+        let events = shared.events.subscribe();
+        return ws.on_upgrade(move |socket| user_stream(shared, socket, events));
+    }
+
+    let Some((symbol, interval)) = stream.split_once("@kline_") else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let key = (symbol.to_uppercase(), interval.to_string());
+    let Some(rows) = shared.config.klines.get(&key).cloned() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let events: Vec<String> = rows.iter().map(|row| kline_event(&key.0, &key.1, row).to_string()).collect();
+    ws.on_upgrade(move |socket| kline_stream(shared, socket, events))
+}
+
+/// Replays the fixture candles as closed kline events, then closes the stream.
+async fn kline_stream(shared: Arc<Shared>, mut socket: WebSocket, events: Vec<String>) {
+    for (sent, event) in events.into_iter().enumerate() {
+        if !send(&shared.faults(), &mut socket, sent, event).await {
+            return;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+/// Forwards account events until either side goes away.
+async fn user_stream(shared: Arc<Shared>, mut socket: WebSocket, mut events: broadcast::Receiver<String>) {
+    let mut sent = 0;
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let Ok(event) = event else { return };
+                if !send(&shared.faults(), &mut socket, sent, event).await {
+                    return;
+                }
+                sent += 1;
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                _ => {}
+            },
+        }
+    }
+}
+
+/// Sends one message with the configured faults applied. `false` means the connection is gone.
+async fn send(faults: &Faults, socket: &mut WebSocket, sent: usize, mut event: String) -> bool {
+    if faults.disconnect_after.is_some_and(|limit| sent >= limit) {
+        debug!("Dropping stream after {sent} messages");
+        // dropping the socket closes the TCP connection without a close frame
+        return false;
+    }
+    if !faults.latency.is_zero() {
+        tokio::time::sleep(faults.latency).await;
+    }
+    if faults.malformed {
+        event.truncate(event.len() / 2);
+    }
+    socket.send(Message::Text(event.into())).await.is_ok()
+}
+
+fn kline_event(symbol: &str, interval: &str, row: &Value) -> Value {
+    let field = |i: usize| row[i].clone();
+    json!({
+        "e": "kline",
+        "E": row[6].as_u64().map_or(0, |close_time| close_time + 1),
+        "s": symbol,
+        "k": {
+            "t": field(0),
+            "T": field(6),
+            "s": symbol,
+            "i": interval,
+            "f": 0,
+            "L": 0,
+            "o": field(1),
+            "c": field(4),
+            "h": field(2),
+            "l": field(3),
+            "v": field(5),
+            "n": field(8),
+            "x": true,
+            "q": field(7),
+            "V": field(9),
+            "Q": field(10),
+            "B": "0",
+        },
+    })
+}
@@ -0,0 +1,210 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Method, Url};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use sha2::Sha256;
+use tracing::{debug, info};
+
+use crate::error::{ApiErrorCode, Error, Result};
+use crate::types::{AccountInfo, ApiErrorBody, Balance, ExchangeInfo, ListenKey, MyTrade, NewOrder, OrderId, OrderResponse, ServerTime, SymbolFilters, TickerPrice};
+
+pub const BINANCE_API: &str = "https://api.binance.com";
+
+/// Signed REST client for the Binance spot trading endpoints.
+///
+/// Every signed request carries a `timestamp` corrected by the offset measured in
+/// [`TradingClient::sync_time`], the `recvWindow` and an HMAC-SHA256 `signature` of the query string.
+pub struct TradingClient {
+    client: Client,
+    base_url: String,
+    api_key: String,
+    secret_key: String,
+    recv_window: u64,
+    time_offset: AtomicI64,
+}
+
+impl TradingClient {
+    pub fn new(api_key: &str, secret_key: &str) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: BINANCE_API.to_string(),
+            api_key: api_key.to_string(),
+            secret_key: secret_key.to_string(),
+            recv_window: 5_000,
+            time_offset: AtomicI64::new(0),
+        }
+    }
+
+    /// Points the client at another server, e.g. the testnet or a local mock.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_recv_window(mut self, recv_window: u64) -> Self {
+        self.recv_window = recv_window;
+        self
+    }
+
+    pub fn time_offset(&self) -> i64 {
+        self.time_offset.load(Ordering::Relaxed)
+    }
+
+    pub async fn server_time(&self) -> Result<i64> {
+        let time: ServerTime = self.send(Method::GET, "/api/v3/time", &[], false).await?;
+        Ok(time.server_time)
+    }
+
+    /// Measures the difference between the server clock and ours and applies it to signed requests.
+    pub async fn sync_time(&self) -> Result<i64> {
+        let before = now_ms();
+        let server_time = self.server_time().await?;
+        let after = now_ms();
+        let offset = server_time - (before + after) / 2;
+        info!("Server time offset: {offset}ms");
+        self.time_offset.store(offset, Ordering::Relaxed);
+        Ok(offset)
+    }
+
+    /// Tick size, lot size and minimum notional orders of `symbol` have to respect.
+    pub async fn symbol_filters(&self, symbol: &str) -> Result<SymbolFilters> {
+        let info: ExchangeInfo = self.send(Method::GET, "/api/v3/exchangeInfo", &[("symbol", symbol.to_string())], false).await?;
+        info.symbols
+            .iter()
+            .find(|s| s.symbol == symbol)
+            .map(|s| SymbolFilters::from_filters(&s.filters))
+            .ok_or_else(|| Error::InvalidRequest(format!("Unknown symbol {symbol}")))
+    }
+
+    /// Last traded price of `symbol`.
+    pub async fn price(&self, symbol: &str) -> Result<f64> {
+        let ticker: TickerPrice = self.send(Method::GET, "/api/v3/ticker/price", &[("symbol", symbol.to_string())], false).await?;
+        Ok(ticker.price)
+    }
+
+    pub async fn new_order(&self, order: &NewOrder) -> Result<OrderResponse> {
+        let mut params = order.to_params();
+        params.push(("newOrderRespType", "RESULT".to_string()));
+        self.send(Method::POST, "/api/v3/order", &params, true).await
+    }
+
+    pub async fn cancel_order(&self, symbol: &str, id: &OrderId) -> Result<OrderResponse> {
+        self.send(Method::DELETE, "/api/v3/order", &order_params(symbol, id), true).await
+    }
+
+    pub async fn query_order(&self, symbol: &str, id: &OrderId) -> Result<OrderResponse> {
+        self.send(Method::GET, "/api/v3/order", &order_params(symbol, id), true).await
+    }
+
+    /// Open orders of `symbol`, or of every symbol if `None`.
+    pub async fn open_orders(&self, symbol: Option<&str>) -> Result<Vec<OrderResponse>> {
+        let params: Vec<(&str, String)> = symbol.map(|s| ("symbol", s.to_string())).into_iter().collect();
+        self.send(Method::GET, "/api/v3/openOrders", &params, true).await
+    }
+
+    pub async fn account(&self) -> Result<AccountInfo> {
+        self.send(Method::GET, "/api/v3/account", &[], true).await
+    }
+
+    /// Non-zero balances of the account.
+    pub async fn balances(&self) -> Result<Vec<Balance>> {
+        Ok(self.account()
+            .await?
+            .balances
+            .into_iter()
+            .filter(|b| b.free > 0.0 || b.locked > 0.0)
+            .collect())
+    }
+
+    pub async fn my_trades(&self, symbol: &str, from_id: Option<u64>) -> Result<Vec<MyTrade>> {
+        let mut params = vec![("symbol", symbol.to_string())];
+        if let Some(from_id) = from_id {
+            params.push(("fromId", from_id.to_string()));
+        }
+        self.send(Method::GET, "/api/v3/myTrades", &params, true).await
+    }
+
+    /// Opens a user data stream and returns its listen key, valid for 60 minutes unless kept alive.
+    pub async fn start_user_stream(&self) -> Result<String> {
+        let key: ListenKey = self.send(Method::POST, "/api/v3/userDataStream", &[], false).await?;
+        Ok(key.listen_key)
+    }
+
+    pub async fn keepalive_user_stream(&self, listen_key: &str) -> Result<()> {
+        let _: IgnoredAny = self.send(Method::PUT, "/api/v3/userDataStream", &[("listenKey", listen_key.to_string())], false).await?;
+        Ok(())
+    }
+
+    pub async fn close_user_stream(&self, listen_key: &str) -> Result<()> {
+        let _: IgnoredAny = self.send(Method::DELETE, "/api/v3/userDataStream", &[("listenKey", listen_key.to_string())], false).await?;
+        Ok(())
+    }
+
+    async fn send<T: DeserializeOwned>(&self, method: Method, path: &str, params: &[(&str, String)], signed: bool) -> Result<T> {
+        let mut url = Url::parse_with_params(&format!("{}{path}", self.base_url), params)
+            .map_err(|e| Error::InvalidRequest(e.to_string()))?;
+        if signed {
+            let timestamp = now_ms() + self.time_offset();
+            url.query_pairs_mut()
+                .append_pair("recvWindow", &self.recv_window.to_string())
+                .append_pair("timestamp", &timestamp.to_string());
+            let signature = sign(&self.secret_key, url.query().unwrap_or_default());
+            url.query_pairs_mut().append_pair("signature", &signature);
+        }
+        self.execute(method, url).await
+    }
+
+    async fn execute<T: DeserializeOwned>(&self, method: Method, url: Url) -> Result<T> {
+        debug!("{method} {}", url.path());
+        let resp = self.client
+            .request(method, url)
+            .header("X-MBX-APIKEY", &self.api_key)
+            .send()
+            .await?;
+        let status = resp.status();
+        let body = resp.text().await?;
+        if !status.is_success() {
+            return Err(match serde_json::from_str::<ApiErrorBody>(&body) {
+                Ok(err) => Error::Api { status: status.as_u16(), code: err.code.into(), msg: err.msg },
+                Err(_) => Error::Api { status: status.as_u16(), code: ApiErrorCode::Unknown, msg: body },
+            });
+        }
+        Ok(serde_json::from_str(&body)?)
+    }
+}
+
+fn order_params(symbol: &str, id: &OrderId) -> Vec<(&'static str, String)> {
+    let id = match id {
+        OrderId::Exchange(id) => ("orderId", id.to_string()),
+        OrderId::Client(id) => ("origClientOrderId", id.clone()),
+    };
+    vec![("symbol", symbol.to_string()), id]
+}
+
+/// Hex encoded HMAC-SHA256 of `payload`.
+pub fn sign(secret_key: &str, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub(crate) fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_matches_binance_docs() {
+        // example from the Binance API documentation
+        let secret = "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+        let payload = "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000&timestamp=1499827319559";
+        assert_eq!(sign(secret, payload), "c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71");
+    }
+}
@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::paper::PaperConfig;
+use crate::risk::RiskConfig;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Simulated fills against live candles, see [`crate::PaperExchange`].
+    #[default]
+    Paper,
+    /// Real orders on the exchange.
+    Live,
+}
+
+/// What to trade and where, read from a TOML file so switching between paper and live is a config change:
+///
+/// ```toml
+/// mode = "paper"
+/// symbol = "BTCUSDT"
+/// interval = "1m"
+/// orders_db = "orders.sqlite"
+///
+/// [paper]
+/// fee_rate = 0.001
+/// state_path = "paper-account.json"
+///
+/// [risk]
+/// max_daily_loss = 200.0
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TraderConfig {
+    pub mode: Mode,
+    pub symbol: String,
+    pub interval: String,
+    /// SQLite file of the order manager, orders are only kept in memory without one.
+    pub orders_db: Option<PathBuf>,
+    pub paper: PaperConfig,
+    pub risk: RiskConfig,
+}
+
+impl Default for TraderConfig {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Paper,
+            symbol: "BTCUSDT".to_string(),
+            interval: "1m".to_string(),
+            orders_db: None,
+            paper: PaperConfig::default(),
+            risk: RiskConfig::default(),
+        }
+    }
+}
+
+impl TraderConfig {
+    pub fn from_toml_str(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let config = TraderConfig::from_toml_str(
+            r#"
+            mode = "live"
+            symbol = "ETHUSDT"
+
+            [paper]
+            fee_rate = 0.00075
+            balances = { USDT = 500.0 }
+
+            [risk]
+            max_open_orders = 3
+            max_position = { ETHUSDT = 2.5 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.mode, Mode::Live);
+        assert_eq!(config.symbol, "ETHUSDT");
+        assert_eq!(config.interval, "1m");
+        assert_eq!(config.paper.fee_rate, 0.00075);
+        assert_eq!(config.paper.balances["USDT"], 500.0);
+        assert_eq!(config.paper.state_path, None);
+        assert_eq!(config.risk.max_open_orders, Some(3));
+        assert_eq!(config.risk.max_position["ETHUSDT"], 2.5);
+        assert_eq!(config.risk.max_daily_loss, None);
+
+        assert!(TraderConfig::from_toml_str("mode = \"yolo\"").is_err());
+    }
+}
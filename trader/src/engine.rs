@@ -0,0 +1,420 @@
+use backtester::{Execution, Ledger};
+use data_downloader::Kline;
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, info, warn};
+
+use crate::client::now_ms;
+use crate::error::Result;
+use crate::exchange::Exchange;
+use crate::oms::OrderManager;
+use crate::risk::{Exposure, RiskManager};
+use crate::strategy::{Account, Strategy};
+use crate::types::{split_symbol, UserEvent};
+
+/// Feeds closed candles of one symbol to a strategy and routes its orders through the risk manager
+/// to an exchange, keeping track of them in the order manager.
+pub struct Trader<E, S> {
+    exchange: E,
+    strategy: S,
+    orders: OrderManager,
+    risk: RiskManager,
+    ledger: Ledger,
+    /// First trade id not booked in the ledger yet.
+    next_trade_id: Option<u64>,
+    user_events: Option<mpsc::Receiver<UserEvent>>,
+    ledger_updates: Option<watch::Sender<Ledger>>,
+    symbol: String,
+}
+
+impl<E: Exchange, S: Strategy> Trader<E, S> {
+    pub fn new(exchange: E, strategy: S, orders: OrderManager, symbol: &str) -> Self {
+        Self {
+            exchange,
+            strategy,
+            orders,
+            risk: RiskManager::default(),
+            ledger: Ledger::new(split_symbol(symbol).map_or("", |(_, quote)| quote)),
+            next_trade_id: None,
+            user_events: None,
+            ledger_updates: None,
+            symbol: symbol.to_string(),
+        }
+    }
+
+    /// Replaces the default risk manager, which only enforces the symbol filters of the exchange.
+    pub fn with_risk(mut self, risk: RiskManager) -> Self {
+        self.risk = risk;
+        self
+    }
+
+    /// Follows orders and balances through the user data stream, see [`crate::UserStream`],
+    /// instead of only polling the exchange once per candle.
+    pub fn with_user_events(mut self, events: mpsc::Receiver<UserEvent>) -> Self {
+        self.user_events = Some(events);
+        self
+    }
+
+    /// Shares a copy of the ledger after every candle and user event, e.g. with a control server
+    /// reporting positions while [`Trader::run`] holds the trader.
+    pub fn with_ledger_updates(mut self, updates: watch::Sender<Ledger>) -> Self {
+        self.ledger_updates = Some(updates);
+        self
+    }
+
+    pub fn exchange(&self) -> &E {
+        &self.exchange
+    }
+
+    pub fn strategy(&self) -> &S {
+        &self.strategy
+    }
+
+    pub fn orders(&self) -> &OrderManager {
+        &self.orders
+    }
+
+    pub fn risk(&self) -> &RiskManager {
+        &self.risk
+    }
+
+    pub fn risk_mut(&mut self) -> &mut RiskManager {
+        &mut self.risk
+    }
+
+    /// Positions, PnL and equity booked from the fills of this run.
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Halts trading and cancels every open order.
+    pub async fn kill_switch(&mut self, reason: &str) {
+        self.risk.halt(reason);
+        let open: Vec<String> = self.orders.open_orders(None).iter().map(|o| o.client_order_id().to_string()).collect();
+        for id in open {
+            if let Err(e) = self.orders.cancel(&self.exchange, &id).await {
+                warn!("Could not cancel {id}: {e}");
+            }
+        }
+    }
+
+    /// Processes one closed candle. Orders refused by the risk manager or rejected by the exchange are
+    /// logged and reported to nobody, the strategy sees them missing from the open orders on the next candle.
+    pub async fn on_candle(&mut self, candle: &Kline) -> Result<()> {
+        for update in self.exchange.on_candle(&self.symbol, candle).await? {
+            self.orders.apply_response(&update)?;
+            self.strategy.on_order_update(&update);
+        }
+
+        self.book_trades().await?;
+        self.mark_held().await;
+        let (base, quote) = split_symbol(&self.symbol).unwrap_or((&self.symbol, ""));
+        self.ledger.mark(base, candle.close);
+
+        let account = Account {
+            balances: self.exchange.balances().await?,
+            open_orders: self.exchange.open_orders(Some(&self.symbol)).await?,
+        };
+        let position = account.total(base);
+        let equity = position * candle.close + account.total(quote);
+        if self.risk.update_equity(equity, candle.close_time) {
+            self.kill_switch("daily loss limit").await;
+        }
+
+        for order in self.strategy.on_candle(candle, &account) {
+            let exposure = Exposure {
+                position,
+                open_orders: self.orders.open_orders(None).len(),
+                price: candle.close,
+            };
+            let order = match self.risk.check(order, &exposure, now_ms() as u64) {
+                Ok(order) => order,
+                Err(e) => {
+                    warn!("Order refused: {e}");
+                    continue;
+                }
+            };
+            match self.orders.submit(&self.exchange, order).await {
+                Ok(order) => debug!("Order {} is {:?}", order.client_order_id(), order.status),
+                Err(e) => warn!("Order failed: {e}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies one user data stream event to the order manager and the ledger.
+    pub async fn on_user_event(&mut self, event: UserEvent) -> Result<()> {
+        match event {
+            UserEvent::ExecutionReport(report) => {
+                let changed = self.orders.apply_report(&report)?;
+                if report.symbol != self.symbol {
+                    return Ok(());
+                }
+                if changed {
+                    self.strategy.on_order_update(&report.to_order_response());
+                }
+                let new_trade = self.next_trade_id.is_none_or(|next| report.trade_id >= next as i64);
+                if let Some(execution) = report.to_execution().filter(|_| new_trade) {
+                    self.price_fee(&execution).await;
+                    self.ledger.apply(&execution);
+                    self.next_trade_id = Some(report.trade_id as u64 + 1);
+                }
+            }
+            UserEvent::OutboundAccountPosition(position) => {
+                for balance in position.balances {
+                    self.ledger.sync_balance(&balance.asset, balance.free + balance.locked);
+                }
+            }
+            UserEvent::BalanceUpdate(update) => {
+                let balance = self.ledger.balance(&update.asset) + update.delta;
+                self.ledger.sync_balance(&update.asset, balance);
+            }
+            UserEvent::Connected => {
+                // catch up with whatever happened while the stream was down
+                self.orders.recover(&self.exchange).await?;
+                self.book_trades().await?;
+                for balance in self.exchange.balances().await? {
+                    self.ledger.sync_balance(&balance.asset, balance.free + balance.locked);
+                }
+            }
+            UserEvent::ListenKeyExpired { .. } | UserEvent::Unknown => {}
+        }
+        Ok(())
+    }
+
+    /// Books the fills since the last call in the ledger.
+    async fn book_trades(&mut self) -> Result<()> {
+        for trade in self.exchange.my_trades(&self.symbol, self.next_trade_id).await? {
+            let execution = trade.to_execution();
+            self.price_fee(&execution).await;
+            self.ledger.apply(&execution);
+            self.next_trade_id = Some(trade.id + 1);
+        }
+        Ok(())
+    }
+
+    /// Makes sure the fee of `execution` is valued, pricing its asset first if the ledger has no price for it.
+    /// Fees in the traded asset are valued at the fill price by the ledger itself.
+    async fn price_fee(&mut self, execution: &Execution) {
+        let asset = &execution.fee_asset;
+        if *asset == execution.asset || self.ledger.price(asset).is_some() {
+            return;
+        }
+        self.mark_asset(asset).await;
+        if self.ledger.price(asset).is_none() {
+            warn!("No price for {asset}, fees paid in it are booked at zero");
+        }
+    }
+
+    fn share_ledger(&self) {
+        if let Some(updates) = &self.ledger_updates {
+            updates.send_replace(self.ledger.clone());
+        }
+    }
+
+    /// Prices the other assets held, e.g. BNB paying the fees, through their own quote pair.
+    async fn mark_held(&mut self) {
+        let (base, quote) = split_symbol(&self.symbol).unwrap_or((&self.symbol, ""));
+        let held: Vec<String> = self.ledger
+            .balances()
+            .iter()
+            .filter(|(asset, amount)| **amount > 0.0 && *asset != base && *asset != quote)
+            .map(|(asset, _)| asset.clone())
+            .collect();
+        for asset in held {
+            self.mark_asset(&asset).await;
+        }
+    }
+
+    /// Marks `asset` at the last price of `<asset><quote>`, failures are logged and leave the old price.
+    async fn mark_asset(&mut self, asset: &str) {
+        let symbol = format!("{asset}{}", self.ledger.quote());
+        match self.exchange.price(&symbol).await {
+            Ok(Some(price)) => self.ledger.mark(asset, price),
+            Ok(None) => {}
+            Err(e) => warn!("Could not price {asset} through {symbol}: {e}"),
+        }
+    }
+
+    /// Trades until the sending side of `receiver` is dropped, e.g. when the kline stream ends.
+    /// User events are handled as they arrive, between candles.
+    /// Orders left open by an earlier run are reconciled with the exchange and the symbol filters loaded first.
+    /// Retryable errors (see [`crate::Error::is_retryable`]) are logged and the next candle or event tries again,
+    /// any other error stops trading.
+    pub async fn run(&mut self, mut receiver: mpsc::Receiver<Kline>) -> Result<()> {
+        self.orders.recover(&self.exchange).await?;
+        // the ledger starts from the current balances, earlier fills are already in them
+        for balance in self.exchange.balances().await? {
+            self.ledger.set_balance(&balance.asset, balance.free + balance.locked);
+        }
+        let booked = self.exchange.my_trades(&self.symbol, self.next_trade_id).await?;
+        if let Some(last) = booked.last() {
+            self.next_trade_id = Some(last.id + 1);
+        }
+        self.share_ledger();
+        if let Some(filters) = self.exchange.symbol_filters(&self.symbol).await? {
+            info!("Filters of {}: {filters:?}", self.symbol);
+            self.risk.set_filters(&self.symbol, filters);
+        }
+        let mut events = self.user_events.take();
+        // the catch-up after a reconnect failed and is repeated before the next candle
+        let mut resync = false;
+        loop {
+            let candle = tokio::select! {
+                candle = receiver.recv() => match candle {
+                    Some(candle) => candle,
+                    None => break,
+                },
+                event = next_event(&mut events) => {
+                    match event {
+                        Some(event) => {
+                            let reconnected = matches!(event, UserEvent::Connected);
+                            if !survive(self.on_user_event(event).await, "User event")? && reconnected {
+                                resync = true;
+                            }
+                            self.share_ledger();
+                        }
+                        None => {
+                            warn!("User data stream stopped, polling the exchange once per candle");
+                            events = None;
+                        }
+                    }
+                    continue;
+                }
+            };
+            if resync {
+                resync = !survive(self.on_user_event(UserEvent::Connected).await, "Catching up with the exchange")?;
+            }
+            let traded = survive(self.on_candle(&candle).await, &format!("Candle {}", candle.open_time))?;
+            self.share_ledger();
+            if !traded {
+                continue;
+            }
+            info!(
+                "Candle {} {} closed at {}, equity {:.2} {}, realized PnL {:.2}, unrealized PnL {:.2}",
+                self.symbol,
+                candle.open_time,
+                candle.close,
+                self.ledger.equity(),
+                self.ledger.quote(),
+                self.ledger.realized_pnl(),
+                self.ledger.unrealized_pnl(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// `Ok(false)` for errors a later attempt may not run into, e.g. rate limits and timeouts, after logging them.
+fn survive(result: Result<()>, what: &str) -> Result<bool> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) if e.is_retryable() => {
+            warn!("{what} failed, trying again later: {e}");
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Next user event, never resolves without a stream.
+async fn next_event(events: &mut Option<mpsc::Receiver<UserEvent>>) -> Option<UserEvent> {
+    match events {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paper::{PaperConfig, PaperExchange};
+    use crate::strategies::RsiThreshold;
+    use crate::risk::RiskConfig;
+    use crate::types::{NewOrder, OrderStatus, Side};
+    use backtester::strategies::RsiThresholdConfig;
+
+    fn candle(i: u64, close: f64) -> Kline {
+        Kline {
+            open_time: i * 60_000,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1.0,
+            close_time: i * 60_000 + 59_999,
+            quote_asset_volume: close,
+            trade_number: 1,
+            buy_base: 0.5,
+            buy_quote: close / 2.0,
+        }
+    }
+
+    #[tokio::test]
+    async fn paper_trades_rsi_crossings() {
+        // choppy warmup, a sell-off and a rally, like the backtester's test
+        let mut closes = vec![100.0, 101.0, 100.0, 101.0, 100.0];
+        closes.extend((1..=5).map(|i| 100.0 - i as f64 * 3.0));
+        closes.extend((1..=10).map(|i| 85.0 + i as f64 * 3.0));
+
+        let (sender, receiver) = mpsc::channel(64);
+        for (i, close) in closes.iter().enumerate() {
+            sender.send(candle(i as u64, *close)).await.unwrap();
+        }
+        drop(sender);
+
+        let paper = PaperExchange::new(PaperConfig::default()).unwrap();
+        let strategy = RsiThreshold::new("BTCUSDT", RsiThresholdConfig { period: 3, ..Default::default() });
+        let (updates, shared) = watch::channel(Ledger::default());
+        let mut trader = Trader::new(paper, strategy, OrderManager::in_memory().unwrap(), "BTCUSDT").with_ledger_updates(updates);
+        trader.run(receiver).await.unwrap();
+
+        let trades = trader.exchange().trades("BTCUSDT");
+        assert_eq!(trades.iter().map(|t| t.is_buyer).collect::<Vec<_>>(), vec![true, false]);
+        assert!(trades[1].price > trades[0].price);
+        assert!(trader.exchange().equity("USDT") > 10_000.0);
+        assert!(trader.exchange().open_orders(None).await.unwrap().is_empty());
+        let last = trader.exchange().query_order("BTCUSDT", &crate::OrderId::Exchange(2)).await.unwrap();
+        assert_eq!((last.side, last.status), (Side::Sell, OrderStatus::Filled));
+        // the order manager followed both orders to the end
+        assert!(trader.orders().open_orders(None).is_empty());
+        assert_eq!(trader.orders().get(&last.client_order_id).unwrap().unwrap().status, OrderStatus::Filled);
+        // the ledger booked the round trip like the paper account
+        let ledger = trader.ledger();
+        assert!((ledger.equity() - trader.exchange().equity("USDT")).abs() < 1e-6);
+        let gross = (trades[1].price - trades[0].price) * trades[1].qty;
+        assert!((ledger.realized_pnl() - gross).abs() < 1e-6);
+        assert!(ledger.fees() > 0.0);
+        assert_eq!(*shared.borrow(), *ledger);
+    }
+
+    /// Bids far below the market on every candle.
+    struct Bidder;
+
+    impl Strategy for Bidder {
+        fn on_candle(&mut self, candle: &Kline, _account: &Account) -> Vec<NewOrder> {
+            vec![NewOrder::limit("BTCUSDT", Side::Buy, 0.01, candle.close / 2.0)]
+        }
+    }
+
+    #[tokio::test]
+    async fn risk_limits_and_kill_switch() {
+        let paper = PaperExchange::new(PaperConfig::default()).unwrap();
+        let risk = RiskManager::new(RiskConfig { max_open_orders: Some(2), ..RiskConfig::default() });
+        let mut trader = Trader::new(paper, Bidder, OrderManager::in_memory().unwrap(), "BTCUSDT").with_risk(risk);
+        for i in 0..3 {
+            trader.on_candle(&candle(i, 1000.0)).await.unwrap();
+        }
+        assert_eq!(trader.exchange().open_orders(None).await.unwrap().len(), 2);
+
+        trader.kill_switch("test").await;
+        assert_eq!(trader.risk().halted(), Some("test"));
+        assert!(trader.exchange().open_orders(None).await.unwrap().is_empty());
+        assert!(trader.orders().open_orders(None).is_empty());
+        trader.on_candle(&candle(3, 1000.0)).await.unwrap();
+        assert!(trader.exchange().open_orders(None).await.unwrap().is_empty());
+
+        trader.risk_mut().resume();
+        trader.on_candle(&candle(4, 1000.0)).await.unwrap();
+        assert_eq!(trader.exchange().open_orders(None).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,143 @@
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Error payload returned by the exchange.
+    Api { status: u16, code: ApiErrorCode, msg: String },
+    Http(reqwest::Error),
+    // boxed, tungstenite's error is large
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+    Json(serde_json::Error),
+    Io(std::io::Error),
+    Database(rusqlite::Error),
+    InvalidRequest(String),
+    Config(String),
+    /// Order refused by the risk manager before reaching the exchange.
+    Risk(String),
+}
+
+/// Binance error codes the client reacts to, see
+/// https://developers.binance.com/docs/binance-spot-api-docs/errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiErrorCode {
+    Unknown,
+    Disconnected,
+    Unauthorized,
+    TooManyRequests,
+    TimestampOutsideRecvWindow,
+    InvalidSignature,
+    IllegalParameter,
+    MandatoryParamMissing,
+    InvalidSymbol,
+    FilterFailure,
+    NewOrderRejected,
+    CancelRejected,
+    NoSuchOrder,
+    InvalidListenKey,
+    BadApiKeyFormat,
+    RejectedApiKey,
+    Other(i64),
+}
+
+impl From<i64> for ApiErrorCode {
+    fn from(code: i64) -> Self {
+        match code {
+            -1000 => ApiErrorCode::Unknown,
+            -1001 => ApiErrorCode::Disconnected,
+            -1002 => ApiErrorCode::Unauthorized,
+            -1003 => ApiErrorCode::TooManyRequests,
+            -1021 => ApiErrorCode::TimestampOutsideRecvWindow,
+            -1022 => ApiErrorCode::InvalidSignature,
+            -1100 | -1101 | -1104 | -1106 | -1111 | -1130 => ApiErrorCode::IllegalParameter,
+            -1102 => ApiErrorCode::MandatoryParamMissing,
+            -1121 => ApiErrorCode::InvalidSymbol,
+            -1013 => ApiErrorCode::FilterFailure,
+            -2010 => ApiErrorCode::NewOrderRejected,
+            -2011 => ApiErrorCode::CancelRejected,
+            -2013 => ApiErrorCode::NoSuchOrder,
+            -1125 => ApiErrorCode::InvalidListenKey,
+            -2014 => ApiErrorCode::BadApiKeyFormat,
+            -2015 => ApiErrorCode::RejectedApiKey,
+            other => ApiErrorCode::Other(other),
+        }
+    }
+}
+
+impl Error {
+    /// Rejection in the shape the exchange sends it, used by the simulated venues.
+    pub(crate) fn rejected(code: i64, msg: &str) -> Self {
+        Error::Api { status: 400, code: code.into(), msg: msg.to_string() }
+    }
+
+    /// Whether repeating the same request later may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Api { status, code, .. } => {
+                *status >= 500
+                    || matches!(code, ApiErrorCode::TooManyRequests | ApiErrorCode::Disconnected | ApiErrorCode::TimestampOutsideRecvWindow)
+            }
+            Error::Http(e) => e.is_timeout() || e.is_connect(),
+            Error::WebSocket(_) => true,
+            _ => false,
+        }
+    }
+}
+
+// region:    - Froms
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(err))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Database(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+// endregion: - Froms
+
+// region:    - Error impl
+impl core::fmt::Display for Error {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for Error {}
+// endregion:  - Error impl
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_mapping() {
+        assert_eq!(ApiErrorCode::from(-2010), ApiErrorCode::NewOrderRejected);
+        assert_eq!(ApiErrorCode::from(-1021), ApiErrorCode::TimestampOutsideRecvWindow);
+        assert_eq!(ApiErrorCode::from(-9999), ApiErrorCode::Other(-9999));
+
+        let rate_limited = Error::Api { status: 429, code: ApiErrorCode::TooManyRequests, msg: String::new() };
+        assert!(rate_limited.is_retryable());
+        let rejected = Error::Api { status: 400, code: ApiErrorCode::NewOrderRejected, msg: String::new() };
+        assert!(!rejected.is_retryable());
+    }
+}
@@ -0,0 +1,75 @@
+use std::future::Future;
+
+use data_downloader::Kline;
+
+use crate::client::TradingClient;
+use crate::error::Result;
+use crate::types::{Balance, MyTrade, NewOrder, OrderId, OrderResponse, SymbolFilters};
+
+/// Venue orders are routed to, the real exchange or the paper simulation.
+/// Both speak the same request and response types, so strategies can not tell them apart.
+pub trait Exchange {
+    fn new_order(&self, order: &NewOrder) -> impl Future<Output = Result<OrderResponse>> + Send;
+
+    fn cancel_order(&self, symbol: &str, id: &OrderId) -> impl Future<Output = Result<OrderResponse>> + Send;
+
+    fn query_order(&self, symbol: &str, id: &OrderId) -> impl Future<Output = Result<OrderResponse>> + Send;
+
+    fn open_orders(&self, symbol: Option<&str>) -> impl Future<Output = Result<Vec<OrderResponse>>> + Send;
+
+    /// Non-zero balances of the account.
+    fn balances(&self) -> impl Future<Output = Result<Vec<Balance>>> + Send;
+
+    /// Fills of `symbol`, starting with the trade id `from_id`.
+    fn my_trades(&self, symbol: &str, from_id: Option<u64>) -> impl Future<Output = Result<Vec<MyTrade>>> + Send;
+
+    /// Trading rules of `symbol`, `None` where the venue does not enforce any.
+    fn symbol_filters(&self, _symbol: &str) -> impl Future<Output = Result<Option<SymbolFilters>>> + Send {
+        async { Ok(None) }
+    }
+
+    /// Last traded price of `symbol`, `None` where the venue only knows the symbols it trades.
+    fn price(&self, _symbol: &str) -> impl Future<Output = Result<Option<f64>>> + Send {
+        async { Ok(None) }
+    }
+
+    /// Lets simulated venues match resting orders against a closed candle.
+    /// Returns the orders that changed; the real exchange reports those through the user data stream.
+    fn on_candle(&self, _symbol: &str, _candle: &Kline) -> impl Future<Output = Result<Vec<OrderResponse>>> + Send {
+        async { Ok(Vec::new()) }
+    }
+}
+
+impl Exchange for TradingClient {
+    async fn new_order(&self, order: &NewOrder) -> Result<OrderResponse> {
+        TradingClient::new_order(self, order).await
+    }
+
+    async fn cancel_order(&self, symbol: &str, id: &OrderId) -> Result<OrderResponse> {
+        TradingClient::cancel_order(self, symbol, id).await
+    }
+
+    async fn query_order(&self, symbol: &str, id: &OrderId) -> Result<OrderResponse> {
+        TradingClient::query_order(self, symbol, id).await
+    }
+
+    async fn open_orders(&self, symbol: Option<&str>) -> Result<Vec<OrderResponse>> {
+        TradingClient::open_orders(self, symbol).await
+    }
+
+    async fn balances(&self) -> Result<Vec<Balance>> {
+        TradingClient::balances(self).await
+    }
+
+    async fn my_trades(&self, symbol: &str, from_id: Option<u64>) -> Result<Vec<MyTrade>> {
+        TradingClient::my_trades(self, symbol, from_id).await
+    }
+
+    async fn symbol_filters(&self, symbol: &str) -> Result<Option<SymbolFilters>> {
+        TradingClient::symbol_filters(self, symbol).await.map(Some)
+    }
+
+    async fn price(&self, symbol: &str) -> Result<Option<f64>> {
+        TradingClient::price(self, symbol).await.map(Some)
+    }
+}
@@ -0,0 +1,30 @@
+// IAMbot Trader
+// Places and manages orders on the exchange or on paper
+
+#![crate_name = "trader"]
+
+mod client;
+mod config;
+mod engine;
+mod error;
+mod exchange;
+mod oms;
+mod paper;
+mod risk;
+mod strategy;
+pub mod strategies;
+mod types;
+mod user_stream;
+
+pub use backtester::{sizing, Execution, Ledger, Position};
+pub use client::{sign, TradingClient, BINANCE_API};
+pub use config::{Mode, TraderConfig};
+pub use engine::Trader;
+pub use error::{ApiErrorCode, Error, Result};
+pub use exchange::Exchange;
+pub use oms::{ManagedOrder, OrderManager};
+pub use paper::{PaperConfig, PaperExchange};
+pub use risk::{Exposure, RiskConfig, RiskManager};
+pub use strategy::{Account, Strategy};
+pub use types::{split_symbol, AccountInfo, AccountPosition, Balance, BalanceUpdate, ExecutionReport, ExecutionType, MyTrade, NewOrder, OrderId, OrderResponse, OrderStatus, OrderType, Side, SymbolFilters, TimeInForce, UserEvent};
+pub use user_stream::{UserStream, BINANCE_WS_API};
@@ -0,0 +1,519 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use tracing::{debug, info, warn};
+
+use crate::client::now_ms;
+use crate::error::{ApiErrorCode, Error, Result};
+use crate::exchange::Exchange;
+use crate::types::{enum_from_str, enum_str, ExecutionReport, NewOrder, OrderId, OrderResponse, OrderStatus, OrderType, Side};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS orders (
+    client_order_id TEXT PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    order_type TEXT NOT NULL,
+    quantity REAL,
+    quote_quantity REAL,
+    price REAL,
+    stop_price REAL,
+    time_in_force TEXT,
+    exchange_id INTEGER,
+    status TEXT NOT NULL,
+    executed_qty REAL NOT NULL,
+    cumulative_quote_qty REAL NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS orders_status ON orders (status);
+";
+
+/// Statuses no order leaves, the rows left out when loading the open orders.
+const TERMINAL: [OrderStatus; 5] =
+    [OrderStatus::Filled, OrderStatus::Canceled, OrderStatus::Rejected, OrderStatus::Expired, OrderStatus::ExpiredInMatch];
+
+const COLUMNS: &str = "client_order_id, symbol, side, order_type, quantity, quote_quantity, price, stop_price, time_in_force, \
+                       exchange_id, status, executed_qty, cumulative_quote_qty, created_at, updated_at";
+
+/// An order as tracked by the [`OrderManager`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManagedOrder {
+    /// The request, always carrying its client order id.
+    pub request: NewOrder,
+    pub exchange_id: Option<u64>,
+    pub status: OrderStatus,
+    pub executed_qty: f64,
+    pub cumulative_quote_qty: f64,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl ManagedOrder {
+    pub fn client_order_id(&self) -> &str {
+        self.request.client_order_id.as_deref().unwrap_or_default()
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.request.symbol
+    }
+
+    /// Volume weighted fill price, `None` before the first fill.
+    pub fn average_price(&self) -> Option<f64> {
+        (self.executed_qty > 0.0).then(|| self.cumulative_quote_qty / self.executed_qty)
+    }
+
+    /// Order known from the exchange only, e.g. placed by hand.
+    fn adopt(response: &OrderResponse) -> Self {
+        Self {
+            request: NewOrder {
+                symbol: response.symbol.clone(),
+                side: response.side,
+                order_type: response.order_type,
+                quantity: Some(response.orig_qty),
+                quote_quantity: None,
+                price: (response.price > 0.0).then_some(response.price),
+                stop_price: response.stop_price.filter(|p| *p > 0.0),
+                time_in_force: response.time_in_force,
+                client_order_id: Some(response.client_order_id.clone()),
+            },
+            exchange_id: Some(response.order_id),
+            status: OrderStatus::PendingNew,
+            executed_qty: 0.0,
+            cumulative_quote_qty: 0.0,
+            created_at: response.last_update(),
+            updated_at: response.last_update(),
+        }
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let text = |i: usize| -> rusqlite::Result<String> { row.get(i) };
+        let parse_err = |i: usize, s: &str| rusqlite::Error::FromSqlConversionFailure(i, rusqlite::types::Type::Text, format!("unknown value {s}").into());
+        let side: String = text(2)?;
+        let order_type: String = text(3)?;
+        let status: String = text(10)?;
+        let time_in_force: Option<String> = row.get(8)?;
+        Ok(Self {
+            request: NewOrder {
+                symbol: text(1)?,
+                side: enum_from_str::<Side>(&side).ok_or_else(|| parse_err(2, &side))?,
+                order_type: enum_from_str::<OrderType>(&order_type).ok_or_else(|| parse_err(3, &order_type))?,
+                quantity: row.get(4)?,
+                quote_quantity: row.get(5)?,
+                price: row.get(6)?,
+                stop_price: row.get(7)?,
+                time_in_force: time_in_force.and_then(|t| enum_from_str(&t)),
+                client_order_id: Some(text(0)?),
+            },
+            exchange_id: row.get::<_, Option<i64>>(9)?.map(|id| id as u64),
+            status: enum_from_str(&status).ok_or_else(|| parse_err(10, &status))?,
+            executed_qty: row.get(11)?,
+            cumulative_quote_qty: row.get(12)?,
+            created_at: row.get::<_, i64>(13)? as u64,
+            updated_at: row.get::<_, i64>(14)? as u64,
+        })
+    }
+}
+
+/// Order management system: follows every order through its life cycle, see
+/// [`OrderStatus::can_become`], and keeps it in SQLite so open orders survive a crash.
+///
+/// Orders are identified by their client order id. Ids are assigned here when the strategy does
+/// not set one and re-sending an id that is already tracked never places a second order.
+pub struct OrderManager {
+    db: Connection,
+    /// Orders that are not in a terminal state yet.
+    open: BTreeMap<String, ManagedOrder>,
+    id_prefix: String,
+    next_id: u64,
+}
+
+impl OrderManager {
+    /// Opens or creates the order database at `path` and loads the open orders.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Order manager without persistence, for tests and backtests.
+    pub fn in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(db: Connection) -> Result<Self> {
+        db.execute_batch(SCHEMA)?;
+        let mut manager = Self {
+            db,
+            open: BTreeMap::new(),
+            // unique per process start, Binance allows up to 36 characters
+            id_prefix: format!("iam{}", now_ms()),
+            next_id: 1,
+        };
+        // everything not known to be done, a row that does not decode stops the start instead of an
+        // order going untracked
+        let terminal: Vec<String> = TERMINAL.iter().map(|status| format!("'{}'", enum_str(status))).collect();
+        let open = {
+            let mut stmt = manager.db.prepare(&format!("SELECT {COLUMNS} FROM orders WHERE status NOT IN ({})", terminal.join(", ")))?;
+            let rows = stmt.query_map([], ManagedOrder::from_row)?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        if !open.is_empty() {
+            info!("Loaded {} open orders", open.len());
+        }
+        manager.open = open.into_iter().map(|o| (o.client_order_id().to_string(), o)).collect();
+        Ok(manager)
+    }
+
+    /// Open orders, of `symbol` only if given.
+    pub fn open_orders(&self, symbol: Option<&str>) -> Vec<&ManagedOrder> {
+        self.open.values().filter(|o| symbol.is_none_or(|s| s == o.symbol())).collect()
+    }
+
+    /// Looks up any order ever tracked, open or not.
+    pub fn get(&self, client_order_id: &str) -> Result<Option<ManagedOrder>> {
+        if let Some(order) = self.open.get(client_order_id) {
+            return Ok(Some(order.clone()));
+        }
+        Ok(self.db
+            .query_row(&format!("SELECT {COLUMNS} FROM orders WHERE client_order_id = ?1"), [client_order_id], ManagedOrder::from_row)
+            .optional()?)
+    }
+
+    /// Starts tracking `order` as PENDING_NEW, assigning a client order id if it has none.
+    /// An order whose id is already tracked is returned as it is.
+    pub fn track(&mut self, mut order: NewOrder) -> Result<ManagedOrder> {
+        let id = match &order.client_order_id {
+            Some(id) => id.clone(),
+            None => {
+                let id = format!("{}-{}", self.id_prefix, self.next_id);
+                self.next_id += 1;
+                order.client_order_id = Some(id.clone());
+                id
+            }
+        };
+        if let Some(existing) = self.get(&id)? {
+            debug!("Order {id} is already tracked");
+            return Ok(existing);
+        }
+        let now = now_ms() as u64;
+        let managed = ManagedOrder {
+            request: order,
+            exchange_id: None,
+            status: OrderStatus::PendingNew,
+            executed_qty: 0.0,
+            cumulative_quote_qty: 0.0,
+            created_at: now,
+            updated_at: now,
+        };
+        self.insert(&managed)?;
+        self.open.insert(id, managed.clone());
+        Ok(managed)
+    }
+
+    /// Tracks and places `order`. If the outcome is unknown, e.g. the request timed out, the order
+    /// stays PENDING_NEW until [`OrderManager::recover`] finds out what happened.
+    pub async fn submit<E: Exchange>(&mut self, exchange: &E, order: NewOrder) -> Result<ManagedOrder> {
+        let managed = self.track(order)?;
+        let id = managed.client_order_id().to_string();
+        if managed.status != OrderStatus::PendingNew {
+            return Ok(managed);
+        }
+
+        match exchange.new_order(&managed.request).await {
+            Ok(response) => {
+                self.apply_response(&response)?;
+            }
+            // an earlier attempt with this id made it after all
+            Err(Error::Api { msg, .. }) if msg.starts_with("Duplicate order") => {
+                let response = exchange.query_order(managed.symbol(), &OrderId::Client(id.clone())).await?;
+                self.apply_response(&response)?;
+            }
+            Err(err @ Error::Api { status: 400..=499, .. }) => {
+                warn!("Order {id} rejected: {err}");
+                self.transition(&id, None, OrderStatus::Rejected, 0.0, 0.0, now_ms() as u64)?;
+                return Err(err);
+            }
+            Err(err) => return Err(err),
+        }
+        Ok(self.get(&id)?.unwrap_or(managed))
+    }
+
+    pub async fn cancel<E: Exchange>(&mut self, exchange: &E, client_order_id: &str) -> Result<ManagedOrder> {
+        let Some(order) = self.open.get(client_order_id) else {
+            return Err(Error::InvalidRequest(format!("order {client_order_id} is not open")));
+        };
+        let response = exchange.cancel_order(&order.request.symbol, &OrderId::Client(client_order_id.to_string())).await?;
+        self.apply_response(&response)?;
+        Ok(self.get(client_order_id)?.expect("order is tracked"))
+    }
+
+    /// Applies an order state returned by the REST API. Returns whether anything changed.
+    pub fn apply_response(&mut self, response: &OrderResponse) -> Result<bool> {
+        if self.get(&response.client_order_id)?.is_none() {
+            info!("Adopting unknown order {}", response.client_order_id);
+            let adopted = ManagedOrder::adopt(response);
+            self.insert(&adopted)?;
+            self.open.insert(response.client_order_id.clone(), adopted);
+        }
+        self.transition(
+            &response.client_order_id,
+            Some(response.order_id),
+            response.status,
+            response.executed_qty,
+            response.cumulative_quote_qty,
+            response.last_update(),
+        )
+    }
+
+    /// Applies an `executionReport` of the user data stream. Returns whether anything changed.
+    pub fn apply_report(&mut self, report: &ExecutionReport) -> Result<bool> {
+        let id = report.order_client_id().to_string();
+        if self.get(&id)?.is_none() {
+            info!("Adopting unknown order {id}");
+            let adopted = ManagedOrder::adopt(&OrderResponse {
+                symbol: report.symbol.clone(),
+                order_id: report.order_id,
+                client_order_id: id.clone(),
+                price: report.price,
+                orig_qty: report.quantity,
+                executed_qty: 0.0,
+                cumulative_quote_qty: 0.0,
+                status: OrderStatus::PendingNew,
+                time_in_force: None,
+                order_type: report.order_type,
+                side: report.side,
+                stop_price: None,
+                transact_time: Some(report.transaction_time),
+                update_time: None,
+            });
+            self.insert(&adopted)?;
+            self.open.insert(id.clone(), adopted);
+        }
+        self.transition(
+            &id,
+            Some(report.order_id),
+            report.status,
+            report.cumulative_quantity,
+            report.cumulative_quote_quantity,
+            report.transaction_time,
+        )
+    }
+
+    /// Asks the exchange about every open order and picks up open orders we do not know yet.
+    /// Meant to run on start, after a crash orders may have filled or been canceled meanwhile.
+    pub async fn recover<E: Exchange>(&mut self, exchange: &E) -> Result<()> {
+        let tracked: Vec<(String, String, OrderStatus)> = self.open
+            .values()
+            .map(|o| (o.client_order_id().to_string(), o.symbol().to_string(), o.status))
+            .collect();
+        for (id, symbol, status) in tracked {
+            match exchange.query_order(&symbol, &OrderId::Client(id.clone())).await {
+                Ok(response) => {
+                    self.apply_response(&response)?;
+                }
+                // the request never reached the exchange
+                Err(Error::Api { code: ApiErrorCode::NoSuchOrder, .. }) if status == OrderStatus::PendingNew => {
+                    warn!("Order {id} never reached the exchange");
+                    self.transition(&id, None, OrderStatus::Rejected, 0.0, 0.0, now_ms() as u64)?;
+                }
+                Err(e) => warn!("Could not recover order {id}: {e}"),
+            }
+        }
+        for response in exchange.open_orders(None).await? {
+            self.apply_response(&response)?;
+        }
+        info!("Recovered {} open orders", self.open.len());
+        Ok(())
+    }
+
+    /// Moves an order to `status` if the state machine allows it. Stale updates, e.g. a report
+    /// arriving after the REST response of a later state, are ignored.
+    fn transition(&mut self, id: &str, exchange_id: Option<u64>, status: OrderStatus, executed_qty: f64, cumulative_quote_qty: f64, time: u64) -> Result<bool> {
+        let Some(mut order) = self.get(id)? else {
+            return Ok(false);
+        };
+        let progressed = executed_qty > order.executed_qty;
+        if executed_qty < order.executed_qty
+            || (status == order.status && !progressed)
+            || (status != order.status && !order.status.can_become(status))
+        {
+            debug!("Ignoring stale update of {id}: {:?} -> {status:?}", order.status);
+            return Ok(false);
+        }
+
+        debug!("Order {id}: {:?} -> {status:?}, executed {executed_qty}", order.status);
+        order.exchange_id = exchange_id.or(order.exchange_id);
+        order.status = status;
+        order.executed_qty = executed_qty;
+        order.cumulative_quote_qty = cumulative_quote_qty;
+        order.updated_at = time.max(order.updated_at);
+        self.db.execute(
+            "UPDATE orders SET exchange_id = ?2, status = ?3, executed_qty = ?4, cumulative_quote_qty = ?5, updated_at = ?6
+             WHERE client_order_id = ?1",
+            params![
+                id,
+                order.exchange_id.map(|id| id as i64),
+                enum_str(&order.status),
+                order.executed_qty,
+                order.cumulative_quote_qty,
+                order.updated_at as i64
+            ],
+        )?;
+        if order.status.is_open() {
+            self.open.insert(id.to_string(), order);
+        } else {
+            self.open.remove(id);
+        }
+        Ok(true)
+    }
+
+    fn insert(&self, order: &ManagedOrder) -> Result<()> {
+        let r = &order.request;
+        self.db.execute(
+            &format!("INSERT INTO orders ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)"),
+            params![
+                order.client_order_id(),
+                r.symbol,
+                enum_str(&r.side),
+                enum_str(&r.order_type),
+                r.quantity,
+                r.quote_quantity,
+                r.price,
+                r.stop_price,
+                r.time_in_force.map(|t| enum_str(&t)),
+                order.exchange_id.map(|id| id as i64),
+                enum_str(&order.status),
+                order.executed_qty,
+                order.cumulative_quote_qty,
+                order.created_at as i64,
+                order.updated_at as i64
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paper::{PaperConfig, PaperExchange};
+    use data_downloader::Kline;
+
+    fn candle(time: u64, price: f64) -> Kline {
+        Kline {
+            open_time: time,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 1.0,
+            close_time: time + 59_999,
+            quote_asset_volume: price,
+            trade_number: 1,
+            buy_base: 0.5,
+            buy_quote: price / 2.0,
+        }
+    }
+
+    fn report(status: &str, cumulative: &str) -> ExecutionReport {
+        let json = format!(
+            r#"{{"e":"executionReport","E":2,"s":"BTCUSDT","c":"a","C":"","S":"BUY","o":"LIMIT","f":"GTC","q":"2","p":"100","P":"0","x":"TRADE","X":"{status}","r":"NONE","i":7,"l":"1","z":"{cumulative}","L":"100","n":"0","N":null,"T":2,"t":1,"Z":"{}"}}"#,
+            cumulative.parse::<f64>().unwrap() * 100.0
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn follows_the_state_machine() {
+        let mut oms = OrderManager::in_memory().unwrap();
+        oms.track(NewOrder::limit("BTCUSDT", Side::Buy, 2.0, 100.0).with_client_order_id("a")).unwrap();
+
+        assert!(oms.apply_report(&report("PARTIALLY_FILLED", "1")).unwrap());
+        // a replayed report changes nothing
+        assert!(!oms.apply_report(&report("PARTIALLY_FILLED", "1")).unwrap());
+        assert!(oms.apply_report(&report("FILLED", "2")).unwrap());
+        // nothing leaves a terminal state
+        assert!(!oms.apply_report(&report("CANCELED", "2")).unwrap());
+
+        let order = oms.get("a").unwrap().unwrap();
+        assert_eq!(order.status, OrderStatus::Filled);
+        assert_eq!(order.exchange_id, Some(7));
+        assert_eq!(order.average_price(), Some(100.0));
+        assert!(oms.open_orders(None).is_empty());
+    }
+
+    #[test]
+    fn assigns_ids_and_deduplicates() {
+        let mut oms = OrderManager::in_memory().unwrap();
+        let first = oms.track(NewOrder::market("BTCUSDT", Side::Buy, 1.0)).unwrap();
+        let second = oms.track(NewOrder::market("BTCUSDT", Side::Buy, 1.0)).unwrap();
+        assert_ne!(first.client_order_id(), second.client_order_id());
+        assert!(first.client_order_id().len() <= 36);
+
+        let again = oms.track(NewOrder::market("BTCUSDT", Side::Sell, 5.0).with_client_order_id(first.client_order_id())).unwrap();
+        assert_eq!(again, first);
+        assert_eq!(oms.open_orders(Some("BTCUSDT")).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn recovers_open_orders_after_a_crash() {
+        let path = std::env::temp_dir().join(format!("iambot-oms-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let paper = PaperExchange::new(PaperConfig::default()).unwrap();
+        paper.on_candle("BTCUSDT", &candle(0, 100.0)).await.unwrap();
+
+        {
+            let mut oms = OrderManager::open(&path).unwrap();
+            let limit = oms.submit(&paper, NewOrder::limit("BTCUSDT", Side::Buy, 1.0, 90.0)).await.unwrap();
+            assert_eq!(limit.status, OrderStatus::New);
+            let market = oms.submit(&paper, NewOrder::market("BTCUSDT", Side::Buy, 1.0)).await.unwrap();
+            // submitting the same id again does not place a second order
+            oms.submit(&paper, market.request.clone()).await.unwrap();
+            assert_eq!(paper.open_orders(None).await.unwrap().len(), 2);
+
+            let rejected = oms.submit(&paper, NewOrder::market("BTCUSDT", Side::Sell, 1.0)).await;
+            assert!(rejected.is_err());
+            // crash while the orders are open, the market order fills meanwhile
+        }
+        paper.on_candle("BTCUSDT", &candle(60_000, 95.0)).await.unwrap();
+
+        let mut oms = OrderManager::open(&path).unwrap();
+        assert_eq!(oms.open_orders(None).len(), 2);
+        oms.recover(&paper).await.unwrap();
+        let open = oms.open_orders(None);
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].request.price, Some(90.0));
+
+        let id = open[0].client_order_id().to_string();
+        let canceled = oms.cancel(&paper, &id).await.unwrap();
+        assert_eq!(canceled.status, OrderStatus::Canceled);
+        let statuses: Vec<OrderStatus> = OrderManager::open(&path)
+            .unwrap()
+            .db
+            .prepare("SELECT status FROM orders ORDER BY created_at")
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .map(|s| enum_from_str(&s.unwrap()).unwrap())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(statuses.len(), 3);
+        assert!(statuses.contains(&OrderStatus::Rejected));
+        assert!(statuses.contains(&OrderStatus::Filled));
+        assert!(statuses.contains(&OrderStatus::Canceled));
+    }
+
+    #[test]
+    fn refuses_orders_it_cannot_read() {
+        let path = std::env::temp_dir().join(format!("iambot-oms-unknown-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let mut oms = OrderManager::open(&path).unwrap();
+            oms.track(NewOrder::limit("BTCUSDT", Side::Buy, 1.0, 90.0).with_client_order_id("a")).unwrap();
+            oms.db.execute("UPDATE orders SET status = 'HALTED' WHERE client_order_id = 'a'", []).unwrap();
+        }
+        let reopened = OrderManager::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(reopened, Err(Error::Database(_))));
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use data_downloader::Kline;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::error::{Error, Result};
+use crate::exchange::Exchange;
+use crate::types::{split_symbol, Balance, MyTrade, NewOrder, OrderId, OrderResponse, OrderStatus, OrderType, Side, TimeInForce};
+
+// tolerance for float rounding when checking balances
+const EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaperConfig {
+    /// Commission as a fraction of the traded amount, charged in the received asset like Binance does.
+    pub fee_rate: f64,
+    /// Adverse price move applied to market and stop fills, as a fraction of the price.
+    pub slippage: f64,
+    /// Balances of a fresh account.
+    pub balances: BTreeMap<String, f64>,
+    /// Where the account is saved after every change and resumed from on start.
+    pub state_path: Option<PathBuf>,
+}
+
+impl Default for PaperConfig {
+    fn default() -> Self {
+        Self {
+            fee_rate: 0.001,
+            slippage: 0.0,
+            balances: BTreeMap::from([("USDT".to_string(), 10_000.0)]),
+            state_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PaperOrder {
+    order: OrderResponse,
+    /// Quote amount of market orders placed with `quoteOrderQty`.
+    quote_quantity: Option<f64>,
+    /// Asset and amount moved from free to locked while the order rests.
+    reserved: (String, f64),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PaperState {
+    /// `(free, locked)` per asset.
+    balances: BTreeMap<String, (f64, f64)>,
+    orders: Vec<PaperOrder>,
+    trades: Vec<MyTrade>,
+    /// Close and close time of the last candle seen per symbol.
+    last_candle: BTreeMap<String, (f64, u64)>,
+}
+
+/// Simulated account filling orders at the next candle, see [`Exchange::on_candle`].
+///
+/// Market orders fill at the next open, limit and stop orders once the candle trades through their
+/// price, gaps fill at the open. Fees are taken from the received asset.
+pub struct PaperExchange {
+    config: PaperConfig,
+    state: Mutex<PaperState>,
+}
+
+impl PaperExchange {
+    /// Resumes the account saved at `config.state_path`, or opens a fresh one.
+    pub fn new(config: PaperConfig) -> Result<Self> {
+        let state = match &config.state_path {
+            Some(path) if path.exists() => {
+                info!("Resuming paper account from {}", path.display());
+                serde_json::from_str(&std::fs::read_to_string(path)?)?
+            }
+            _ => PaperState {
+                balances: config.balances.iter().map(|(asset, free)| (asset.clone(), (*free, 0.0))).collect(),
+                ..PaperState::default()
+            },
+        };
+        Ok(Self { config, state: Mutex::new(state) })
+    }
+
+    pub fn config(&self) -> &PaperConfig {
+        &self.config
+    }
+
+    /// `(free, locked)` amount of `asset`.
+    pub fn balance(&self, asset: &str) -> (f64, f64) {
+        self.state.lock().unwrap().balances.get(asset).copied().unwrap_or_default()
+    }
+
+    pub fn trades(&self, symbol: &str) -> Vec<MyTrade> {
+        self.state.lock().unwrap().trades.iter().filter(|t| t.symbol == symbol).cloned().collect()
+    }
+
+    /// Every asset valued in `quote` at the last seen closes, assets without a price are skipped.
+    pub fn equity(&self, quote: &str) -> f64 {
+        let state = self.state.lock().unwrap();
+        state.balances
+            .iter()
+            .map(|(asset, (free, locked))| {
+                let amount = free + locked;
+                if asset == quote {
+                    return amount;
+                }
+                state.last_candle.get(&format!("{asset}{quote}")).map_or(0.0, |(close, _)| amount * close)
+            })
+            .sum()
+    }
+
+    fn place(&self, order: &NewOrder) -> Result<OrderResponse> {
+        let Some((base, quote)) = split_symbol(&order.symbol) else {
+            return Err(Error::rejected(-1121, "Invalid symbol."));
+        };
+        let quantity = order.quantity.unwrap_or_default();
+        if quantity <= 0.0 && order.quote_quantity.is_none_or(|q| q <= 0.0) {
+            return Err(Error::InvalidRequest("order quantity must be positive".to_string()));
+        }
+        let price = order.price.unwrap_or_default();
+        let stop_price = order.stop_price.unwrap_or_default();
+        if matches!(order.order_type, OrderType::Limit | OrderType::LimitMaker | OrderType::StopLossLimit | OrderType::TakeProfitLimit)
+            && price <= 0.0
+        {
+            return Err(Error::InvalidRequest("limit orders need a price".to_string()));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(id) = &order.client_order_id {
+            if state.orders.iter().any(|o| &o.order.client_order_id == id && o.order.status.is_open()) {
+                return Err(Error::rejected(-2010, "Duplicate order sent."));
+            }
+        }
+        let reserved = match order.side {
+            Side::Buy => {
+                let estimate = match (order.quote_quantity, price.max(stop_price)) {
+                    (Some(quote_quantity), _) => quote_quantity,
+                    (None, p) if p > 0.0 => quantity * p,
+                    (None, _) => {
+                        let Some((close, _)) = state.last_candle.get(&order.symbol) else {
+                            return Err(Error::InvalidRequest(format!("no price for {} yet", order.symbol)));
+                        };
+                        quantity * close * (1.0 + self.config.slippage)
+                    }
+                };
+                (quote.to_string(), estimate)
+            }
+            Side::Sell => match order.quote_quantity {
+                Some(quote_quantity) => {
+                    let Some((close, _)) = state.last_candle.get(&order.symbol) else {
+                        return Err(Error::InvalidRequest(format!("no price for {} yet", order.symbol)));
+                    };
+                    (base.to_string(), quote_quantity / close)
+                }
+                None => (base.to_string(), quantity),
+            },
+        };
+        let balance = state.balances.entry(reserved.0.clone()).or_default();
+        if balance.0 + EPSILON < reserved.1 {
+            return Err(Error::rejected(-2010, "Account has insufficient balance for requested action."));
+        }
+        balance.0 -= reserved.1;
+        balance.1 += reserved.1;
+
+        let order_id = state.orders.len() as u64 + 1;
+        let time = state.last_candle.get(&order.symbol).map_or(0, |(_, time)| *time);
+        let response = OrderResponse {
+            symbol: order.symbol.clone(),
+            order_id,
+            client_order_id: order.client_order_id.clone().unwrap_or_else(|| format!("paper{order_id}")),
+            price,
+            orig_qty: quantity,
+            executed_qty: 0.0,
+            cumulative_quote_qty: 0.0,
+            status: OrderStatus::New,
+            time_in_force: order.time_in_force.or(Some(TimeInForce::GoodTillCanceled)),
+            order_type: order.order_type,
+            side: order.side,
+            stop_price: order.stop_price,
+            transact_time: Some(time),
+            update_time: Some(time),
+        };
+        debug!("Paper order {order_id} placed: {:?} {:?} {quantity}", order.side, order.order_type);
+        state.orders.push(PaperOrder { order: response.clone(), quote_quantity: order.quote_quantity, reserved });
+        Ok(response)
+    }
+
+    /// Matches the open orders of `symbol` against `candle` and returns the ones that changed.
+    fn match_orders(&self, symbol: &str, candle: &Kline) -> Vec<OrderResponse> {
+        let mut state = self.state.lock().unwrap();
+        let Some((base, quote)) = split_symbol(symbol) else {
+            return Vec::new();
+        };
+        let (base, quote) = (base.to_string(), quote.to_string());
+        let mut changed = Vec::new();
+
+        for i in 0..state.orders.len() {
+            let paper = &state.orders[i];
+            if paper.order.symbol != symbol || !paper.order.status.is_open() {
+                continue;
+            }
+            let Some(price) = fill_price(&paper.order, candle, self.config.slippage) else {
+                continue;
+            };
+            let paper = paper.clone();
+            let quantity = match paper.quote_quantity {
+                Some(quote_quantity) => quote_quantity / price,
+                None => paper.order.orig_qty,
+            };
+            let notional = quantity * price;
+
+            // release the reservation, then settle at the actual price
+            let (asset, amount) = &paper.reserved;
+            let balance = state.balances.entry(asset.clone()).or_default();
+            balance.0 += amount;
+            balance.1 -= amount;
+            let (pay_asset, pay, receive_asset, receive) = match paper.order.side {
+                Side::Buy => (&quote, notional, &base, quantity),
+                Side::Sell => (&base, quantity, &quote, notional),
+            };
+            let free = state.balances.get(pay_asset).map_or(0.0, |b| b.0);
+            let order = &mut state.orders[i].order;
+            order.update_time = Some(candle.open_time);
+            if free + EPSILON < pay {
+                warn!("Paper order {} expired: {pay} {pay_asset} needed, {free} available", order.order_id);
+                order.status = OrderStatus::Expired;
+                changed.push(order.clone());
+                continue;
+            }
+            order.status = OrderStatus::Filled;
+            order.executed_qty = quantity;
+            order.cumulative_quote_qty = notional;
+            if order.orig_qty == 0.0 {
+                order.orig_qty = quantity;
+            }
+            let order = order.clone();
+
+            let commission = receive * self.config.fee_rate;
+            state.balances.entry(pay_asset.clone()).or_default().0 -= pay;
+            state.balances.entry(receive_asset.clone()).or_default().0 += receive - commission;
+            let id = state.trades.len() as u64 + 1;
+            state.trades.push(MyTrade {
+                symbol: symbol.to_string(),
+                id,
+                order_id: order.order_id,
+                price,
+                qty: quantity,
+                quote_qty: notional,
+                commission,
+                commission_asset: receive_asset.clone(),
+                time: candle.open_time,
+                is_buyer: order.side == Side::Buy,
+                is_maker: matches!(order.order_type, OrderType::Limit | OrderType::LimitMaker),
+            });
+            info!("Paper order {} filled: {:?} {quantity} {symbol} at {price}", order.order_id, order.side);
+            changed.push(order);
+        }
+
+        state.last_candle.insert(symbol.to_string(), (candle.close, candle.close_time));
+        changed
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.config.state_path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(&*self.state.lock().unwrap())?;
+        write_atomic(path, &json)
+    }
+
+    fn find(&self, symbol: &str, id: &OrderId) -> Option<usize> {
+        self.state.lock().unwrap().orders.iter().position(|o| {
+            o.order.symbol == symbol
+                && match id {
+                    OrderId::Exchange(id) => o.order.order_id == *id,
+                    OrderId::Client(id) => &o.order.client_order_id == id,
+                }
+        })
+    }
+}
+
+impl Exchange for PaperExchange {
+    async fn new_order(&self, order: &NewOrder) -> Result<OrderResponse> {
+        let response = self.place(order)?;
+        self.save()?;
+        Ok(response)
+    }
+
+    async fn cancel_order(&self, symbol: &str, id: &OrderId) -> Result<OrderResponse> {
+        let index = self.find(symbol, id).ok_or_else(|| Error::rejected(-2011, "Unknown order sent."))?;
+        let canceled = {
+            let mut state = self.state.lock().unwrap();
+            let paper = state.orders[index].clone();
+            if !paper.order.status.is_open() {
+                return Err(Error::rejected(-2011, "Unknown order sent."));
+            }
+            let balance = state.balances.entry(paper.reserved.0.clone()).or_default();
+            balance.0 += paper.reserved.1;
+            balance.1 -= paper.reserved.1;
+            let order = &mut state.orders[index].order;
+            order.status = OrderStatus::Canceled;
+            order.clone()
+        };
+        self.save()?;
+        Ok(canceled)
+    }
+
+    async fn query_order(&self, symbol: &str, id: &OrderId) -> Result<OrderResponse> {
+        let index = self.find(symbol, id).ok_or_else(|| Error::rejected(-2013, "Order does not exist."))?;
+        Ok(self.state.lock().unwrap().orders[index].order.clone())
+    }
+
+    async fn open_orders(&self, symbol: Option<&str>) -> Result<Vec<OrderResponse>> {
+        Ok(self.state.lock().unwrap().orders
+            .iter()
+            .map(|o| &o.order)
+            .filter(|o| o.status.is_open() && symbol.is_none_or(|s| s == o.symbol))
+            .cloned()
+            .collect())
+    }
+
+    async fn balances(&self) -> Result<Vec<Balance>> {
+        Ok(self.state.lock().unwrap().balances
+            .iter()
+            .filter(|(_, (free, locked))| *free > 0.0 || *locked > 0.0)
+            .map(|(asset, (free, locked))| Balance { asset: asset.clone(), free: *free, locked: *locked })
+            .collect())
+    }
+
+    async fn my_trades(&self, symbol: &str, from_id: Option<u64>) -> Result<Vec<MyTrade>> {
+        let mut trades = self.trades(symbol);
+        trades.retain(|t| from_id.is_none_or(|from| t.id >= from));
+        Ok(trades)
+    }
+
+    async fn on_candle(&self, symbol: &str, candle: &Kline) -> Result<Vec<OrderResponse>> {
+        let changed = self.match_orders(symbol, candle);
+        self.save()?;
+        Ok(changed)
+    }
+}
+
+/// Price `order` executes at during `candle`, `None` if it does not trigger.
+fn fill_price(order: &OrderResponse, candle: &Kline, slippage: f64) -> Option<f64> {
+    let stop = order.stop_price.unwrap_or_default();
+    let buy = order.side == Side::Buy;
+    let slipped = |price: f64| if buy { price * (1.0 + slippage) } else { price * (1.0 - slippage) };
+    match order.order_type {
+        OrderType::Market => Some(slipped(candle.open)),
+        OrderType::Limit | OrderType::LimitMaker if buy => (candle.low <= order.price).then(|| candle.open.min(order.price)),
+        OrderType::Limit | OrderType::LimitMaker => (candle.high >= order.price).then(|| candle.open.max(order.price)),
+        // buy stops trigger on the way up, take profits on the way down, and the other way round for sells
+        OrderType::StopLoss | OrderType::TakeProfit => {
+            let rising = buy == (order.order_type == OrderType::StopLoss);
+            if rising {
+                (candle.high >= stop).then(|| slipped(candle.open.max(stop)))
+            } else {
+                (candle.low <= stop).then(|| slipped(candle.open.min(stop)))
+            }
+        }
+        // stop-limit orders fill at their limit once triggered
+        OrderType::StopLossLimit | OrderType::TakeProfitLimit => {
+            let rising = buy == (order.order_type == OrderType::StopLossLimit);
+            let triggered = if rising { candle.high >= stop } else { candle.low <= stop };
+            let reachable = if buy { candle.low <= order.price } else { candle.high >= order.price };
+            (triggered && reachable).then_some(order.price)
+        }
+    }
+}
+
+/// Writes through a temporary file so a crash never leaves a truncated state file behind.
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(time: u64, open: f64, high: f64, low: f64, close: f64) -> Kline {
+        Kline {
+            open_time: time,
+            open,
+            high,
+            low,
+            close,
+            volume: 1.0,
+            close_time: time + 59_999,
+            quote_asset_volume: close,
+            trade_number: 1,
+            buy_base: 0.5,
+            buy_quote: close / 2.0,
+        }
+    }
+
+    fn exchange(state_path: Option<PathBuf>) -> PaperExchange {
+        PaperExchange::new(PaperConfig { fee_rate: 0.01, state_path, ..PaperConfig::default() }).unwrap()
+    }
+
+    #[tokio::test]
+    async fn market_orders_fill_at_the_next_open() {
+        let paper = exchange(None);
+        paper.on_candle("BTCUSDT", &candle(0, 100.0, 100.0, 100.0, 100.0)).await.unwrap();
+
+        let order = paper.new_order(&NewOrder::market("BTCUSDT", Side::Buy, 10.0)).await.unwrap();
+        assert_eq!(order.status, OrderStatus::New);
+        assert_eq!(paper.balance("USDT"), (9_000.0, 1_000.0));
+
+        let changed = paper.on_candle("BTCUSDT", &candle(60_000, 90.0, 95.0, 85.0, 92.0)).await.unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].status, OrderStatus::Filled);
+        assert_eq!(changed[0].cumulative_quote_qty, 900.0);
+        assert_eq!(paper.balance("USDT"), (9_100.0, 0.0));
+        // 1% commission taken from the bought BTC
+        assert_eq!(paper.balance("BTC"), (9.9, 0.0));
+        assert_eq!(paper.trades("BTCUSDT")[0].commission_asset, "BTC");
+        assert!((paper.equity("USDT") - (9_100.0 + 9.9 * 92.0)).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn limit_orders_rest_and_reserve_funds() {
+        let paper = exchange(None);
+        let order = paper.new_order(&NewOrder::limit("BTCUSDT", Side::Buy, 1.0, 95.0)).await.unwrap();
+        assert!(paper.on_candle("BTCUSDT", &candle(0, 100.0, 101.0, 96.0, 100.0)).await.unwrap().is_empty());
+        assert_eq!(paper.open_orders(Some("BTCUSDT")).await.unwrap().len(), 1);
+
+        // too little cash left for a second order
+        let err = paper.new_order(&NewOrder::limit("BTCUSDT", Side::Buy, 100.0, 99.5)).await.unwrap_err();
+        assert!(matches!(err, Error::Api { code: crate::ApiErrorCode::NewOrderRejected, .. }));
+
+        let canceled = paper.cancel_order("BTCUSDT", &OrderId::Exchange(order.order_id)).await.unwrap();
+        assert_eq!(canceled.status, OrderStatus::Canceled);
+        assert_eq!(paper.balance("USDT"), (10_000.0, 0.0));
+        assert!(paper.cancel_order("BTCUSDT", &OrderId::Exchange(order.order_id)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn state_survives_a_restart() {
+        let path = std::env::temp_dir().join(format!("iambot-paper-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let paper = exchange(Some(path.clone()));
+            paper.on_candle("BTCUSDT", &candle(0, 100.0, 100.0, 100.0, 100.0)).await.unwrap();
+            paper.new_order(&NewOrder::market_quote("BTCUSDT", Side::Buy, 500.0)).await.unwrap();
+            paper.on_candle("BTCUSDT", &candle(60_000, 100.0, 100.0, 100.0, 100.0)).await.unwrap();
+            paper.new_order(&NewOrder::limit("BTCUSDT", Side::Sell, 2.0, 120.0).with_client_order_id("tp")).await.unwrap();
+        }
+
+        let paper = exchange(Some(path.clone()));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(paper.balance("USDT"), (9_500.0, 0.0));
+        assert!((paper.balance("BTC").0 - 2.95).abs() < 1e-9);
+        assert_eq!(paper.balance("BTC").1, 2.0);
+        let open = paper.open_orders(None).await.unwrap();
+        assert_eq!(open[0].client_order_id, "tp");
+        assert_eq!(paper.trades("BTCUSDT").len(), 1);
+    }
+}
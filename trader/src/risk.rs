@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::error::{Error, Result};
+use crate::types::{NewOrder, Side, SymbolFilters};
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+const MINUTE_MS: u64 = 60 * 1000;
+
+/// Limits every order has to pass before it reaches the exchange, unset limits are not enforced.
+///
+/// ```toml
+/// [risk]
+/// max_order_notional = 1000.0
+/// max_daily_loss = 200.0
+/// max_open_orders = 4
+/// max_orders_per_minute = 10
+/// max_position = { BTCUSDT = 0.05 }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RiskConfig {
+    /// Largest position per symbol, in the base asset.
+    pub max_position: BTreeMap<String, f64>,
+    /// Largest order value, in the quote asset.
+    pub max_order_notional: Option<f64>,
+    /// Trading halts once equity fell this far below its value at the start of the UTC day.
+    pub max_daily_loss: Option<f64>,
+    pub max_open_orders: Option<usize>,
+    pub max_orders_per_minute: Option<usize>,
+}
+
+/// What the account holds when an order is checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Exposure {
+    /// Base asset held, free and locked.
+    pub position: f64,
+    pub open_orders: usize,
+    /// Last traded price, values market orders.
+    pub price: f64,
+}
+
+/// Pre-trade checks between the strategies and the exchange.
+///
+/// Orders are rounded to the symbol filters and refused with [`Error::Risk`] when they break a limit.
+/// Once halted, by the daily loss limit or by hand, every order is refused until [`RiskManager::resume`].
+#[derive(Debug, Clone, Default)]
+pub struct RiskManager {
+    config: RiskConfig,
+    filters: BTreeMap<String, SymbolFilters>,
+    sent: VecDeque<u64>,
+    day_start: Option<(u64, f64)>,
+    halted: Option<String>,
+}
+
+impl RiskManager {
+    pub fn new(config: RiskConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    pub fn with_filters(mut self, symbol: &str, filters: SymbolFilters) -> Self {
+        self.set_filters(symbol, filters);
+        self
+    }
+
+    pub fn set_filters(&mut self, symbol: &str, filters: SymbolFilters) {
+        self.filters.insert(symbol.to_string(), filters);
+    }
+
+    pub fn config(&self) -> &RiskConfig {
+        &self.config
+    }
+
+    /// Why trading is halted, `None` while it is not.
+    pub fn halted(&self) -> Option<&str> {
+        self.halted.as_deref()
+    }
+
+    pub fn halt(&mut self, reason: &str) {
+        warn!("Trading halted: {reason}");
+        self.halted = Some(reason.to_string());
+    }
+
+    pub fn resume(&mut self) {
+        info!("Trading resumed");
+        self.halted = None;
+    }
+
+    /// Tracks the equity of the day `time` falls on and halts trading once the daily loss limit is
+    /// exceeded. Returns whether this update tripped the limit.
+    pub fn update_equity(&mut self, equity: f64, time: u64) -> bool {
+        let day = time / DAY_MS;
+        let start = match self.day_start {
+            Some((start_day, start)) if start_day == day => start,
+            _ => {
+                self.day_start = Some((day, equity));
+                equity
+            }
+        };
+        let loss = start - equity;
+        match self.config.max_daily_loss {
+            Some(max) if loss > max && self.halted.is_none() => {
+                self.halt(&format!("daily loss of {loss:.2} exceeds {max}"));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Rounds `order` to the symbol filters and checks it against the limits. `now` is the wall clock
+    /// in milliseconds, accepted orders count towards the rate limit.
+    pub fn check(&mut self, mut order: NewOrder, exposure: &Exposure, now: u64) -> Result<NewOrder> {
+        if let Some(reason) = &self.halted {
+            return Err(Error::Risk(format!("trading halted: {reason}")));
+        }
+
+        if let Some(filters) = self.filters.get(&order.symbol) {
+            apply_filters(&mut order, filters, exposure.price)?;
+        }
+
+        let price = order.price.or(order.stop_price).unwrap_or(exposure.price);
+        let (quantity, notional) = match (order.quantity, order.quote_quantity) {
+            (Some(quantity), _) => (quantity, quantity * price),
+            (None, Some(quote)) if price > 0.0 => (quote / price, quote),
+            _ => (0.0, order.quote_quantity.unwrap_or_default()),
+        };
+        if let Some(max) = self.config.max_order_notional.filter(|max| notional > *max) {
+            return Err(Error::Risk(format!("notional {notional:.2} exceeds {max}")));
+        }
+        if let Some(max) = self.config.max_position.get(&order.symbol) {
+            if order.side == Side::Buy && exposure.position + quantity > *max {
+                return Err(Error::Risk(format!("position of {} would exceed {max}", exposure.position + quantity)));
+            }
+        }
+        if let Some(max) = self.config.max_open_orders.filter(|max| exposure.open_orders >= *max) {
+            return Err(Error::Risk(format!("{} orders are open, at most {max} allowed", exposure.open_orders)));
+        }
+        if let Some(max) = self.config.max_orders_per_minute {
+            while self.sent.front().is_some_and(|sent| *sent + MINUTE_MS <= now) {
+                self.sent.pop_front();
+            }
+            if self.sent.len() >= max {
+                return Err(Error::Risk(format!("more than {max} orders per minute")));
+            }
+            self.sent.push_back(now);
+        }
+        Ok(order)
+    }
+}
+
+/// Snaps prices to the tick size and quantities down to the step size, then enforces the lot size
+/// and the minimum notional the exchange would reject the order for.
+fn apply_filters(order: &mut NewOrder, filters: &SymbolFilters, market_price: f64) -> Result<()> {
+    if filters.tick_size > 0.0 {
+        order.price = order.price.map(|p| round_to_step(p, filters.tick_size, f64::round));
+        order.stop_price = order.stop_price.map(|p| round_to_step(p, filters.tick_size, f64::round));
+    }
+    if let Some(quantity) = order.quantity {
+        let quantity = if filters.step_size > 0.0 {
+            round_to_step(quantity, filters.step_size, f64::floor)
+        } else {
+            quantity
+        };
+        if quantity < filters.min_qty || quantity <= 0.0 {
+            return Err(Error::Risk(format!("quantity {quantity} is below the minimum of {}", filters.min_qty)));
+        }
+        if filters.max_qty > 0.0 && quantity > filters.max_qty {
+            return Err(Error::Risk(format!("quantity {quantity} exceeds the maximum of {}", filters.max_qty)));
+        }
+        order.quantity = Some(quantity);
+    }
+
+    let price = order.price.or(order.stop_price).unwrap_or(market_price);
+    let notional = order.quote_quantity.unwrap_or_else(|| order.quantity.unwrap_or_default() * price);
+    if notional < filters.min_notional {
+        return Err(Error::Risk(format!("notional {notional:.2} is below the minimum of {}", filters.min_notional)));
+    }
+    Ok(())
+}
+
+/// Rounds `value` to a multiple of `step` with `round`, dropping the float noise the division leaves
+/// so the number prints with the step's decimals.
+fn round_to_step(value: f64, step: f64, round: fn(f64) -> f64) -> f64 {
+    let decimals = (-step.log10()).ceil().max(0.0) as i32;
+    let scale = 10f64.powi(decimals);
+    ((round(value / step + 1e-9) * step) * scale).round() / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn btc_filters() -> SymbolFilters {
+        SymbolFilters { tick_size: 0.01, step_size: 0.00001, min_qty: 0.00001, max_qty: 9000.0, min_notional: 5.0 }
+    }
+
+    fn exposure() -> Exposure {
+        Exposure { position: 0.0, open_orders: 0, price: 50_000.0 }
+    }
+
+    #[test]
+    fn rounds_to_symbol_filters() {
+        let mut risk = RiskManager::default().with_filters("BTCUSDT", btc_filters());
+        let order = risk.check(NewOrder::limit("BTCUSDT", Side::Buy, 0.123456789, 49_999.987), &exposure(), 0).unwrap();
+        assert_eq!(order.quantity, Some(0.12345));
+        assert_eq!(order.price, Some(49_999.99));
+        assert_eq!(order.to_params()[4..6], [("quantity", "0.12345".to_string()), ("price", "49999.99".to_string())]);
+
+        // 0.00005 BTC are worth 2.5 USDT
+        let dust = risk.check(NewOrder::market("BTCUSDT", Side::Sell, 0.00005), &exposure(), 0);
+        assert!(matches!(dust, Err(Error::Risk(_))));
+        assert!(risk.check(NewOrder::market("BTCUSDT", Side::Sell, 0.000001), &exposure(), 0).is_err());
+        assert!(risk.check(NewOrder::market_quote("BTCUSDT", Side::Buy, 4.0), &exposure(), 0).is_err());
+        // symbols without filters pass as they are
+        assert!(risk.check(NewOrder::market("ETHUSDT", Side::Sell, 0.000001), &exposure(), 0).is_ok());
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let config = RiskConfig {
+            max_position: BTreeMap::from([("BTCUSDT".to_string(), 0.1)]),
+            max_order_notional: Some(4_000.0),
+            max_open_orders: Some(2),
+            max_orders_per_minute: Some(3),
+            ..RiskConfig::default()
+        };
+        let mut risk = RiskManager::new(config);
+        let small = NewOrder::market("BTCUSDT", Side::Buy, 0.01);
+
+        assert!(risk.check(NewOrder::market("BTCUSDT", Side::Buy, 0.1), &exposure(), 0).is_err());
+        assert!(risk.check(NewOrder::market_quote("BTCUSDT", Side::Buy, 5_000.0), &exposure(), 0).is_err());
+        let full = Exposure { position: 0.095, ..exposure() };
+        assert!(risk.check(small.clone(), &full, 0).is_err());
+        // selling reduces the position
+        assert!(risk.check(NewOrder::market("BTCUSDT", Side::Sell, 0.01), &full, 0).is_ok());
+        let busy = Exposure { open_orders: 2, ..exposure() };
+        assert!(risk.check(small.clone(), &busy, 0).is_err());
+
+        // one order was sent at 0, two more fit into that minute
+        assert!(risk.check(small.clone(), &exposure(), 10_000).is_ok());
+        assert!(risk.check(small.clone(), &exposure(), 20_000).is_ok());
+        assert!(risk.check(small.clone(), &exposure(), 30_000).is_err());
+        assert!(risk.check(small.clone(), &exposure(), 60_000).is_ok());
+    }
+
+    #[test]
+    fn daily_loss_halts_trading() {
+        let mut risk = RiskManager::new(RiskConfig { max_daily_loss: Some(100.0), ..RiskConfig::default() });
+        let order = NewOrder::market("BTCUSDT", Side::Buy, 0.01);
+
+        assert!(!risk.update_equity(10_000.0, 1_000));
+        assert!(!risk.update_equity(9_950.0, 2_000));
+        assert!(risk.check(order.clone(), &exposure(), 0).is_ok());
+        assert!(risk.update_equity(9_890.0, 3_000));
+        assert!(!risk.update_equity(9_800.0, 4_000));
+        assert!(risk.halted().unwrap().contains("daily loss"));
+        assert!(risk.check(order.clone(), &exposure(), 0).is_err());
+
+        // a new day starts from the current equity, but a halt stays until resumed
+        assert!(!risk.update_equity(9_800.0, DAY_MS + 1_000));
+        assert!(risk.check(order.clone(), &exposure(), 0).is_err());
+        risk.resume();
+        assert!(risk.check(order, &exposure(), 0).is_ok());
+    }
+}
@@ -0,0 +1,3 @@
+mod rsi_threshold;
+
+pub use rsi_threshold::RsiThreshold;
@@ -0,0 +1,56 @@
+use backtester::strategies::RsiThresholdConfig;
+use backtester::Rsi;
+use data_downloader::Kline;
+use tracing::debug;
+
+use crate::strategy::{Account, Strategy};
+use crate::types::{split_symbol, NewOrder, Side};
+
+// positions worth less than this are dust the exchange would not let us sell
+const MIN_NOTIONAL: f64 = 5.0;
+
+/// Live counterpart of the backtester's `RsiThreshold`: buys oversold and sells overbought RSI crossings.
+#[derive(Debug, Clone)]
+pub struct RsiThreshold {
+    symbol: String,
+    config: RsiThresholdConfig,
+    rsi: Rsi,
+    prev_rsi: Option<f64>,
+}
+
+impl RsiThreshold {
+    pub fn new(symbol: &str, config: RsiThresholdConfig) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            rsi: Rsi::new(config.period),
+            config,
+            prev_rsi: None,
+        }
+    }
+}
+
+impl Strategy for RsiThreshold {
+    fn on_candle(&mut self, candle: &Kline, account: &Account) -> Vec<NewOrder> {
+        let rsi = self.rsi.update(candle.close);
+        let prev = std::mem::replace(&mut self.prev_rsi, rsi);
+        let (Some(prev), Some(rsi), Some((base, quote))) = (prev, rsi, split_symbol(&self.symbol)) else {
+            return Vec::new();
+        };
+        if !account.open_orders.is_empty() {
+            return Vec::new();
+        }
+
+        let position = account.free(base);
+        let flat = position * candle.close < MIN_NOTIONAL;
+        if flat && prev >= self.config.buy_below && rsi < self.config.buy_below {
+            let spend = account.free(quote) * self.config.allocation;
+            debug!("RSI {rsi:.2} crossed below {}, buying for {spend} {quote}", self.config.buy_below);
+            vec![NewOrder::market_quote(&self.symbol, Side::Buy, spend)]
+        } else if !flat && prev <= self.config.sell_above && rsi > self.config.sell_above {
+            debug!("RSI {rsi:.2} crossed above {}, selling {position} {base}", self.config.sell_above);
+            vec![NewOrder::market(&self.symbol, Side::Sell, position)]
+        } else {
+            Vec::new()
+        }
+    }
+}
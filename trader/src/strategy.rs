@@ -0,0 +1,32 @@
+use data_downloader::Kline;
+
+use crate::types::{Balance, NewOrder, OrderResponse};
+
+/// Balances and open orders as they were when the candle closed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Account {
+    pub balances: Vec<Balance>,
+    pub open_orders: Vec<OrderResponse>,
+}
+
+impl Account {
+    pub fn free(&self, asset: &str) -> f64 {
+        self.balances.iter().find(|b| b.asset == asset).map_or(0.0, |b| b.free)
+    }
+
+    /// Free and locked balance of `asset`.
+    pub fn total(&self, asset: &str) -> f64 {
+        self.balances.iter().find(|b| b.asset == asset).map_or(0.0, |b| b.free + b.locked)
+    }
+}
+
+/// A strategy trading on closed candles, on paper or on the exchange.
+///
+/// The returned orders are placed after the candle closed, so a strategy never trades on prices it
+/// has not seen yet.
+pub trait Strategy {
+    /// Called once per closed candle, after the order updates of that candle were reported.
+    fn on_candle(&mut self, candle: &Kline, account: &Account) -> Vec<NewOrder>;
+
+    fn on_order_update(&mut self, _order: &OrderResponse) {}
+}
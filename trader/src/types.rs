@@ -0,0 +1,648 @@
+use backtester::Execution;
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderType {
+    Market,
+    Limit,
+    StopLoss,
+    StopLossLimit,
+    TakeProfit,
+    TakeProfitLimit,
+    LimitMaker,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TimeInForce {
+    #[serde(rename = "GTC")]
+    GoodTillCanceled,
+    #[serde(rename = "IOC")]
+    ImmediateOrCancel,
+    #[serde(rename = "FOK")]
+    FillOrKill,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderStatus {
+    New,
+    PendingNew,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    PendingCancel,
+    Rejected,
+    Expired,
+    ExpiredInMatch,
+}
+
+impl OrderStatus {
+    /// Whether the order can still trade.
+    pub fn is_open(self) -> bool {
+        matches!(self, OrderStatus::PendingNew | OrderStatus::New | OrderStatus::PartiallyFilled | OrderStatus::PendingCancel)
+    }
+
+    pub fn is_terminal(self) -> bool {
+        !self.is_open()
+    }
+
+    /// Whether an order may move from `self` to `next`:
+    /// NEW → PARTIALLY_FILLED → FILLED/CANCELED/REJECTED/EXPIRED, nothing leaves a terminal state.
+    pub fn can_become(self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        match self {
+            PendingNew => next != PendingNew,
+            New => !matches!(next, PendingNew | New),
+            PartiallyFilled => !matches!(next, PendingNew | New | Rejected),
+            PendingCancel => matches!(next, PartiallyFilled | Filled | Canceled | Expired | ExpiredInMatch),
+            Filled | Canceled | Rejected | Expired | ExpiredInMatch => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExecutionType {
+    New,
+    Canceled,
+    Replaced,
+    Rejected,
+    Trade,
+    Expired,
+    TradePrevention,
+}
+
+/// Identifies an existing order, either by the exchange id or by our client order id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderId {
+    Exchange(u64),
+    Client(String),
+}
+
+/// Parameters of `POST /api/v3/order`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewOrder {
+    pub symbol: String,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub quantity: Option<f64>,
+    /// Spend/receive this much of the quote asset instead of a base `quantity`, market orders only.
+    pub quote_quantity: Option<f64>,
+    pub price: Option<f64>,
+    pub stop_price: Option<f64>,
+    pub time_in_force: Option<TimeInForce>,
+    pub client_order_id: Option<String>,
+}
+
+impl NewOrder {
+    pub fn market(symbol: &str, side: Side, quantity: f64) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            side,
+            order_type: OrderType::Market,
+            quantity: Some(quantity),
+            quote_quantity: None,
+            price: None,
+            stop_price: None,
+            time_in_force: None,
+            client_order_id: None,
+        }
+    }
+
+    /// Market order spending (buy) or receiving (sell) `quote_quantity` of the quote asset.
+    pub fn market_quote(symbol: &str, side: Side, quote_quantity: f64) -> Self {
+        Self {
+            quantity: None,
+            quote_quantity: Some(quote_quantity),
+            ..Self::market(symbol, side, 0.0)
+        }
+    }
+
+    pub fn limit(symbol: &str, side: Side, quantity: f64, price: f64) -> Self {
+        Self {
+            order_type: OrderType::Limit,
+            price: Some(price),
+            time_in_force: Some(TimeInForce::GoodTillCanceled),
+            ..Self::market(symbol, side, quantity)
+        }
+    }
+
+    pub fn stop_loss(symbol: &str, side: Side, quantity: f64, stop_price: f64) -> Self {
+        Self {
+            order_type: OrderType::StopLoss,
+            stop_price: Some(stop_price),
+            ..Self::market(symbol, side, quantity)
+        }
+    }
+
+    pub fn with_client_order_id(mut self, id: &str) -> Self {
+        self.client_order_id = Some(id.to_string());
+        self
+    }
+
+    /// Request parameters in the form the REST API expects.
+    pub fn to_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("symbol", self.symbol.clone()),
+            ("side", enum_str(&self.side)),
+            ("type", enum_str(&self.order_type)),
+        ];
+        if let Some(tif) = &self.time_in_force {
+            params.push(("timeInForce", enum_str(tif)));
+        }
+        if let Some(quantity) = self.quantity {
+            params.push(("quantity", quantity.to_string()));
+        }
+        if let Some(quote_quantity) = self.quote_quantity {
+            params.push(("quoteOrderQty", quote_quantity.to_string()));
+        }
+        if let Some(price) = self.price {
+            params.push(("price", price.to_string()));
+        }
+        if let Some(stop_price) = self.stop_price {
+            params.push(("stopPrice", stop_price.to_string()));
+        }
+        if let Some(id) = &self.client_order_id {
+            params.push(("newClientOrderId", id.clone()));
+        }
+        params
+    }
+}
+
+/// Order as returned by the order endpoints.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderResponse {
+    pub symbol: String,
+    pub order_id: u64,
+    pub client_order_id: String,
+    #[serde(deserialize_with = "de_f64")]
+    pub price: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub orig_qty: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub executed_qty: f64,
+    #[serde(rename = "cummulativeQuoteQty", deserialize_with = "de_f64")]
+    pub cumulative_quote_qty: f64,
+    pub status: OrderStatus,
+    pub time_in_force: Option<TimeInForce>,
+    #[serde(rename = "type")]
+    pub order_type: OrderType,
+    pub side: Side,
+    #[serde(default, deserialize_with = "de_opt_f64")]
+    pub stop_price: Option<f64>,
+    /// Set on new and canceled orders.
+    #[serde(default)]
+    pub transact_time: Option<u64>,
+    /// Set on queried orders.
+    #[serde(default)]
+    pub update_time: Option<u64>,
+}
+
+impl OrderResponse {
+    pub fn last_update(&self) -> u64 {
+        self.update_time.or(self.transact_time).unwrap_or_default()
+    }
+}
+
+/// `executionReport` event of the user data stream, sent on every change of an order.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ExecutionReport {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "c")]
+    pub client_order_id: String,
+    /// Client id of the canceled order on cancel reports, `c` is then the id of the cancel request.
+    #[serde(rename = "C", default)]
+    pub orig_client_order_id: String,
+    #[serde(rename = "S")]
+    pub side: Side,
+    #[serde(rename = "o")]
+    pub order_type: OrderType,
+    #[serde(rename = "q", deserialize_with = "de_f64")]
+    pub quantity: f64,
+    #[serde(rename = "p", deserialize_with = "de_f64")]
+    pub price: f64,
+    #[serde(rename = "x")]
+    pub execution_type: ExecutionType,
+    #[serde(rename = "X")]
+    pub status: OrderStatus,
+    #[serde(rename = "r")]
+    pub reject_reason: String,
+    #[serde(rename = "i")]
+    pub order_id: u64,
+    #[serde(rename = "l", deserialize_with = "de_f64")]
+    pub last_quantity: f64,
+    #[serde(rename = "z", deserialize_with = "de_f64")]
+    pub cumulative_quantity: f64,
+    #[serde(rename = "L", deserialize_with = "de_f64")]
+    pub last_price: f64,
+    #[serde(rename = "n", deserialize_with = "de_f64")]
+    pub commission: f64,
+    #[serde(rename = "N")]
+    pub commission_asset: Option<String>,
+    #[serde(rename = "T")]
+    pub transaction_time: u64,
+    /// `-1` unless this report is a trade.
+    #[serde(rename = "t")]
+    pub trade_id: i64,
+    #[serde(rename = "Z", deserialize_with = "de_f64")]
+    pub cumulative_quote_quantity: f64,
+}
+
+impl ExecutionReport {
+    /// The order as the order endpoints would return it after this report.
+    pub fn to_order_response(&self) -> OrderResponse {
+        OrderResponse {
+            symbol: self.symbol.clone(),
+            order_id: self.order_id,
+            client_order_id: self.order_client_id().to_string(),
+            price: self.price,
+            orig_qty: self.quantity,
+            executed_qty: self.cumulative_quantity,
+            cumulative_quote_qty: self.cumulative_quote_quantity,
+            status: self.status,
+            time_in_force: None,
+            order_type: self.order_type,
+            side: self.side,
+            stop_price: None,
+            transact_time: Some(self.transaction_time),
+            update_time: None,
+        }
+    }
+
+    /// The fill of a `TRADE` report, `None` for every other execution type.
+    pub fn to_execution(&self) -> Option<Execution> {
+        (self.execution_type == ExecutionType::Trade).then(|| Execution {
+            asset: split_symbol(&self.symbol).map_or(self.symbol.as_str(), |(base, _)| base).to_string(),
+            side: match self.side {
+                Side::Buy => backtester::Side::Buy,
+                Side::Sell => backtester::Side::Sell,
+            },
+            quantity: self.last_quantity,
+            price: self.last_price,
+            fee: self.commission,
+            fee_asset: self.commission_asset.clone().unwrap_or_default(),
+            time: self.transaction_time,
+        })
+    }
+
+    /// Client id the order was placed with.
+    pub fn order_client_id(&self) -> &str {
+        if self.orig_client_order_id.is_empty() {
+            &self.client_order_id
+        } else {
+            &self.orig_client_order_id
+        }
+    }
+}
+
+/// Balance of the account endpoint, the short names are used by the user data stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Balance {
+    #[serde(alias = "a")]
+    pub asset: String,
+    #[serde(alias = "f", deserialize_with = "de_f64")]
+    pub free: f64,
+    #[serde(alias = "l", deserialize_with = "de_f64")]
+    pub locked: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInfo {
+    pub can_trade: bool,
+    #[serde(default)]
+    pub update_time: u64,
+    pub balances: Vec<Balance>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MyTrade {
+    pub symbol: String,
+    pub id: u64,
+    pub order_id: u64,
+    #[serde(deserialize_with = "de_f64")]
+    pub price: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub qty: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub quote_qty: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub commission: f64,
+    pub commission_asset: String,
+    pub time: u64,
+    pub is_buyer: bool,
+    pub is_maker: bool,
+}
+
+impl MyTrade {
+    /// The fill in the form the accounting ledger books it.
+    pub fn to_execution(&self) -> Execution {
+        Execution {
+            asset: split_symbol(&self.symbol).map_or(self.symbol.as_str(), |(base, _)| base).to_string(),
+            side: if self.is_buyer { backtester::Side::Buy } else { backtester::Side::Sell },
+            quantity: self.qty,
+            price: self.price,
+            fee: self.commission,
+            fee_asset: self.commission_asset.clone(),
+            time: self.time,
+        }
+    }
+}
+
+/// Event of the user data stream, tagged by its `e` field.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "e", rename_all = "camelCase")]
+pub enum UserEvent {
+    ExecutionReport(ExecutionReport),
+    /// Balances of the assets that changed with an order.
+    OutboundAccountPosition(AccountPosition),
+    /// Deposit, withdrawal or transfer.
+    BalanceUpdate(BalanceUpdate),
+    /// The stream is gone, a new listen key is needed.
+    ListenKeyExpired {
+        #[serde(rename = "E")]
+        event_time: u64,
+    },
+    /// Not sent by the exchange: the stream (re)connected and events may have been missed since the last one.
+    #[serde(skip)]
+    Connected,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AccountPosition {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "u")]
+    pub last_update: u64,
+    #[serde(rename = "B")]
+    pub balances: Vec<Balance>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BalanceUpdate {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "a")]
+    pub asset: String,
+    #[serde(rename = "d", deserialize_with = "de_f64")]
+    pub delta: f64,
+    #[serde(rename = "T")]
+    pub clear_time: u64,
+}
+
+/// Trading rules of a symbol from `GET /api/v3/exchangeInfo`, zero where the exchange sets no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SymbolFilters {
+    pub tick_size: f64,
+    pub step_size: f64,
+    pub min_qty: f64,
+    pub max_qty: f64,
+    pub min_notional: f64,
+}
+
+impl SymbolFilters {
+    pub(crate) fn from_filters(filters: &[Filter]) -> Self {
+        filters.iter().fold(Self::default(), |acc, filter| match *filter {
+            Filter::Price { tick_size } => Self { tick_size, ..acc },
+            Filter::LotSize { min_qty, max_qty, step_size } => Self { step_size, min_qty, max_qty, ..acc },
+            Filter::Notional { min_notional } | Filter::MinNotional { min_notional } => Self { min_notional, ..acc },
+            Filter::Other => acc,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ExchangeInfo {
+    pub symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SymbolInfo {
+    pub symbol: String,
+    pub filters: Vec<Filter>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "filterType", rename_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum Filter {
+    #[serde(rename = "PRICE_FILTER", rename_all = "camelCase")]
+    Price {
+        #[serde(deserialize_with = "de_f64")]
+        tick_size: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    LotSize {
+        #[serde(deserialize_with = "de_f64")]
+        min_qty: f64,
+        #[serde(deserialize_with = "de_f64")]
+        max_qty: f64,
+        #[serde(deserialize_with = "de_f64")]
+        step_size: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Notional {
+        #[serde(deserialize_with = "de_f64")]
+        min_notional: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    MinNotional {
+        #[serde(deserialize_with = "de_f64")]
+        min_notional: f64,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ApiErrorBody {
+    pub code: i64,
+    pub msg: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ListenKey {
+    pub listen_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ServerTime {
+    pub server_time: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TickerPrice {
+    #[serde(deserialize_with = "de_f64")]
+    pub price: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Decimal {
+    Str(String),
+    Num(f64),
+}
+
+impl Decimal {
+    fn parse<E: serde::de::Error>(self) -> std::result::Result<f64, E> {
+        match self {
+            Decimal::Str(s) => s.parse().map_err(E::custom),
+            Decimal::Num(n) => Ok(n),
+        }
+    }
+}
+
+/// Binance sends decimals as strings to keep their precision, our own serialized state uses numbers.
+pub(crate) fn de_f64<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<f64, D::Error> {
+    Decimal::deserialize(deserializer)?.parse()
+}
+
+pub(crate) fn de_opt_f64<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<f64>, D::Error> {
+    Option::<Decimal>::deserialize(deserializer)?
+        .map(Decimal::parse)
+        .transpose()
+}
+
+const QUOTE_ASSETS: [&str; 5] = ["USDT", "USDC", "FDUSD", "BTC", "ETH"];
+
+/// Splits `BTCUSDT` into `("BTC", "USDT")`, `None` for unknown quote assets.
+pub fn split_symbol(symbol: &str) -> Option<(&str, &str)> {
+    QUOTE_ASSETS
+        .iter()
+        .find(|quote| symbol.len() > quote.len() && symbol.ends_with(*quote))
+        .map(|quote| symbol.split_at(symbol.len() - quote.len()))
+}
+
+/// Wire name of a unit enum variant, e.g. `"PARTIALLY_FILLED"`.
+pub(crate) fn enum_str<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// Inverse of [`enum_str`].
+pub(crate) fn enum_from_str<T: serde::de::DeserializeOwned>(s: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(s.to_string())).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_params() {
+        let order = NewOrder::limit("BTCUSDT", Side::Buy, 0.5, 42000.1).with_client_order_id("abc");
+        assert_eq!(
+            order.to_params(),
+            vec![
+                ("symbol", "BTCUSDT".to_string()),
+                ("side", "BUY".to_string()),
+                ("type", "LIMIT".to_string()),
+                ("timeInForce", "GTC".to_string()),
+                ("quantity", "0.5".to_string()),
+                ("price", "42000.1".to_string()),
+                ("newClientOrderId", "abc".to_string()),
+            ]
+        );
+        let stop = NewOrder::stop_loss("BTCUSDT", Side::Sell, 1.0, 40000.0);
+        assert!(stop.to_params().contains(&("type", "STOP_LOSS".to_string())));
+    }
+
+    #[test]
+    fn parse_order_response() {
+        let json = r#"{"symbol":"BTCUSDT","orderId":28,"orderListId":-1,"clientOrderId":"6gCrw2kRUAF9CvJDGP16IP","transactTime":1507725176595,"price":"0.00000000","origQty":"10.00000000","executedQty":"10.00000000","cummulativeQuoteQty":"10.00000000","status":"FILLED","timeInForce":"GTC","type":"MARKET","side":"SELL"}"#;
+        let order: OrderResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(order.order_id, 28);
+        assert_eq!(order.status, OrderStatus::Filled);
+        assert_eq!(order.executed_qty, 10.0);
+        assert_eq!(order.last_update(), 1507725176595);
+        assert_eq!(order.stop_price, None);
+
+        assert!(order.status.is_terminal());
+        assert!(!order.status.can_become(OrderStatus::Canceled));
+        assert!(OrderStatus::New.can_become(OrderStatus::PartiallyFilled));
+        assert!(!OrderStatus::PartiallyFilled.can_become(OrderStatus::New));
+
+        // our own serialization round-trips
+        let json = serde_json::to_string(&order).unwrap();
+        assert_eq!(serde_json::from_str::<OrderResponse>(&json).unwrap(), order);
+    }
+
+    #[test]
+    fn parse_execution_report() {
+        let json = r#"{"e":"executionReport","E":1499405658658,"s":"ETHBTC","c":"mUvoqJxFIILMdfAW5iGSOW","S":"BUY","o":"LIMIT","f":"GTC","q":"1.00000000","p":"0.10264410","P":"0.00000000","F":"0.00000000","g":-1,"C":"","x":"TRADE","X":"PARTIALLY_FILLED","r":"NONE","i":4293153,"l":"0.40000000","z":"0.40000000","L":"0.10264410","n":"0.00040000","N":"ETH","T":1499405658657,"t":42,"I":8641984,"w":false,"m":true,"M":false,"O":1499405658657,"Z":"0.04105764","Y":"0.04105764","Q":"0.00000000"}"#;
+        let report: ExecutionReport = serde_json::from_str(json).unwrap();
+        assert_eq!(report.execution_type, ExecutionType::Trade);
+        assert_eq!(report.status, OrderStatus::PartiallyFilled);
+        assert_eq!(report.order_client_id(), "mUvoqJxFIILMdfAW5iGSOW");
+        assert_eq!(report.last_quantity, 0.4);
+        assert_eq!(report.commission_asset.as_deref(), Some("ETH"));
+
+        let execution = report.to_execution().unwrap();
+        assert_eq!((execution.asset.as_str(), execution.quantity, execution.fee), ("ETH", 0.4, 0.0004));
+        let order = report.to_order_response();
+        assert_eq!((order.executed_qty, order.status), (0.4, OrderStatus::PartiallyFilled));
+
+        match serde_json::from_str(json).unwrap() {
+            UserEvent::ExecutionReport(event) => assert_eq!(event, report),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_user_events() {
+        let position = r#"{"e":"outboundAccountPosition","E":1564034571105,"u":1564034571073,"B":[{"a":"ETH","f":"10000.000000","l":"0.000000"}]}"#;
+        match serde_json::from_str(position).unwrap() {
+            UserEvent::OutboundAccountPosition(position) => {
+                assert_eq!(position.balances, vec![Balance { asset: "ETH".to_string(), free: 10_000.0, locked: 0.0 }]);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let update = r#"{"e":"balanceUpdate","E":1573200697110,"a":"BTC","d":"100.00000000","T":1573200697068}"#;
+        match serde_json::from_str(update).unwrap() {
+            UserEvent::BalanceUpdate(update) => assert_eq!((update.asset.as_str(), update.delta), ("BTC", 100.0)),
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let expired: UserEvent = serde_json::from_str(r#"{"e":"listenKeyExpired","E":1576653824250,"listenKey":"OfYGbUzi3PraNagEkdKuFwUHn48brFsItTdsuiIXrucEvD0rhRXZ7I6URWfE8YE8"}"#).unwrap();
+        assert_eq!(expired, UserEvent::ListenKeyExpired { event_time: 1576653824250 });
+        let unknown: UserEvent = serde_json::from_str(r#"{"e":"externalLockUpdate","E":1581557507324,"a":"NEO","d":"10.00000000"}"#).unwrap();
+        assert_eq!(unknown, UserEvent::Unknown);
+    }
+
+    #[test]
+    fn parse_symbol_filters() {
+        let info: SymbolInfo = serde_json::from_str(
+            r#"{
+                "symbol": "BTCUSDT",
+                "filters": [
+                    { "filterType": "PRICE_FILTER", "minPrice": "0.01", "maxPrice": "1000000.00", "tickSize": "0.01" },
+                    { "filterType": "LOT_SIZE", "minQty": "0.00001", "maxQty": "9000.0", "stepSize": "0.00001" },
+                    { "filterType": "ICEBERG_PARTS", "limit": 10 },
+                    { "filterType": "NOTIONAL", "minNotional": "5.0", "applyMinToMarket": true }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let filters = SymbolFilters::from_filters(&info.filters);
+        assert_eq!(filters, SymbolFilters { tick_size: 0.01, step_size: 0.00001, min_qty: 0.00001, max_qty: 9000.0, min_notional: 5.0 });
+    }
+}
@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, info, warn};
+
+use crate::client::TradingClient;
+use crate::error::{ApiErrorCode, Error, Result};
+use crate::types::UserEvent;
+
+pub const BINANCE_WS_API: &str = "wss://stream.binance.com:9443/ws";
+
+/// Binance user data stream: order, balance and account updates of the account behind the API key.
+///
+/// The listen key is kept alive every 30 minutes and replaced when the exchange forgets it. Dropped
+/// connections are reopened with a growing delay, each (re)connect is announced with
+/// [`UserEvent::Connected`] so the receiver can reconcile what it missed. A connection without any
+/// traffic, not even the pings Binance sends every few minutes, is given up as dead.
+pub struct UserStream {
+    client: TradingClient,
+    ws_url: String,
+    keepalive: Duration,
+    idle_timeout: Duration,
+    reconnect_delay: Duration,
+    max_reconnect_delay: Duration,
+}
+
+impl UserStream {
+    pub fn new(client: TradingClient) -> Self {
+        Self {
+            client,
+            ws_url: BINANCE_WS_API.to_string(),
+            keepalive: Duration::from_secs(30 * 60),
+            idle_timeout: Duration::from_secs(10 * 60),
+            reconnect_delay: Duration::from_secs(1),
+            max_reconnect_delay: Duration::from_secs(60),
+        }
+    }
+
+    /// Points the stream at another server, e.g. the testnet or a local mock.
+    pub fn with_ws_url(mut self, ws_url: &str) -> Self {
+        self.ws_url = ws_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_keepalive(mut self, keepalive: Duration) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    /// How long the connection may stay silent before it is dropped and reopened.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// First delay before reconnecting, doubled after every failed attempt up to `max`.
+    pub fn with_reconnect_delay(mut self, delay: Duration, max: Duration) -> Self {
+        self.reconnect_delay = delay;
+        self.max_reconnect_delay = max;
+        self
+    }
+
+    /// Forwards the events to `sender` until the receiver is dropped, reconnecting as often as needed.
+    pub async fn run(&self, sender: mpsc::Sender<UserEvent>) -> Result<()> {
+        let mut delay = self.reconnect_delay;
+        loop {
+            match self.connect(&sender).await {
+                Ok(Session::ReceiverGone) => return Ok(()),
+                Ok(Session::Ended) => {
+                    delay = self.reconnect_delay;
+                    info!("User data stream ended, reconnecting in {delay:?}");
+                }
+                Err(e) => warn!("User data stream failed: {e}, reconnecting in {delay:?}"),
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(self.max_reconnect_delay);
+        }
+    }
+
+    /// One listen key and connection, the key is closed once either goes away.
+    async fn connect(&self, sender: &mpsc::Sender<UserEvent>) -> Result<Session> {
+        let listen_key = self.client.start_user_stream().await?;
+        let session = self.session(&listen_key, sender).await;
+        if let Err(e) = self.client.close_user_stream(&listen_key).await {
+            debug!("Could not close the listen key: {e}");
+        }
+        session
+    }
+
+    async fn session(&self, listen_key: &str, sender: &mpsc::Sender<UserEvent>) -> Result<Session> {
+        let (mut ws, _) = connect_async(format!("{}/{listen_key}", self.ws_url)).await?;
+        info!("User data stream connected");
+        if sender.send(UserEvent::Connected).await.is_err() {
+            return Ok(Session::ReceiverGone);
+        }
+
+        let mut keepalive = tokio::time::interval_at(tokio::time::Instant::now() + self.keepalive, self.keepalive);
+        let idle = tokio::time::sleep(self.idle_timeout);
+        tokio::pin!(idle);
+        loop {
+            tokio::select! {
+                _ = sender.closed() => return Ok(Session::ReceiverGone),
+                () = &mut idle => {
+                    warn!("No traffic on the user data stream for {:?}", self.idle_timeout);
+                    return Ok(Session::Ended);
+                }
+                _ = keepalive.tick() => match self.client.keepalive_user_stream(listen_key).await {
+                    Ok(()) => debug!("Listen key kept alive"),
+                    Err(Error::Api { code: ApiErrorCode::InvalidListenKey, .. }) => {
+                        warn!("Listen key expired");
+                        return Ok(Session::Ended);
+                    }
+                    Err(e) => warn!("Could not keep the listen key alive: {e}"),
+                },
+                message = ws.next() => {
+                    idle.as_mut().reset(tokio::time::Instant::now() + self.idle_timeout);
+                    let text = match message {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(frame))) => {
+                            info!("User data stream closed: {frame:?}");
+                            return Ok(Session::Ended);
+                        }
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => {
+                            warn!("User data stream error: {e}");
+                            return Ok(Session::Ended);
+                        }
+                        None => return Ok(Session::Ended),
+                    };
+                    let event = match serde_json::from_str::<UserEvent>(&text) {
+                        Ok(UserEvent::ListenKeyExpired { .. }) => {
+                            warn!("Listen key expired");
+                            return Ok(Session::Ended);
+                        }
+                        Ok(UserEvent::Unknown) => continue,
+                        Ok(event) => event,
+                        Err(e) => {
+                            warn!("Could not parse user data stream message: {e}");
+                            continue;
+                        }
+                    };
+                    if sender.send(event).await.is_err() {
+                        return Ok(Session::ReceiverGone);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How a connection ended.
+enum Session {
+    /// The connection was up and dropped, or the listen key expired.
+    Ended,
+    ReceiverGone,
+}
@@ -0,0 +1,110 @@
+use mock_exchange::{Faults, MockConfig, MockExchange};
+use trader::{ApiErrorCode, Error, Exposure, NewOrder, OrderId, OrderStatus, RiskManager, Side, TradingClient};
+
+async fn setup() -> (MockExchange, TradingClient) {
+    let mock = MockExchange::start(MockConfig::default()).await.unwrap();
+    let config = mock.config();
+    let client = TradingClient::new(&config.api_key, &config.secret_key).with_base_url(&mock.url());
+    (mock, client)
+}
+
+#[tokio::test]
+async fn order_lifecycle() {
+    let (mock, client) = setup().await;
+
+    let offset = client.sync_time().await.unwrap();
+    assert_eq!(offset, client.time_offset());
+
+    let market = client.new_order(&NewOrder::market("BTCUSDT", Side::Buy, 0.01)).await.unwrap();
+    assert_eq!(market.status, OrderStatus::Filled);
+    assert_eq!(market.executed_qty, 0.01);
+
+    let limit = NewOrder::limit("BTCUSDT", Side::Sell, 0.01, 50000.0).with_client_order_id("my-limit");
+    let limit = client.new_order(&limit).await.unwrap();
+    assert_eq!(limit.status, OrderStatus::New);
+    assert_eq!(limit.price, 50000.0);
+
+    let open = client.open_orders(Some("BTCUSDT")).await.unwrap();
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].client_order_id, "my-limit");
+
+    assert!(mock.fill_order(limit.order_id, 0.004, 50000.0));
+    let queried = client.query_order("BTCUSDT", &OrderId::Client("my-limit".to_string())).await.unwrap();
+    assert_eq!(queried.order_id, limit.order_id);
+    assert_eq!(queried.status, OrderStatus::PartiallyFilled);
+    assert_eq!(queried.executed_qty, 0.004);
+
+    let canceled = client.cancel_order("BTCUSDT", &OrderId::Exchange(limit.order_id)).await.unwrap();
+    assert_eq!(canceled.status, OrderStatus::Canceled);
+    assert!(client.open_orders(None).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn account_and_trades() {
+    let (_mock, client) = setup().await;
+    client.new_order(&NewOrder::market("BTCUSDT", Side::Sell, 0.5)).await.unwrap();
+
+    let balances = client.balances().await.unwrap();
+    assert_eq!(balances.iter().map(|b| b.asset.as_str()).collect::<Vec<_>>(), vec!["BTC", "USDT"]);
+    assert_eq!(balances[0].free, 0.5);
+
+    let trades = client.my_trades("BTCUSDT", None).await.unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].qty, 0.5);
+    assert!(!trades[0].is_buyer);
+    assert_eq!(balances[1].free, 10_000.0 + trades[0].quote_qty);
+}
+
+#[tokio::test]
+async fn orders_follow_symbol_filters() {
+    let (_mock, client) = setup().await;
+    let filters = client.symbol_filters("BTCUSDT").await.unwrap();
+    assert_eq!((filters.tick_size, filters.step_size, filters.min_notional), (0.01, 0.00001, 5.0));
+    assert!(client.symbol_filters("NOPE").await.is_err());
+
+    let mut risk = RiskManager::default().with_filters("BTCUSDT", filters);
+    let exposure = Exposure { price: 40_000.0, ..Exposure::default() };
+    let order = risk.check(NewOrder::limit("BTCUSDT", Side::Buy, 0.0012345678, 39_999.999), &exposure, 0).unwrap();
+    let placed = client.new_order(&order).await.unwrap();
+    assert_eq!((placed.orig_qty, placed.price), (0.00123, 40_000.0));
+    assert!(matches!(risk.check(NewOrder::market("BTCUSDT", Side::Buy, 0.0001), &exposure, 0), Err(Error::Risk(_))));
+}
+
+#[tokio::test]
+async fn api_errors_are_mapped() {
+    let (mock, client) = setup().await;
+
+    match client.new_order(&NewOrder::market("NOPE", Side::Buy, 1.0)).await {
+        Err(Error::Api { status: 400, code: ApiErrorCode::InvalidSymbol, .. }) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+    match client.new_order(&NewOrder::market("BTCUSDT", Side::Buy, 100.0)).await {
+        Err(Error::Api { code: ApiErrorCode::NewOrderRejected, .. }) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+    match client.cancel_order("BTCUSDT", &OrderId::Exchange(99)).await {
+        Err(Error::Api { code: ApiErrorCode::CancelRejected, .. }) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+
+    let wrong_secret = TradingClient::new(&mock.config().api_key, "other").with_base_url(&mock.url());
+    match wrong_secret.account().await {
+        Err(Error::Api { code: ApiErrorCode::InvalidSignature, .. }) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+
+    let wrong_key = TradingClient::new("other", &mock.config().secret_key).with_base_url(&mock.url());
+    match wrong_key.account().await {
+        Err(err @ Error::Api { code: ApiErrorCode::RejectedApiKey, .. }) => assert!(!err.is_retryable()),
+        other => panic!("unexpected {other:?}"),
+    }
+
+    mock.set_faults(Faults { rate_limit: Some(0), ..Faults::default() });
+    match client.account().await {
+        Err(err @ Error::Api { status: 429, code: ApiErrorCode::TooManyRequests, .. }) => assert!(err.is_retryable()),
+        other => panic!("unexpected {other:?}"),
+    }
+
+    mock.set_faults(Faults { malformed: true, ..Faults::default() });
+    assert!(matches!(client.account().await, Err(Error::Json(_))));
+}
@@ -0,0 +1,263 @@
+use std::time::Duration;
+
+use data_downloader::Kline;
+use mock_exchange::{Faults, MockConfig, MockExchange};
+use tokio::sync::mpsc;
+use trader::{Account, ExecutionType, NewOrder, OrderManager, OrderStatus, Side, Strategy, Trader, TradingClient, UserEvent, UserStream};
+
+fn client(mock: &MockExchange) -> TradingClient {
+    let config = mock.config();
+    TradingClient::new(&config.api_key, &config.secret_key).with_base_url(&mock.url())
+}
+
+fn start(mock: &MockExchange) -> mpsc::Receiver<UserEvent> {
+    let stream = UserStream::new(client(mock))
+        .with_ws_url(&mock.ws_url())
+        .with_keepalive(Duration::from_millis(50))
+        .with_reconnect_delay(Duration::from_millis(10), Duration::from_millis(100));
+    let (sender, receiver) = mpsc::channel(64);
+    tokio::spawn(async move { stream.run(sender).await });
+    receiver
+}
+
+async fn next(events: &mut mpsc::Receiver<UserEvent>) -> UserEvent {
+    tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .expect("no user event within 5s")
+        .expect("user stream stopped")
+}
+
+#[tokio::test]
+async fn decodes_account_events() {
+    let mock = MockExchange::start(MockConfig::default()).await.unwrap();
+    let mut events = start(&mock);
+    assert_eq!(next(&mut events).await, UserEvent::Connected);
+
+    let order = client(&mock).new_order(&NewOrder::limit("BTCUSDT", Side::Buy, 0.1, 30_000.0)).await.unwrap();
+    match next(&mut events).await {
+        UserEvent::ExecutionReport(report) => {
+            assert_eq!((report.order_id, report.execution_type, report.status), (order.order_id, ExecutionType::New, OrderStatus::New));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    // outlives a few keepalives
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(mock.fill_order(order.order_id, 0.1, 30_000.0));
+    match next(&mut events).await {
+        UserEvent::ExecutionReport(report) => {
+            assert_eq!((report.execution_type, report.status), (ExecutionType::Trade, OrderStatus::Filled));
+            assert_eq!((report.last_quantity, report.last_price), (0.1, 30_000.0));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match next(&mut events).await {
+        UserEvent::OutboundAccountPosition(position) => {
+            let usdt = position.balances.iter().find(|b| b.asset == "USDT").unwrap();
+            assert_eq!(usdt.free, 10_000.0 - 3_000.0);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    mock.deposit("BNB", 2.0);
+    match next(&mut events).await {
+        UserEvent::BalanceUpdate(update) => assert_eq!((update.asset.as_str(), update.delta), ("BNB", 2.0)),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn reconnects_after_expiry_and_drops() {
+    let mock = MockExchange::start(MockConfig::default()).await.unwrap();
+    let mut events = start(&mock);
+    assert_eq!(next(&mut events).await, UserEvent::Connected);
+
+    mock.expire_listen_keys();
+    assert_eq!(next(&mut events).await, UserEvent::Connected);
+    assert_eq!(mock.listen_keys(), 1);
+
+    // every connection now drops on its second message
+    mock.set_faults(Faults { disconnect_after: Some(1), ..Faults::default() });
+    mock.deposit("BNB", 1.0);
+    assert!(matches!(next(&mut events).await, UserEvent::BalanceUpdate(_)));
+    mock.deposit("BNB", 2.0);
+    assert_eq!(next(&mut events).await, UserEvent::Connected);
+    mock.deposit("BNB", 3.0);
+    match next(&mut events).await {
+        UserEvent::BalanceUpdate(update) => assert_eq!(update.delta, 3.0),
+        other => panic!("unexpected {other:?}"),
+    }
+
+    drop(events);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(mock.listen_keys(), 0);
+}
+
+#[tokio::test]
+async fn reconnects_when_the_connection_goes_silent() {
+    let mock = MockExchange::start(MockConfig::default()).await.unwrap();
+    let stream = UserStream::new(client(&mock))
+        .with_ws_url(&mock.ws_url())
+        .with_idle_timeout(Duration::from_millis(200))
+        .with_reconnect_delay(Duration::from_millis(10), Duration::from_millis(100));
+    let (sender, mut events) = mpsc::channel(64);
+    tokio::spawn(async move { stream.run(sender).await });
+    assert_eq!(next(&mut events).await, UserEvent::Connected);
+
+    // traffic keeps the connection
+    for _ in 0..3 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        mock.deposit("BNB", 1.0);
+        assert!(matches!(next(&mut events).await, UserEvent::BalanceUpdate(_)));
+    }
+    // silence drops it, the stream reconnects with a new listen key
+    assert_eq!(next(&mut events).await, UserEvent::Connected);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(mock.listen_keys(), 1);
+}
+
+/// Bids once, below the market.
+struct Bidder {
+    done: bool,
+}
+
+impl Strategy for Bidder {
+    fn on_candle(&mut self, candle: &Kline, _account: &Account) -> Vec<NewOrder> {
+        if std::mem::replace(&mut self.done, true) {
+            return Vec::new();
+        }
+        vec![NewOrder::limit("BTCUSDT", Side::Buy, 0.1, (candle.close * 0.9).round())]
+    }
+}
+
+#[tokio::test]
+async fn feeds_orders_and_ledger() {
+    let mock = MockExchange::start(MockConfig::default()).await.unwrap();
+    let mut events = start(&mock);
+    let mut trader = Trader::new(client(&mock), Bidder { done: false }, OrderManager::in_memory().unwrap(), "BTCUSDT");
+    trader.on_user_event(next(&mut events).await).await.unwrap();
+
+    let candle = Kline {
+        open_time: 0,
+        open: 40_000.0,
+        high: 40_000.0,
+        low: 40_000.0,
+        close: 40_000.0,
+        volume: 1.0,
+        close_time: 59_999,
+        quote_asset_volume: 40_000.0,
+        trade_number: 1,
+        buy_base: 0.5,
+        buy_quote: 20_000.0,
+    };
+    trader.on_candle(&candle).await.unwrap();
+    let order = trader.orders().open_orders(None)[0].clone();
+    assert_eq!(order.status, OrderStatus::New);
+
+    assert!(mock.fill_order(order.exchange_id.unwrap(), 0.1, 36_000.0));
+    // NEW, TRADE and the account position
+    for _ in 0..3 {
+        trader.on_user_event(next(&mut events).await).await.unwrap();
+    }
+    let filled = trader.orders().get(order.client_order_id()).unwrap().unwrap();
+    assert_eq!((filled.status, filled.executed_qty), (OrderStatus::Filled, 0.1));
+    let position = trader.ledger().position("BTC").unwrap();
+    assert_eq!(position.avg_entry, 36_000.0);
+    assert_eq!(trader.ledger().balance("USDT"), 10_000.0 - 3_600.0);
+
+    mock.deposit("USDT", 500.0);
+    trader.on_user_event(next(&mut events).await).await.unwrap();
+    assert_eq!(trader.ledger().balance("USDT"), 10_000.0 - 3_600.0 + 500.0);
+}
+
+#[tokio::test]
+async fn prices_fees_paid_in_bnb() {
+    let mut config = MockConfig::default();
+    config.balances.push(("BNB".to_string(), 2.0));
+    let bnb = serde_json::json!([0, "600.00000000", "600.00000000", "600.00000000", "600.00000000", "1.00000000", 3_599_999, "600.00000000", 1, "0.50000000", "300.00000000", "0"]);
+    config.klines.insert(("BNBUSDT".to_string(), "1h".to_string()), vec![bnb]);
+    let mock = MockExchange::start(config).await.unwrap();
+
+    // a fill paying its commission in BNB prices BNB before booking the fee
+    let mut trader = Trader::new(client(&mock), Bidder { done: true }, OrderManager::in_memory().unwrap(), "BTCUSDT");
+    trader.on_user_event(UserEvent::Connected).await.unwrap();
+    let report: UserEvent = serde_json::from_value(serde_json::json!({
+        "e": "executionReport", "E": 1, "s": "BTCUSDT", "c": "bnb-fee", "S": "BUY", "o": "LIMIT", "f": "GTC",
+        "q": "0.10000000", "p": "40000.00000000", "P": "0.00000000", "x": "TRADE", "X": "FILLED", "r": "NONE",
+        "i": 7, "l": "0.10000000", "z": "0.10000000", "L": "40000.00000000", "n": "0.01000000", "N": "BNB",
+        "T": 1, "t": 1, "O": 1, "Z": "4000.00000000",
+    }))
+    .unwrap();
+    trader.on_user_event(report).await.unwrap();
+    assert_eq!(trader.ledger().price("BNB"), Some(600.0));
+    assert!((trader.ledger().fees() - 6.0).abs() < 1e-9);
+
+    // BNB held without trading it is priced on every candle
+    let mut trader = Trader::new(client(&mock), Bidder { done: true }, OrderManager::in_memory().unwrap(), "BTCUSDT");
+    trader.on_user_event(UserEvent::Connected).await.unwrap();
+    let candle = Kline {
+        open_time: 0,
+        open: 40_000.0,
+        high: 40_000.0,
+        low: 40_000.0,
+        close: 40_000.0,
+        volume: 1.0,
+        close_time: 59_999,
+        quote_asset_volume: 40_000.0,
+        trade_number: 1,
+        buy_base: 0.5,
+        buy_quote: 20_000.0,
+    };
+    trader.on_candle(&candle).await.unwrap();
+    assert_eq!(trader.ledger().price("BNB"), Some(600.0));
+    assert!((trader.ledger().equity() - (40_000.0 + 10_000.0 + 2.0 * 600.0)).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn keeps_trading_through_rate_limits() {
+    let mock = MockExchange::start(MockConfig::default()).await.unwrap();
+    let mut trader = Trader::new(client(&mock), Bidder { done: false }, OrderManager::in_memory().unwrap(), "BTCUSDT");
+    let (sender, receiver) = mpsc::channel(8);
+    let run = tokio::spawn(async move { trader.run(receiver).await.map(|()| trader) });
+    let candle = |i: u64| Kline {
+        open_time: i * 60_000,
+        open: 40_000.0,
+        high: 40_000.0,
+        low: 40_000.0,
+        close: 40_000.0,
+        volume: 1.0,
+        close_time: i * 60_000 + 59_999,
+        quote_asset_volume: 40_000.0,
+        trade_number: 1,
+        buy_base: 0.5,
+        buy_quote: 20_000.0,
+    };
+
+    sender.send(candle(0)).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while mock.orders().is_empty() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("no order within 5s");
+
+    // the next candle runs into a 429
+    let served = mock.request_count();
+    mock.set_faults(Faults { rate_limit: Some(0), ..Faults::default() });
+    sender.send(candle(1)).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while mock.request_count() == served {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("no request within 5s");
+    mock.set_faults(Faults::default());
+    sender.send(candle(2)).await.unwrap();
+    drop(sender);
+
+    let trader = run.await.unwrap().expect("the rate limit stopped trading");
+    assert!(mock.request_count() > served + 1);
+    assert_eq!(trader.orders().open_orders(None).len(), 1);
+}